@@ -0,0 +1,175 @@
+//! Checks of a configuration against the machine it runs on.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::compdb::{self, CompilationEntry};
+use crate::conf::CompDBConf;
+
+/// Where `program` would be run from: itself if it names a path, otherwise
+/// the first match on `PATH`.
+pub fn find_executable(program : &str) -> Option<PathBuf> {
+    let candidates = |path : PathBuf| {
+        let mut candidates = vec![path.clone()];
+        if cfg!(windows) && path.extension().is_none() {
+            candidates.push(path.with_extension("exe"));
+        }
+        candidates
+    };
+
+    if program.contains(['/', '\\']) {
+        return candidates(PathBuf::from(program)).into_iter().find(|p| p.is_file());
+    }
+    std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default())
+        .flat_map(|dir| candidates(dir.join(program)))
+        .find(|p| p.is_file())
+}
+
+/// The first dotted number in `text`, e.g. `12.2.0` in gcc's banner.
+fn first_version(text : &str) -> Option<Vec<u64>> {
+    let version = regex::Regex::new(r"\d+(\.\d+)+").unwrap();
+    Some(version.find(text)?.as_str().split('.').filter_map(|n| n.parse().ok()).collect())
+}
+
+/// Whether `version` satisfies `constraint`: comma-separated clauses of
+/// `>=`, `<=`, `>`, `<` or `=` and a version; a bare version is a prefix
+/// match, so `12` accepts any 12.x.
+fn satisfies(version : &[u64], constraint : &str) -> Result<bool, String> {
+    for clause in constraint.split(',').map(|c| c.trim()).filter(|c| !c.is_empty()) {
+        let operator_len = clause.find(|c : char| c.is_ascii_digit()).ok_or_else(|| format!("invalid version constraint \"{}\"", constraint))?;
+        let (operator, wanted) = clause.split_at(operator_len);
+        let wanted : Vec<u64> = wanted.split('.').map(|n| n.parse().map_err(|_| format!("invalid version constraint \"{}\"", constraint))).collect::<Result<_, _>>()?;
+        let ordering = version.iter().take(wanted.len()).cmp(wanted.iter());
+        let ok = match operator.trim() {
+            "" | "=" | "==" => ordering.is_eq(),
+            ">=" => ordering.is_ge(),
+            "<=" => ordering.is_le(),
+            ">" => ordering.is_gt(),
+            "<" => ordering.is_lt(),
+            other => return Err(format!("invalid operator \"{}\" in version constraint \"{}\"", other, constraint)),
+        };
+        if !ok {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Checks that the configured compilers exist and, if `expected_version`
+/// is set, that their `--version` satisfies it. Missing compilers are
+/// errors; version mismatches are warnings.
+pub fn verify_compilers(conf : &CompDBConf) -> Result<(), String> {
+    for (key, command) in [("c_compiler", &conf.common.c_compiler), ("cpp_compiler", &conf.common.cpp_compiler)] {
+        let command = compdb::strip_compiler_wrappers(command);
+        let Some(program) = command.first() else {
+            return Err(format!("common.{} is empty", key));
+        };
+        let Some(executable) = find_executable(program) else {
+            return Err(format!("common.{}: \"{}\" not found", key, program));
+        };
+
+        let output = Command::new(&executable).arg("--version").output().map_err(|e| format!("common.{}: {}: {}", key, executable.display(), e))?;
+        let banner = String::from_utf8_lossy(&output.stdout).into_owned() + &String::from_utf8_lossy(&output.stderr);
+        let version = first_version(&banner);
+        let shown = version.as_ref().map(|v| v.iter().map(|n| n.to_string()).collect::<Vec<_>>().join("."));
+        eprintln!("{}: {} ({})", key, executable.display(), shown.as_deref().unwrap_or("unknown version"));
+
+        if let Some(expected) = &conf.common.expected_version {
+            match version {
+                Some(version) if !satisfies(&version, expected)? => crate::diag::warn(&format!("common.{}: version {} does not satisfy expected_version \"{}\"", key, shown.unwrap(), expected)),
+                None => crate::diag::warn(&format!("common.{}: cannot tell the version of {} from --version", key, program)),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs the compiler of `entry` with `-fsyntax-only` in place of
+/// producing an object. On failure returns the compiler's diagnostics.
+pub fn syntax_check(entry : &CompilationEntry) -> Result<(), String> {
+    let mut argv = analysis_argv(entry);
+    argv.push("-fsyntax-only".into());
+
+    let (program, arguments) = argv.split_first().ok_or("empty command")?;
+    let output = Command::new(program).args(arguments).current_dir(&entry.directory).output().map_err(|e| format!("{}: {}", program, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim_end().into())
+    }
+}
+
+/// The entry's argument vector without `-c` and `-o`, for tools that parse
+/// the file instead of compiling it.
+pub fn analysis_argv(entry : &CompilationEntry) -> Vec<String> {
+    let mut argv = Vec::<String>::new();
+    let mut arguments = entry.argv().into_iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-c" => {}
+            "-o" => { arguments.next(); }
+            _ if argument.starts_with("-o") && argument.len() > 2 => {}
+            _ => argv.push(argument),
+        }
+    }
+    argv
+}
+
+/// `count` entries spread evenly over `entries`, all of them if there are
+/// fewer; the same subset every run so failures are reproducible.
+pub fn sample(entries : Vec<CompilationEntry>, count : Option<usize>) -> Vec<CompilationEntry> {
+    match count {
+        Some(count) if count < entries.len() => {
+            let step = entries.len() as f64 / count as f64;
+            let picked : Vec<usize> = (0..count).map(|i| (i as f64 * step) as usize).collect();
+            entries.into_iter().enumerate().filter(|(i, _)| picked.contains(i)).map(|(_, e)| e).collect()
+        }
+        _ => entries,
+    }
+}
+
+/// A diagnostic `clangd --check` reported, e.g. code `pp_file_not_found`.
+pub struct ClangdDiagnostic {
+    pub code : String,
+    pub message : String,
+    pub line : Option<u64>,
+}
+
+impl ClangdDiagnostic {
+    /// The diagnostics `verify --clangd` aggregates: headers clangd cannot
+    /// find, flags its driver does not know, and files it failed to check.
+    pub fn kind(&self) -> Option<&'static str> {
+        match self.code.as_str() {
+            CLANGD_FAILED => Some("check failed"),
+            "pp_file_not_found" | "pp_file_not_found_angled_include_not_fatal" => Some("missing header"),
+            "drv_unknown_argument" | "drv_unknown_argument_with_suggestion" | "drv_unsupported_opt" | "drv_unsupported_opt_for_target" => Some("unknown flag"),
+            _ => None,
+        }
+    }
+}
+
+/// The code of the diagnostic added when `clangd --check` exits with an error.
+const CLANGD_FAILED : &str = "clangd_check_failed";
+
+/// Runs `clangd --check` on the file of `entry`, reading the database in
+/// `db_dir`. Returns the error diagnostics clangd logged, and one more when
+/// it failed.
+pub fn clangd_check(clangd : &str, db_dir : &Path, entry : &CompilationEntry) -> Result<Vec<ClangdDiagnostic>, String> {
+    let output = Command::new(clangd)
+        .arg(format!("--compile-commands-dir={}", db_dir.display()))
+        .arg(format!("--check={}", entry.path().display()))
+        .output()
+        .map_err(|e| format!("{}: {}", clangd, e))?;
+
+    // E[12:34:56.789] [pp_file_not_found] Line 3: 'hal.h' file not found
+    let diagnostic = regex::Regex::new(r"^E\[[^\]]*\] \[([A-Za-z0-9_-]+)\] (?:Line (\d+): )?(.*)$").unwrap();
+    let mut diagnostics : Vec<_> = String::from_utf8_lossy(&output.stderr).lines()
+        .filter_map(|line| diagnostic.captures(line))
+        .map(|c| ClangdDiagnostic { code : c[1].into(), message : c[3].into(), line : c.get(2).and_then(|line| line.as_str().parse().ok()) })
+        .collect();
+    if !output.status.success() {
+        diagnostics.push(ClangdDiagnostic { code : CLANGD_FAILED.into(), message : format!("clangd --check failed ({})", output.status), line : None });
+    }
+    Ok(diagnostics)
+}
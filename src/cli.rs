@@ -0,0 +1,1556 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::engine::{ArgValueCompleter, CompletionCandidate};
+use serde::Serialize;
+
+use crate::{check, compdb, conf, db, diag, export, generate, graph, hooks, import, includes, init, intercept, run, sarif, serve};
+use crate::conf::{CompDBConf, EffectiveConf, WorkSpaceConf, load_conf};
+use crate::generate::{Generation, list_include_dirs, list_target_files};
+use crate::plugin::Registry;
+
+/// The configuration files given on the command line, or the nearest one found.
+fn config_paths(given : &[String]) -> Result<Vec<String>, String> {
+    if given.is_empty() {
+        Ok(vec![generate::lossy(&conf::find_conf()?)])
+    } else {
+        Ok(given.to_vec())
+    }
+}
+
+/// Names and paths of the workspaces of the configuration found from the
+/// current directory, for shell completion.
+fn complete_workspaces(current : &std::ffi::OsStr) -> Vec<CompletionCandidate> {
+    let Some(conf) = conf::find_conf().ok().and_then(|path| load_conf(&[path.to_str()?.into()], None, &[]).ok()) else {
+        return Vec::new();
+    };
+    let current = current.to_string_lossy();
+    conf.workspace.iter()
+        .flat_map(|w| w.name.iter().chain(std::iter::once(&w.path)))
+        .filter(|id| id.starts_with(current.as_ref()))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+fn select_workspaces<'a>(conf : &'a CompDBConf, only : &[String], exclude : &[String]) -> Result<Vec<&'a WorkSpaceConf>, String> {
+    for id in only.iter().chain(exclude) {
+        if !conf.workspace.iter().any(|w| w.is_called(id)) {
+            return Err(format!("No workspace matches \"{}\"", id));
+        }
+    }
+
+    Ok(conf.workspace.iter()
+        .filter(|w| only.is_empty() || only.iter().any(|id| w.is_called(id)))
+        .filter(|w| !exclude.iter().any(|id| w.is_called(id)))
+        .collect())
+}
+
+fn generate(args : &GenerateArgs, registry : &Registry) -> Result<(), String> {
+    let result = generate_db(args, registry);
+    if let Some(path) = &args.sarif {
+        sarif::write(path, vec![sarif::own_run(result.as_ref().err().map(String::as_str))])?;
+    }
+    result
+}
+
+fn generate_db(args : &GenerateArgs, registry : &Registry) -> Result<(), String> {
+    let (inputs, output) = args.inputs_and_output()?;
+    let _lock = compdb::lock_output(Path::new(&output), args.wait_for_lock)?;
+    let mut conf = load_conf(&inputs, args.config_format, &args.set)?;
+    conf.select_variant(args.test)?;
+    if args.verify_compilers {
+        check::verify_compilers(&conf)?;
+    }
+    // Hooks run from the root, so they get the database as an absolute path.
+    let output_path = match output.as_str() {
+        "-" => output.clone(),
+        _ => std::path::absolute(&output).map_err(|e| format!("{}: {}", output, e))?.to_string_lossy().into_owned(),
+    };
+    hooks::run("pre", conf.hooks.pre.as_deref().unwrap_or_default(), Path::new(&conf.common.root_dir), &output_path)?;
+    let workspaces = select_workspaces(&conf, &args.only, &args.exclude)?;
+    let quiet = args.quiet || args.hook;
+    let fingerprint_path = format!("{}.fingerprint", output);
+    let indent = if args.compact { None } else { Some(conf.common.indent.unwrap_or(2)) };
+    let write_options = compdb::WriteOptions { indent, compress : args.compress, keep_plain : args.keep_plain, chunk_size : args.chunk_size };
+    let mut generation = Generation::new(args.strict, conf.policy.clone());
+    if let Some(git_ref) = &args.changed_since {
+        generation.changed_since = Some((git_ref.clone(), generate::changed_files(Path::new(&conf.common.root_dir), git_ref)?));
+    }
+    let fingerprint = if args.hook {
+        if output == "-" {
+            return Err("--hook needs an output file, not stdout".into());
+        }
+        let fingerprint = generate::fingerprint(&inputs, &conf, &workspaces, &mut generation)?;
+        let written = match &args.exporter {
+            Some(_) => Path::new(&output).exists(),
+            None => compdb::written_files(Path::new(&output), &write_options).is_some(),
+        };
+        if written && std::fs::read_to_string(&fingerprint_path).is_ok_and(|cached| cached.trim() == fingerprint) {
+            return Ok(());
+        }
+        Some(fingerprint)
+    } else {
+        None
+    };
+    // Log lines would tear the bars, so they are only drawn while logging is quiet.
+    if !quiet && !tracing::enabled!(tracing::Level::INFO) {
+        generation.progress = Some(indicatif::MultiProgress::new());
+    }
+    let mut compilation_db = generate::generate_entries(&conf, &workspaces, &mut generation)?;
+    if !quiet {
+        print_summary(&generation.stats);
+    }
+    if let Some(report) = &args.report {
+        #[derive(Serialize)]
+        struct Report<'a> {
+            workspace : &'a [generate::WorkspaceStats],
+        }
+
+        let json = serde_json::to_string_pretty(&Report { workspace : &generation.stats }).unwrap();
+        std::fs::write(report, json).map_err(|e| format!("{}: {}", report, e))?;
+    }
+    let dead_patterns = generation.dead_patterns();
+    if !dead_patterns.is_empty() {
+        generation.warn(generate::Warning::Other, &format!("patterns that never matched: {}", dead_patterns.join(", ")))?;
+    }
+
+    if !conf.path_map.is_empty() {
+        compilation_db = compilation_db.into_iter().map(|entry| entry.map_paths(&conf.path_map)).collect();
+    }
+    if let Some(translation) = args.path_translation {
+        compilation_db = compilation_db.into_iter().map(|entry| entry.rewrite_paths(|path| translation.translate(path))).collect();
+    }
+    if args.update && Path::new(&output).exists() {
+        let existing = compdb::read_db(Path::new(&output))?;
+        let kept = existing.into_iter().filter(|entry| entry.path().exists()).collect();
+        compilation_db = compdb::update(kept, compilation_db);
+    }
+    match &args.exporter {
+        Some(exporter) => registry.exporter(exporter)?.export(Path::new(&output), &compilation_db)?,
+        None => compdb::write_db(Path::new(&output), &compilation_db, &write_options)?,
+    }
+    if let Some(fingerprint) = fingerprint {
+        std::fs::write(&fingerprint_path, fingerprint).map_err(|e| format!("{}: {}", fingerprint_path, e))?;
+        eprintln!("{}: regenerated", output);
+    }
+    hooks::run("post", conf.hooks.post.as_deref().unwrap_or_default(), Path::new(&conf.common.root_dir), &output_path)?;
+    if args.stage {
+        let written = match &args.exporter {
+            Some(_) => vec![PathBuf::from(&output)],
+            None => compdb::written_files(Path::new(&output), &write_options).ok_or_else(|| format!("{}: not all of the database was written", output))?,
+        };
+        let status = std::process::Command::new("git").args(["add", "--"]).args(&written).status().map_err(|e| format!("git add: {}", e))?;
+        if !status.success() {
+            return Err(format!("git add {} failed ({})", output, status));
+        }
+    }
+    Ok(())
+}
+
+fn print_summary(stats : &[generate::WorkspaceStats]) {
+    let width = stats.iter().map(|s| s.name.len()).chain(["workspace".len()]).max().unwrap();
+    eprintln!("{:width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>9}", "workspace", "matched", "ignored", "includes", "entries", "elapsed");
+    for s in stats {
+        let row = format!("{:width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>7.0}ms", s.name, s.matched, s.ignored, s.include_dirs, s.entries, s.elapsed.as_secs_f64() * 1000.0);
+        // A workspace without entries is usually a typo in its patterns.
+        match s.entries {
+            0 => eprintln!("{}  {}", console::style(row).for_stderr().yellow().bold(), console::style("no entries").for_stderr().yellow()),
+            _ => eprintln!("{}", row),
+        }
+    }
+}
+
+fn list(what : &ListCommand) -> Result<(), String> {
+    let (args, list_files) = match what {
+        ListCommand::Files(args) => (args, true),
+        ListCommand::Includes(args) => (args, false),
+    };
+    let conf = args.config.load()?;
+
+    let mut stdout = std::io::stdout().lock();
+    for workspace in select_workspaces(&conf, args.workspace.as_slice(), &[])? {
+        let effective = EffectiveConf::resolve(&conf, workspace);
+        let paths = if list_files {
+            list_target_files(&effective, &mut Generation::default())?
+        } else {
+            list_include_dirs(&effective, &mut Generation::default())?
+        };
+        for path in paths {
+            writeln!(stdout, "{}", path.display()).map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn config(what : &ConfigCommand) -> Result<(), String> {
+    match what {
+        ConfigCommand::Print(args) => {
+            #[derive(Serialize)]
+            struct EffectiveDump {
+                workspace : Vec<EffectiveConf>,
+            }
+
+            let conf = args.config.load()?;
+            let dump = EffectiveDump {
+                workspace : select_workspaces(&conf, args.workspace.as_slice(), &[])?.into_iter().map(|w| EffectiveConf::resolve(&conf, w)).collect(),
+            };
+            print!("{}", toml::to_string_pretty(&dump).map_err(|e| e.to_string())?);
+            Ok(())
+        }
+        ConfigCommand::Check(args) => {
+            let conf = args.config.load()?;
+            check::verify_compilers(&conf)
+        }
+        ConfigCommand::Migrate(args) => {
+            let format = args.config_format.unwrap_or_else(|| conf::ConfigFormat::of(&args.config));
+            let (text, migrated) = conf::migrate_file(&args.config, format)?;
+            if !args.in_place {
+                print!("{}", text);
+            } else if migrated {
+                std::fs::write(&args.config, text).map_err(|e| format!("{}: {}", args.config, e))?;
+                eprintln!("{}: migrated to version {}", args.config, conf::CONF_VERSION);
+            } else {
+                eprintln!("{}: already at version {}", args.config, conf::CONF_VERSION);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn verify(args : &VerifyArgs) -> Result<(), String> {
+    let mut findings = Vec::<sarif::Finding>::new();
+    let result = verify_workspaces(args, &mut findings);
+    if let Some(path) = &args.sarif {
+        sarif::write(path, vec![sarif::run(if args.clangd { "clangd" } else { "compiler" }, &findings), sarif::own_run(result.as_ref().err().map(String::as_str))])?;
+    }
+    result
+}
+
+fn verify_workspaces(args : &VerifyArgs, findings : &mut Vec<sarif::Finding>) -> Result<(), String> {
+    let conf = args.config.load()?;
+    let db_dir = std::env::temp_dir().join(format!("compdb-verify-{}", std::process::id()));
+
+    let mut failed_workspaces = 0;
+    for workspace in select_workspaces(&conf, &args.only, &args.exclude)? {
+        let entries = generate::workspace_entries(&EffectiveConf::resolve(&conf, workspace), &mut Generation::default())?;
+        if args.clangd {
+            std::fs::create_dir_all(&db_dir).map_err(|e| format!("{}: {}", db_dir.display(), e))?;
+            compdb::write_db(&db_dir.join("compile_commands.json"), &entries, &compdb::WriteOptions::default())?;
+        }
+        let entries = check::sample(entries, args.sample);
+
+        let checked = entries.len();
+        let mut failed = 0;
+        let mut kinds = std::collections::BTreeMap::<(&str, String), usize>::new();
+        for entry in entries {
+            if args.clangd {
+                let mut ok = true;
+                for diagnostic in check::clangd_check(&args.clangd_path, &db_dir, &entry)? {
+                    if let Some(kind) = diagnostic.kind() {
+                        ok = false;
+                        findings.push(sarif::Finding {
+                            rule : diagnostic.code.clone(),
+                            level : "error".into(),
+                            message : diagnostic.message.clone(),
+                            file : Some(generate::slashed(&entry.path())),
+                            line : diagnostic.line,
+                            column : None,
+                        });
+                        *kinds.entry((kind, diagnostic.message)).or_default() += 1;
+                    }
+                }
+                if !ok {
+                    failed += 1;
+                }
+            } else if let Err(diagnostics) = check::syntax_check(&entry) {
+                failed += 1;
+                eprintln!("error: {}\n{}", entry.path().display(), diagnostics);
+                findings.extend(sarif::compiler_findings(&diagnostics, "compile-error", &entry.directory, &generate::slashed(&entry.path())));
+            }
+        }
+
+        let name = workspace.name.as_deref().unwrap_or(&workspace.path);
+        for ((kind, message), count) in kinds {
+            eprintln!("{}: {}: {} ({} files)", name, kind, message, count);
+        }
+        println!("{}: {}/{} entries {}", name, checked - failed, checked, if args.clangd { "pass clangd --check" } else { "compile" });
+        if failed > 0 {
+            failed_workspaces += 1;
+        }
+    }
+    if args.clangd {
+        let _ = std::fs::remove_dir_all(&db_dir);
+    }
+
+    match failed_workspaces {
+        0 => Ok(()),
+        n => Err(format!("{} workspace(s) have entries that do not {}", n, if args.clangd { "pass clangd --check" } else { "compile" })),
+    }
+}
+
+fn run_tool(what : &RunCommand) -> Result<(), String> {
+    match what {
+        RunCommand::Iwyu(args) => {
+            let conf = args.config.config.load()?;
+            let mapping_files : Vec<String> = conf.iwyu.as_ref().and_then(|iwyu| iwyu.mapping_file.as_ref()).into_iter().flatten()
+                .map(|file| generate::slashed(&Path::new(&conf.common.root_dir).join(file)))
+                .collect();
+            let jobs = args.jobs.unwrap_or_else(run::default_jobs);
+
+            let mut stdout = std::io::stdout().lock();
+            let mut failed = 0;
+            for workspace in select_workspaces(&conf, args.config.workspace.as_slice(), &[])? {
+                let entries = generate::workspace_entries(&EffectiveConf::resolve(&conf, workspace), &mut Generation::default())?;
+                let results = run::parallel(&entries, jobs, |entry| run::iwyu(&args.iwyu_path, &mapping_files, entry));
+
+                let (mut files, mut added, mut removed) = (std::collections::BTreeSet::<String>::new(), 0, 0);
+                for result in results {
+                    match result {
+                        Ok(suggestions) => for suggestion in suggestions {
+                            // A header shared by several sources is reported once.
+                            if !files.insert(suggestion.file.clone()) {
+                                continue;
+                            }
+                            writeln!(stdout, "{}:", suggestion.file).map_err(|e| e.to_string())?;
+                            for line in &suggestion.add {
+                                writeln!(stdout, "  + {}", line).map_err(|e| e.to_string())?;
+                            }
+                            for line in &suggestion.remove {
+                                writeln!(stdout, "  - {}", line).map_err(|e| e.to_string())?;
+                            }
+                            added += suggestion.add.len();
+                            removed += suggestion.remove.len();
+                        },
+                        Err(message) => {
+                            failed += 1;
+                            eprintln!("error: {}", message);
+                        }
+                    }
+                }
+                let name = workspace.name.as_deref().unwrap_or(&workspace.path);
+                writeln!(stdout, "{}: {} entries, {} files with suggestions, {} includes to add, {} to remove", name, entries.len(), files.len(), added, removed).map_err(|e| e.to_string())?;
+            }
+            match failed {
+                0 => Ok(()),
+                n => Err(format!("include-what-you-use failed on {} entries", n)),
+            }
+        }
+        RunCommand::ClangTidy(args) => {
+            let conf = args.config.config.load()?;
+            let jobs = args.jobs.unwrap_or_else(run::default_jobs);
+            let db_dir = std::env::temp_dir().join(format!("compdb-clang-tidy-{}", std::process::id()));
+
+            let mut diagnostics = Vec::<run::TidyDiagnostic>::new();
+            let mut summaries = Vec::<String>::new();
+            let mut failed = 0;
+            for workspace in select_workspaces(&conf, args.config.workspace.as_slice(), &[])? {
+                let name = workspace.name.as_deref().unwrap_or(&workspace.path).to_string();
+                let checks : Vec<&str> = [conf.clang_tidy.as_ref().and_then(|tidy| tidy.checks.as_deref()), args.checks.as_deref(), workspace.clang_tidy_checks.as_deref()]
+                    .into_iter().flatten().collect();
+                let checks = (!checks.is_empty()).then(|| checks.join(","));
+
+                let entries = generate::workspace_entries(&EffectiveConf::resolve(&conf, workspace), &mut Generation::default())?;
+                let workspace_db = db_dir.join(summaries.len().to_string());
+                std::fs::create_dir_all(&workspace_db).map_err(|e| format!("{}: {}", workspace_db.display(), e))?;
+                compdb::write_db(&workspace_db.join("compile_commands.json"), &entries, &compdb::WriteOptions::default())?;
+                let files : Vec<String> = entries.iter().map(|entry| generate::slashed(&entry.path())).collect();
+                let shards : Vec<&[String]> = files.chunks(files.len().div_ceil(jobs.max(1)).max(1)).collect();
+                let results = run::parallel(&shards, jobs, |shard| run::clang_tidy(&args.clang_tidy_path, checks.as_deref(), &workspace_db, &name, shard));
+
+                // A header included by several shards is reported by each of them.
+                let mut seen = std::collections::HashSet::<run::TidyDiagnostic>::new();
+                let mut per_check = std::collections::BTreeMap::<String, usize>::new();
+                for result in results {
+                    match result {
+                        Ok(found) => for diagnostic in found {
+                            if seen.insert(diagnostic.clone()) {
+                                *per_check.entry(diagnostic.check.clone()).or_default() += 1;
+                                diagnostics.push(diagnostic);
+                            }
+                        },
+                        Err(message) => {
+                            failed += 1;
+                            eprintln!("error: {}", message);
+                        }
+                    }
+                }
+                let counts : Vec<String> = per_check.iter().map(|(check, count)| format!("{} {}", count, check)).collect();
+                summaries.push(format!("{}: {} entries, {} diagnostics{}{}", name, entries.len(), seen.len(), if counts.is_empty() { "" } else { ": " }, counts.join(", ")));
+            }
+            let _ = std::fs::remove_dir_all(&db_dir);
+
+            let report = match args.format {
+                run::ReportFormat::Text => diagnostics.iter()
+                    .map(|d| format!("{}:{}:{}: {}: {} [{}]\n", d.file, d.line, d.column, d.severity, d.message, d.check))
+                    .chain(summaries.iter().map(|summary| format!("{}\n", summary)))
+                    .collect(),
+                run::ReportFormat::Json => serde_json::to_string_pretty(&diagnostics).unwrap() + "\n",
+                run::ReportFormat::Sarif => {
+                    let findings : Vec<_> = diagnostics.iter().map(run::TidyDiagnostic::to_finding).collect();
+                    serde_json::to_string_pretty(&sarif::log(vec![sarif::run("clang-tidy", &findings)])).unwrap() + "\n"
+                }
+            };
+            match args.output.as_str() {
+                "-" => print!("{}", report),
+                output => std::fs::write(output, report).map_err(|e| format!("{}: {}", output, e))?,
+            }
+
+            let errors = diagnostics.iter().filter(|d| d.severity == "error").count();
+            let result = match (failed, errors) {
+                (0, 0) => Ok(()),
+                (0, n) => Err(format!("clang-tidy reported {} errors", n)),
+                (n, _) => Err(format!("clang-tidy failed on {} shards", n)),
+            };
+            if let Some(path) = &args.sarif {
+                let findings : Vec<_> = diagnostics.iter().map(run::TidyDiagnostic::to_finding).collect();
+                sarif::write(path, vec![sarif::run("clang-tidy", &findings), sarif::own_run(result.as_ref().err().map(String::as_str))])?;
+            }
+            result
+        }
+    }
+}
+
+fn report(what : &ReportCommand) -> Result<(), String> {
+    match what {
+        ReportCommand::Shadowing(args) => {
+            let conf = args.config.load()?;
+            let root = Path::new(&conf.common.root_dir);
+            let shown = |dir : &Path| match generate::lossy(&generate::get_slashed_path_without_prefix(dir, root)) {
+                relative if relative.is_empty() => ".".to_string(),
+                relative => relative,
+            };
+            let mut headers = std::collections::HashMap::new();
+            let mut stdout = std::io::stdout().lock();
+            for workspace in select_workspaces(&conf, args.workspace.as_slice(), &[])? {
+                let entries = generate::workspace_entries(&EffectiveConf::resolve(&conf, workspace), &mut Generation::default())?;
+                // Entries of a workspace mostly share their dirs, so each shadowing is listed once with how many entries see it.
+                let mut shadowings = std::collections::BTreeMap::<(String, Vec<PathBuf>), usize>::new();
+                for entry in &entries {
+                    for shadowing in includes::shadowed_headers(&includes::search_dirs(entry), &mut headers) {
+                        *shadowings.entry(shadowing).or_default() += 1;
+                    }
+                }
+                writeln!(stdout, "workspace {}", workspace.name.as_deref().unwrap_or(&workspace.path)).map_err(|e| e.to_string())?;
+                for ((header, dirs), count) in shadowings {
+                    let others : Vec<String> = dirs[1..].iter().map(|dir| shown(dir)).collect();
+                    writeln!(stdout, "{}: {} picked over {} ({}/{} entries)", header, shown(&dirs[0]), others.join(", "), count, entries.len()).map_err(|e| e.to_string())?;
+                }
+            }
+            Ok(())
+        }
+        ReportCommand::Graph(args) => {
+            let conf = args.config.config.load()?;
+            let root = Path::new(&conf.common.root_dir);
+            let name_of = |workspace : &WorkSpaceConf| workspace.name.clone().unwrap_or_else(|| workspace.path.clone());
+            let nodes : Vec<graph::Node> = select_workspaces(&conf, args.config.workspace.as_slice(), &[])?.into_iter().map(|workspace| {
+                let effective = EffectiveConf::resolve(&conf, workspace);
+                graph::Node {
+                    name : name_of(workspace),
+                    path : workspace.path.clone(),
+                    uses : effective.uses.iter().filter_map(|used| conf.workspace.iter().find(|w| w.is_called(used))).map(name_of).collect(),
+                    include_roots : effective.include.root_dir.iter().flatten()
+                        .map(|dir| match generate::lossy(&generate::get_slashed_path_without_prefix(&db::clean(Path::new(dir)), root)) {
+                            relative if relative.is_empty() => ".".into(),
+                            relative => relative,
+                        })
+                        .collect(),
+                }
+            }).collect();
+            let text = graph::dot(&nodes);
+            match args.dot.as_str() {
+                "-" => print!("{}", text),
+                dot => std::fs::write(dot, text).map_err(|e| format!("{}: {}", dot, e))?,
+            }
+            Ok(())
+        }
+        ReportCommand::Includes(args) => {
+            let conf = args.config.load()?;
+            let mut stdout = std::io::stdout().lock();
+            for workspace in select_workspaces(&conf, args.workspace.as_slice(), &[])? {
+                let effective = EffectiveConf::resolve(&conf, workspace);
+                let mut generation = Generation::default();
+                let root = Path::new(&effective.root_dir);
+                let targets = list_target_files(&effective, &mut generation)?;
+                let include_dirs = list_include_dirs(&effective, &mut generation)?;
+                let usage = includes::resolve_usage(root, &targets, &include_dirs);
+
+                writeln!(stdout, "workspace {}", workspace.name.as_deref().unwrap_or(&workspace.path)).map_err(|e| e.to_string())?;
+                writeln!(stdout, "{:>8}  {:>8}  dir", "headers", "included").map_err(|e| e.to_string())?;
+                let mut first_provider = std::collections::BTreeMap::<String, &Path>::new();
+                let mut shadowed = Vec::<String>::new();
+                for (index, dir) in include_dirs.iter().enumerate() {
+                    let headers = includes::headers_under(&root.join(dir));
+                    let included = usage.resolved.values().filter(|&&i| i == index).count();
+                    writeln!(stdout, "{:>8}  {:>8}  {}", headers.len(), included, dir.display()).map_err(|e| e.to_string())?;
+                    for header in headers {
+                        match first_provider.get(&header) {
+                            Some(first) if *first != dir.as_path() => shadowed.push(format!("{}: {} shadows {}", header, first.display(), dir.display())),
+                            Some(_) => {}
+                            None => { first_provider.insert(header, dir); }
+                        }
+                    }
+                }
+                for line in shadowed {
+                    writeln!(stdout, "shadowed {}", line).map_err(|e| e.to_string())?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn export(what : &ExportCommand) -> Result<(), String> {
+    fn write(output : &str, text : &str) -> Result<(), String> {
+        match output {
+            "-" => {
+                print!("{}", text);
+                Ok(())
+            }
+            output => std::fs::write(output, text).map_err(|e| format!("{}: {}", output, e)),
+        }
+    }
+
+    match what {
+        ExportCommand::Lint(args) => {
+            let conf = args.config.config.load()?;
+            let entries = generate::generate_entries(&conf, &select_workspaces(&conf, args.config.workspace.as_slice(), &[])?, &mut Generation::new(false, conf.policy.clone()))?;
+            write(&args.output, &export::lint(&entries, args.tool))
+        }
+        ExportCommand::Tags(args) => {
+            let conf = args.config.config.load()?;
+            let (mut files, mut include_dirs) = (Vec::<String>::new(), Vec::<String>::new());
+            let mut seen = std::collections::HashSet::<String>::new();
+            for workspace in select_workspaces(&conf, args.config.workspace.as_slice(), &[])? {
+                let effective = EffectiveConf::resolve(&conf, workspace);
+                let mut generation = Generation::default();
+                let targets = list_target_files(&effective, &mut generation)?;
+                let dirs = list_include_dirs(&effective, &mut generation)?;
+                files.extend(export::tag_files(Path::new(&effective.root_dir), &targets, &dirs).into_iter().filter(|file| seen.insert(file.clone())));
+                for dir in dirs.iter().map(|dir| generate::slashed(dir)) {
+                    if !include_dirs.contains(&dir) {
+                        include_dirs.push(dir);
+                    }
+                }
+            }
+            if let Some(include_list) = &args.include_list {
+                write(include_list, &include_dirs.iter().map(|dir| format!("{}\n", dir)).collect::<String>())?;
+            }
+            write(&args.output, &files.iter().map(|file| format!("{}\n", file)).collect::<String>())
+        }
+        ExportCommand::Doxygen(args) => {
+            let conf = args.config.config.load()?;
+            let workspaces = select_workspaces(&conf, args.config.workspace.as_slice(), &[])?;
+            let (mut inputs, mut exclude_patterns) = (Vec::<String>::new(), Vec::<String>::new());
+            for workspace in &workspaces {
+                let effective = EffectiveConf::resolve(&conf, workspace);
+                inputs.push(generate::slashed(&Path::new(&effective.root_dir).join(&effective.path)));
+                // Doxygen takes globs, so only the pruned directories translate.
+                for dir in effective.target.prune_dirs.iter().flatten() {
+                    let pattern = format!("*/{}/*", dir);
+                    if !exclude_patterns.contains(&pattern) {
+                        exclude_patterns.push(pattern);
+                    }
+                }
+            }
+            let entries = generate::generate_entries(&conf, &workspaces, &mut Generation::new(false, conf.policy.clone()))?;
+            write(&args.output, &export::doxygen(&inputs, &exclude_patterns, &entries))
+        }
+        ExportCommand::Sourcetrail(args) => {
+            let conf = args.config.config.load()?;
+            let entries = generate::generate_entries(&conf, &select_workspaces(&conf, args.config.workspace.as_slice(), &[])?, &mut Generation::new(false, conf.policy.clone()))?;
+            compdb::write_db(Path::new(&args.output), &export::sourcetrail(entries)?, &compdb::WriteOptions::default())
+        }
+    }
+}
+
+fn import(what : &ImportCommand, registry : &Registry) -> Result<(), String> {
+    let (imported, output) = match what {
+        ImportCommand::Cmake(args) => (import::cmake::import(Path::new(&args.build_dir), args.configuration.as_deref())?, &args.output),
+        ImportCommand::Ninja(args) => (import::ninja::import(&args.ninja, Path::new(&args.build_dir), &args.rule)?, &args.output),
+        ImportCommand::Make(args) => {
+            let directory = std::path::absolute(&args.dir).map_err(|e| e.to_string())?;
+            let log = match &args.log {
+                Some(log) => std::fs::read_to_string(log).map_err(|e| format!("{}: {}", log, e))?,
+                None => import::make::dry_run(&args.make, &directory, &args.make_args)?,
+            };
+            let compiler = args.compiler.as_deref().map(regex::Regex::new).transpose().map_err(|e| e.to_string())?;
+            (import::make::parse_log(&log, &directory, compiler.as_ref()), &args.output)
+        }
+        ImportCommand::Autosar(args) => {
+            let fragment = import::autosar::import(Path::new(&args.root), Path::new(&args.arxml_dir), Path::new(&args.src_dir), Path::new(&args.rte_dir))?;
+            return import::write_fragment(&fragment, &args.output);
+        }
+        ImportCommand::Depfiles(args) => (import::depfiles::import(&args.glob, Path::new(&args.dir), &args.compiler)?, &args.output),
+        ImportCommand::Eclipse(args) => {
+            let project = import::eclipse::read_project(Path::new(&args.project))?;
+            let configuration = project.configuration(args.configuration.as_deref())?;
+            return import::write_fragment(&project.fragment(configuration, Path::new(&args.root))?, &args.output);
+        }
+        ImportCommand::Stm32cube(args) => {
+            let project = import::eclipse::read_project(Path::new(&args.project))?;
+            let configuration = project.configuration(args.configuration.as_deref())?;
+            let target_flags = import::stm32cube::target_flags(configuration);
+            (project.entries(configuration, &args.c_compiler, &args.cpp_compiler, &target_flags), &args.output)
+        }
+        ImportCommand::Iar(args) => (import::iar::import(Path::new(&args.project), args.configuration.as_deref(), &args.compiler, args.toolkit_dir.as_deref())?, &args.output),
+        ImportCommand::Keil(args) => (import::keil::import(Path::new(&args.project), args.target.as_deref(), &args.compiler)?, &args.output),
+        ImportCommand::Vcxproj(args) => (import::vcxproj::import(Path::new(&args.project), args.configuration.as_deref(), &args.compiler)?, &args.output),
+        ImportCommand::Zephyr(args) => {
+            let build = import::zephyr::import(Path::new(&args.build_dir))?;
+            return write_imported(build.entries, &args.output, build.application_flags);
+        }
+        ImportCommand::Platformio(args) => {
+            let project_dir = Path::new(&args.project_dir);
+            let metadata = match &args.metadata {
+                Some(metadata) => std::fs::read_to_string(metadata).map_err(|e| format!("{}: {}", metadata, e))?,
+                None => import::platformio::run_metadata(&args.pio, project_dir, &args.env)?,
+            };
+            let mut databases = import::platformio::import(&metadata, project_dir, Path::new(&args.src_dir))?;
+            databases.retain(|environment, _| args.env.is_empty() || args.env.contains(environment));
+            let single = databases.len() == 1;
+            for (environment, entries) in databases {
+                let mut output = args.output.clone();
+                if !single {
+                    let path = Path::new(&output.output);
+                    output.output = generate::lossy(&path.with_file_name(format!("{}.{}.json", path.file_stem().unwrap_or_default().to_string_lossy(), environment)));
+                }
+                write_imported(entries, &output, Vec::new())?;
+            }
+            return Ok(());
+        }
+        ImportCommand::Plugin(args) => (registry.importer(&args.name)?.import(Path::new(&args.project))?, &args.output),
+        ImportCommand::EspIdf(args) => {
+            let build = import::esp_idf::import(Path::new(&args.build_dir))?;
+            return write_imported(build.entries, &args.output, build.application_flags);
+        }
+    };
+
+    write_imported(imported, output, Vec::new())
+}
+
+/// Writes `imported`, merged over the workspaces of `--config` if given.
+/// Those workspaces get `config_flags` on top of their own options.
+fn write_imported(mut imported : Vec<compdb::CompilationEntry>, output : &ImportOutput, config_flags : Vec<String>) -> Result<(), String> {
+    if output.strip_wrappers {
+        for entry in &mut imported {
+            entry.arguments = compdb::strip_compiler_wrappers(&entry.arguments);
+        }
+    }
+    let entries = match &output.config {
+        Some(config) => {
+            let mut conf = load_conf(std::slice::from_ref(config), None, &[])?;
+            conf.select_variant(false)?;
+            conf.common.option.get_or_insert_default().arg.get_or_insert_default().extend(config_flags);
+            let generated = generate::generate_entries(&conf, &select_workspaces(&conf, &[], &[])?, &mut Generation::default())?;
+            compdb::merge(generated, imported)
+        }
+        None => imported,
+    };
+    compdb::write_db(Path::new(&output.output), &entries, &compdb::WriteOptions::default())
+}
+
+fn db(what : &DbCommand) -> Result<(), String> {
+    fn read(io : &DbIo) -> Result<Vec<compdb::CompilationEntry>, String> {
+        compdb::read_db(Path::new(&io.input))
+    }
+    fn output(io : &DbIo) -> &String {
+        io.output.as_ref().unwrap_or(&io.input)
+    }
+    let (output, entries) = match what {
+        DbCommand::Dedup { io, options } => (output(io), db::dedup(read(io)?, *options)),
+        DbCommand::Strip { io, pattern } => {
+            let anchored : Vec<String> = pattern.iter().map(|p| format!("^(?:{})$", p)).collect();
+            let patterns = regex::RegexSet::new(&anchored).map_err(|e| e.to_string())?;
+            (output(io), db::strip(read(io)?, &patterns))
+        }
+        DbCommand::Normalize { io } => (output(io), db::normalize(read(io)?)?),
+        DbCommand::Merge { inputs, output, conflict } => {
+            let databases = inputs.iter().map(|input| compdb::read_db(Path::new(input))).collect::<Result<_, String>>()?;
+            (output, db::merge(databases, *conflict))
+        }
+        DbCommand::Diff { old, new } => {
+            if old == "-" && new == "-" {
+                return Err("db diff: only one of the databases can be read from stdin (-)".into());
+            }
+            let changes = db::diff(&compdb::read_db(Path::new(old))?, &compdb::read_db(Path::new(new))?);
+            print_diff(&changes).map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+        DbCommand::Validate { input } => {
+            let violations = db::validate(&compdb::read_text(Path::new(input))?).map_err(|e| format!("{}: {}", input, e))?;
+            for (index, message) in &violations {
+                eprintln!("{}: entry {}: {}", input, index, message);
+            }
+            return match violations.len() {
+                0 => Ok(()),
+                n => Err(format!("{}: {} violation(s) of the JSON Compilation Database format", input, n)),
+            };
+        }
+    };
+    compdb::write_db(Path::new(output), &entries, &compdb::WriteOptions::default())
+}
+
+fn print_diff(changes : &[db::Change]) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    for change in changes {
+        match change {
+            db::Change::Added(path) => writeln!(stdout, "+ {}", path.display())?,
+            db::Change::Removed(path) => writeln!(stdout, "- {}", path.display())?,
+            db::Change::Changed { path, removed, added } => {
+                writeln!(stdout, "~ {}", path.display())?;
+                if removed.is_empty() && added.is_empty() {
+                    writeln!(stdout, "    (options reordered)")?;
+                }
+                for option in removed {
+                    writeln!(stdout, "    - {}", option)?;
+                }
+                for option in added {
+                    writeln!(stdout, "    + {}", option)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn intercept(args : &InterceptArgs) -> Result<(), String> {
+    let mut compilers = args.compiler.clone();
+    // Without a configuration there are only the usual compilers.
+    if let Ok(conf) = conf::find_conf().and_then(|path| load_conf(&[generate::lossy(&path)], None, &[])) {
+        for compiler in [&conf.common.c_compiler, &conf.common.cpp_compiler] {
+            compilers.extend(compdb::strip_compiler_wrappers(compiler).into_iter().next());
+        }
+    }
+    let recorded = intercept::run(&args.build, &compilers)?;
+    let output = Path::new(&args.output);
+    let entries = if args.merge && output.exists() {
+        compdb::merge(compdb::read_db(output)?, recorded)
+    } else {
+        recorded
+    };
+    compdb::write_db(output, &entries, &compdb::WriteOptions::default())
+}
+
+fn serve(args : &ServeArgs) -> Result<(), String> {
+    let config = args.config.paths()?;
+    serve::run(&args.listen, &config, || {
+        let conf = args.config.load_from(&config)?;
+        let entries = generate::generate_entries(&conf, &select_workspaces(&conf, &[], &[])?, &mut Generation::new(false, conf.policy.clone()))?;
+        Ok((conf.common.root_dir.into(), entries))
+    })
+}
+
+/// The script registering the shell's completion, which calls back into this
+/// binary with `COMPLETE=<shell>` set.
+fn completions(shell : &str) -> Result<(), String> {
+    let name = Cli::command().get_name().to_string();
+    let completer = std::env::current_exe().ok().and_then(|exe| exe.to_str().map(String::from)).unwrap_or_else(|| name.clone());
+    let shells = clap_complete::env::Shells::builtins();
+    let shell = shells.completer(shell).ok_or_else(|| format!("Unsupported shell {}", shell))?;
+    shell.write_registration("COMPLETE", &name, &name, &completer, &mut std::io::stdout()).map_err(|e| e.to_string())
+}
+
+/// Logs to stderr at `--log-level`, or at a level picked by the number of `-v`.
+fn init_logging(cli : &Cli) {
+    let level = cli.log_level.unwrap_or(match cli.verbose {
+        0 => tracing::Level::WARN,
+        1 => tracing::Level::INFO,
+        2 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    });
+    let subscriber = tracing_subscriber::fmt().with_max_level(level).with_writer(std::io::stderr);
+    if cli.log_json {
+        subscriber.json().init();
+    } else {
+        subscriber.init();
+    }
+}
+
+#[derive(Parser)]
+#[command(version, about = "Generate compile_commands.json from a TOML description of the source tree")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command : Option<Command>,
+    #[command(flatten)]
+    generate : GenerateArgs,
+    /// Log more: -v for per-workspace statistics and timings, -vv for phases
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose : u8,
+    /// Log level (error, warn, info, debug, trace); overrides -v
+    #[arg(long, global = true)]
+    log_level : Option<tracing::Level>,
+    /// Log as JSON lines
+    #[arg(long, global = true)]
+    log_json : bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate the compilation database (default when no subcommand is given)
+    Generate(GenerateArgs),
+    /// Print what the generator would walk, one path per line
+    List {
+        #[command(subcommand)]
+        what : ListCommand,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        what : ConfigCommand,
+    },
+    /// Build a compilation database from another build system's description
+    Import {
+        #[command(subcommand)]
+        what : ImportCommand,
+    },
+    /// Analyse the configuration's output
+    Report {
+        #[command(subcommand)]
+        what : ReportCommand,
+    },
+    /// Write other tools' configuration from the generated entries
+    Export {
+        #[command(subcommand)]
+        what : ExportCommand,
+    },
+    /// Clean up an existing compilation database, whichever tool generated it
+    Db {
+        #[command(subcommand)]
+        what : DbCommand,
+    },
+    /// Compile the generated entries with -fsyntax-only and report failures per workspace
+    Verify(VerifyArgs),
+    /// Run a source analysis tool over the generated entries
+    Run {
+        #[command(subcommand)]
+        what : RunCommand,
+    },
+    /// Run a build with compiler wrappers and record what actually gets compiled
+    Intercept(InterceptArgs),
+    /// Answer GET /flags?file=<path> with the file's entry as JSON over HTTP
+    Serve(ServeArgs),
+    /// Write a starter compdb.toml for the current directory
+    Init(InitArgs),
+    /// Print the completion script of a shell; workspace names are completed
+    /// from the configuration found from the current directory
+    Completions {
+        #[arg(value_parser = ["bash", "zsh", "fish", "powershell", "elvish"])]
+        shell : String,
+    },
+}
+
+#[derive(Args)]
+struct GenerateArgs {
+    /// Configuration files (TOML, YAML or JSON) merged left to right, later
+    /// ones overriding earlier keys, then optionally the output compilation
+    /// database (the last path unless it is a .toml, .yaml or .yml file; use
+    /// -o with JSON configurations). compdb.{toml,yaml,yml,json} or
+    /// .compdbgen.{toml,yaml,yml,json} is found upwards from the current
+    /// directory when no file is given.
+    #[arg(value_name = "PATH")]
+    paths : Vec<String>,
+    /// Output compilation database, a directory to write --output-file into,
+    /// or - for stdout; --output-file next to the first configuration by default
+    #[arg(short, long, value_name = "FILE")]
+    output : Option<String>,
+    /// File name of the database when the output is a directory or defaulted;
+    /// compile_commands.json, or compile_commands.test.json with --test
+    #[arg(long, value_name = "NAME")]
+    output_file : Option<String>,
+    /// Write the database on a single line (overrides common.indent)
+    #[arg(long)]
+    compact : bool,
+    /// Write <output>.gz or <output>.zst instead of the plain database
+    #[arg(long, value_enum)]
+    compress : Option<compdb::Compression>,
+    /// With --compress, also write the plain database
+    #[arg(long, requires = "compress")]
+    keep_plain : bool,
+    /// Split the database into <stem>.partNN.json files of N entries, listed
+    /// by <stem>.manifest.json
+    #[arg(long, value_name = "N")]
+    chunk_size : Option<usize>,
+    /// Write Windows drive paths (C:\x) in the form of WSL (/mnt/c/x) or
+    /// MSYS (/c/x), and paths of that form as drive paths
+    #[arg(long, value_enum, value_name = "MODE")]
+    path_translation : Option<compdb::PathTranslation>,
+    /// Override a configuration key after loading, e.g. common.root_dir=/mnt/src
+    /// or common.option.arg+=-DDEBUG (appends); the value is TOML or a plain string
+    #[arg(long, value_name = "KEY=VALUE")]
+    set : Vec<String>,
+    /// Syntax of the configuration files; by extension (.toml, .yaml/.yml, .json) by default
+    #[arg(long, value_enum)]
+    config_format : Option<conf::ConfigFormat>,
+    /// Only generate entries for these workspaces (name or path)
+    #[arg(long, value_name = "NAME", add = ArgValueCompleter::new(complete_workspaces))]
+    only : Vec<String>,
+    /// Skip these workspaces (name or path)
+    #[arg(long, value_name = "NAME", add = ArgValueCompleter::new(complete_workspaces))]
+    exclude : Vec<String>,
+    /// Check that the configured compilers exist and match expected_version first
+    #[arg(long)]
+    verify_compilers : bool,
+    /// Turn every warning of the generation into an error, except categories
+    /// the [policy] table ignores
+    #[arg(long)]
+    strict : bool,
+    /// Do not show progress (shown by default on a terminal) or the summary
+    #[arg(short, long)]
+    quiet : bool,
+    /// Also write the per-workspace summary as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    report : Option<String>,
+    /// Update the existing output: regenerated files replace their entries,
+    /// the others stay unless their file no longer exists (e.g. with --only)
+    #[arg(long, conflicts_with_all = ["chunk_size", "compress"])]
+    update : bool,
+    /// Only write entries for files changed since this git ref (include paths
+    /// stay complete; min_entries/max_entries are not checked)
+    #[arg(long, value_name = "REF")]
+    changed_since : Option<String>,
+    /// For git hooks: regenerate only when the configuration, the command line
+    /// or the set of target files changed since the last --hook run (kept in
+    /// <output>.fingerprint), and print nothing otherwise
+    #[arg(long)]
+    hook : bool,
+    /// Stage the written database with git add
+    #[arg(long, requires = "hook")]
+    stage : bool,
+    /// Write the entries with an exporter registered by the binary (see
+    /// `plugin`) instead of as a JSON Compilation Database
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["compress", "chunk_size", "update"])]
+    exporter : Option<String>,
+    /// Generate the unit-test database: the [test] include roots first, its
+    /// defines, and the test_only workspaces
+    #[arg(long)]
+    test : bool,
+    /// Wait for another run writing the same output to finish instead of failing
+    #[arg(long)]
+    wait_for_lock : bool,
+    /// Also write the configuration warnings, and the error if any, as a SARIF log
+    #[arg(long, value_name = "FILE")]
+    sarif : Option<String>,
+}
+
+impl GenerateArgs {
+    fn inputs_and_output(&self) -> Result<(Vec<String>, String), String> {
+        let (inputs, output) = match (&self.output, self.paths.split_last()) {
+            (None, Some((last, inputs))) if ![".toml", ".yaml", ".yml"].iter().any(|ext| last.ends_with(ext)) => (inputs, Some(last.clone())),
+            (output, _) => (self.paths.as_slice(), output.clone()),
+        };
+        let inputs = config_paths(inputs)?;
+        let output_file = self.output_file.as_deref().unwrap_or(if self.test { "compile_commands.test.json" } else { "compile_commands.json" });
+        let output = match output {
+            Some(dir) if Path::new(&dir).is_dir() => generate::lossy(&Path::new(&dir).join(output_file)),
+            Some(output) => output,
+            None => generate::lossy(&Path::new(&inputs[0]).with_file_name(output_file)),
+        };
+        Ok((inputs, output))
+    }
+}
+
+/// The configuration files a subcommand reads and the keys overridden in them.
+#[derive(Args)]
+struct ConfigArgs {
+    /// Configuration files (TOML, YAML or JSON) merged left to right; found upwards from the current directory by default
+    config : Vec<String>,
+    /// Override a configuration key after loading, e.g. common.root_dir=/mnt/src
+    /// or common.option.arg+=-DDEBUG (appends); the value is TOML or a plain string
+    #[arg(long, value_name = "KEY=VALUE")]
+    set : Vec<String>,
+    /// Syntax of the configuration files; by extension (.toml, .yaml/.yml, .json) by default
+    #[arg(long, value_enum)]
+    config_format : Option<conf::ConfigFormat>,
+    /// Use the unit-test variant: the [test] include roots first, its
+    /// defines, and the test_only workspaces
+    #[arg(long)]
+    test : bool,
+}
+
+impl ConfigArgs {
+    /// The files given, or the one found upwards from the current directory.
+    fn paths(&self) -> Result<Vec<String>, String> {
+        config_paths(&self.config)
+    }
+
+    /// The merged configuration, `--set` applied, of the variant `--test` selects.
+    fn load(&self) -> Result<CompDBConf, String> {
+        self.load_from(&self.paths()?)
+    }
+
+    /// `load` from `paths`, the result of `paths` kept by the caller.
+    fn load_from(&self, paths : &[String]) -> Result<CompDBConf, String> {
+        let mut conf = load_conf(paths, self.config_format, &self.set)?;
+        conf.select_variant(self.test)?;
+        Ok(conf)
+    }
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    #[command(flatten)]
+    config : ConfigArgs,
+    /// Only compile this many entries per workspace, spread over its files
+    #[arg(long, value_name = "N")]
+    sample : Option<usize>,
+    /// Only verify these workspaces (name or path)
+    #[arg(long, value_name = "NAME", add = ArgValueCompleter::new(complete_workspaces))]
+    only : Vec<String>,
+    /// Skip these workspaces (name or path)
+    #[arg(long, value_name = "NAME", add = ArgValueCompleter::new(complete_workspaces))]
+    exclude : Vec<String>,
+    /// Run `clangd --check` instead and aggregate missing-header and unknown-flag diagnostics
+    #[arg(long)]
+    clangd : bool,
+    /// clangd executable
+    #[arg(long, default_value = "clangd")]
+    clangd_path : String,
+    /// Also write the failures and warnings as a SARIF log, for code scanning
+    #[arg(long, value_name = "FILE")]
+    sarif : Option<String>,
+}
+
+#[derive(Subcommand)]
+enum RunCommand {
+    /// include-what-you-use on every entry; prints its suggestions and a summary per workspace
+    Iwyu(IwyuRunArgs),
+    /// clang-tidy on every entry, sharded over processes; per-workspace checks come from the configuration
+    ClangTidy(ClangTidyRunArgs),
+}
+
+#[derive(Args)]
+struct ClangTidyRunArgs {
+    #[command(flatten)]
+    config : ListArgs,
+    /// Checks, appended to clang_tidy.checks and before the workspace's
+    /// clang_tidy_checks; .clang-tidy files decide when none is set
+    #[arg(long, allow_hyphen_values = true)]
+    checks : Option<String>,
+    /// clang-tidy processes at once, each given a share of the entries; one per CPU by default
+    #[arg(short, long, value_name = "N")]
+    jobs : Option<usize>,
+    /// clang-tidy executable
+    #[arg(long, default_value = "clang-tidy")]
+    clang_tidy_path : String,
+    /// Report format
+    #[arg(long, value_enum, default_value = "text")]
+    format : run::ReportFormat,
+    /// File to write the report to, - for stdout
+    #[arg(short, long, default_value = "-")]
+    output : String,
+    /// Also write the diagnostics and warnings as a SARIF log, for code scanning
+    #[arg(long, value_name = "FILE")]
+    sarif : Option<String>,
+}
+
+#[derive(Args)]
+struct IwyuRunArgs {
+    #[command(flatten)]
+    config : ListArgs,
+    /// Entries analysed at once; one per CPU by default
+    #[arg(short, long, value_name = "N")]
+    jobs : Option<usize>,
+    /// include-what-you-use executable
+    #[arg(long, default_value = "include-what-you-use")]
+    iwyu_path : String,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    #[command(flatten)]
+    config : ConfigArgs,
+    /// Address to listen on
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:7878")]
+    listen : String,
+}
+
+#[derive(Subcommand)]
+enum ListCommand {
+    /// Target files that get a compilation entry
+    Files(ListArgs),
+    /// Include directories emitted as -I
+    Includes(ListArgs),
+}
+
+#[derive(Args)]
+struct ListArgs {
+    #[command(flatten)]
+    config : ConfigArgs,
+    /// Restrict the output to the workspace with this name or path
+    #[arg(long, add = ArgValueCompleter::new(complete_workspaces))]
+    workspace : Option<String>,
+}
+
+#[derive(Subcommand)]
+enum ExportCommand {
+    /// Project or options file of a static analyzer
+    Lint(LintExportArgs),
+    /// Sources and headers, one per line, for `ctags -L -`, `gtags -f -` or cscope
+    Tags(TagsExportArgs),
+    /// Doxyfile fragment with INPUT, EXCLUDE_PATTERNS, INCLUDE_PATH and PREDEFINED
+    Doxygen(DoxygenExportArgs),
+    /// Database with one entry per file, the command form and absolute paths, for Sourcetrail
+    Sourcetrail(SourcetrailExportArgs),
+}
+
+#[derive(Args)]
+struct SourcetrailExportArgs {
+    #[command(flatten)]
+    config : ListArgs,
+    /// Database to write, - for stdout
+    #[arg(short, long, default_value = "compile_commands.sourcetrail.json")]
+    output : String,
+}
+
+#[derive(Args)]
+struct DoxygenExportArgs {
+    #[command(flatten)]
+    config : ListArgs,
+    /// File to write, - for stdout
+    #[arg(short, long, default_value = "-")]
+    output : String,
+}
+
+#[derive(Args)]
+struct TagsExportArgs {
+    #[command(flatten)]
+    config : ListArgs,
+    /// File to write the list to, - for stdout
+    #[arg(short, long, default_value = "-")]
+    output : String,
+    /// Also write the include dirs, one per line, to this file
+    #[arg(long, value_name = "FILE")]
+    include_list : Option<String>,
+}
+
+#[derive(Args)]
+struct LintExportArgs {
+    #[command(flatten)]
+    config : ListArgs,
+    /// Analyzer to write for
+    #[arg(long, value_enum)]
+    tool : export::LintTool,
+    /// File to write, - for stdout
+    #[arg(short, long, default_value = "-")]
+    output : String,
+}
+
+#[derive(Subcommand)]
+enum ReportCommand {
+    /// Per include dir: headers it holds, how many the targets include, and shadowed headers
+    Includes(ListArgs),
+    /// GraphViz graph of the workspaces, their include roots and `uses` edges
+    Graph(GraphReportArgs),
+    /// Headers an entry finds in several of its include dirs, and which one the compiler picks
+    Shadowing(ListArgs),
+}
+
+#[derive(Args)]
+struct GraphReportArgs {
+    #[command(flatten)]
+    config : ListArgs,
+    /// DOT file to write, - for stdout
+    #[arg(long, value_name = "FILE", default_value = "-")]
+    dot : String,
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Drop later entries of a file already listed
+    Dedup {
+        #[command(flatten)]
+        io : DbIo,
+        /// Also drop repeated options within each entry
+        #[arg(long)]
+        options : bool,
+    },
+    /// Drop options matching a regex (with their value) from every entry
+    Strip {
+        #[command(flatten)]
+        io : DbIo,
+        /// Regex an option must match as a whole, e.g. '-fno-.*'; repeatable
+        #[arg(long, required = true, allow_hyphen_values = true)]
+        pattern : Vec<String>,
+    },
+    /// Use the arguments form and absolute, clean, forward-slashed paths
+    /// (directories, files, outputs and include directories)
+    Normalize {
+        #[command(flatten)]
+        io : DbIo,
+    },
+    /// Combine several databases into one
+    Merge {
+        /// Databases to merge, in order
+        #[arg(required = true)]
+        inputs : Vec<String>,
+        /// Merged database, - for stdout
+        #[arg(short, long, default_value = "compile_commands.json")]
+        output : String,
+        /// Which entries a file listed by several databases keeps
+        #[arg(long, value_enum, default_value = "prefer-last")]
+        conflict : db::MergePolicy,
+    },
+    /// Print the files added, removed and compiled differently, with the options that changed
+    Diff {
+        /// Database before the change, - for stdin
+        old : String,
+        /// Database after the change, - for stdin (unless the old one is)
+        new : String,
+    },
+    /// Check a database against the JSON Compilation Database format and the file system
+    Validate {
+        /// Compilation database to check, - for stdin
+        input : String,
+    },
+}
+
+#[derive(Args)]
+struct DbIo {
+    /// Compilation database to read, - for stdin
+    input : String,
+    /// Where to write the result, - for stdout; the input by default
+    #[arg(short, long)]
+    output : Option<String>,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the merged configuration each workspace is generated from
+    Print(ListArgs),
+    /// Check the configuration against this machine (compilers, versions)
+    Check(CheckArgs),
+    /// Rewrite a configuration file into the current schema version; with version 1 the only one yet, this stamps it
+    Migrate(MigrateArgs),
+}
+
+#[derive(Args)]
+struct MigrateArgs {
+    /// Configuration file to migrate
+    config : String,
+    /// Replace the file instead of printing the result (comments are not kept)
+    #[arg(long)]
+    in_place : bool,
+    /// Syntax of the configuration file; by extension by default
+    #[arg(long, value_enum)]
+    config_format : Option<conf::ConfigFormat>,
+}
+
+#[derive(Args)]
+struct CheckArgs {
+    #[command(flatten)]
+    config : ConfigArgs,
+}
+
+#[derive(Subcommand)]
+enum ImportCommand {
+    /// Read the CMake File API reply (codemodel v2) of a build directory
+    Cmake(CmakeImportArgs),
+    /// Take the compile commands of a Ninja build via `ninja -t compdb`
+    Ninja(NinjaImportArgs),
+    /// Recover compile commands from `make -nB` or a saved build log
+    Make(MakeImportArgs),
+    /// Derive per-file include dirs from the .d files of a previous build
+    Depfiles(DepfilesImportArgs),
+    /// Write workspaces for the SWCs and BSW modules of an AUTOSAR project,
+    /// with their RTE contract header dirs, as configuration to merge
+    Autosar(AutosarImportArgs),
+    /// Read an MSBuild .vcxproj project
+    Vcxproj(VcxprojImportArgs),
+    /// Write workspaces for the source folders of an Eclipse CDT project (.cproject/.project),
+    /// with its include paths and symbols, as configuration to merge
+    Eclipse(EclipseImportArgs),
+    /// Read an STM32CubeIDE project, adding the MCU's target flags
+    Stm32cube(Stm32cubeImportArgs),
+    /// Read an IAR Embedded Workbench .ewp project
+    Iar(IarImportArgs),
+    /// Read a Keil MDK µVision .uvprojx project
+    Keil(KeilImportArgs),
+    /// Read a Zephyr build directory; --config workspaces get Zephyr's include flags
+    Zephyr(BuildDirImportArgs),
+    /// Read an ESP-IDF build directory; --config workspaces get the component include paths
+    EspIdf(BuildDirImportArgs),
+    /// Read `pio project metadata`; one database per environment
+    Platformio(PlatformioImportArgs),
+    /// Read a project with an importer registered by the binary (see `plugin`)
+    Plugin(PluginImportArgs),
+}
+
+#[derive(Args)]
+struct PluginImportArgs {
+    /// Name the importer is registered under
+    name : String,
+    /// Project file or directory handed to the importer
+    project : String,
+    #[command(flatten)]
+    output : ImportOutput,
+}
+
+#[derive(Args, Clone)]
+struct ImportOutput {
+    /// Output compilation database
+    #[arg(short, long, default_value = "compile_commands.json")]
+    output : String,
+    /// Also generate the workspaces of this configuration file; imported
+    /// entries win for files present in both
+    #[arg(long)]
+    config : Option<String>,
+    /// Drop compiler wrappers (ccache, sccache, distcc, icecc) from imported entries
+    #[arg(long)]
+    strip_wrappers : bool,
+}
+
+#[derive(Args)]
+struct CmakeImportArgs {
+    /// CMake build directory
+    #[arg(long)]
+    build_dir : String,
+    /// Configuration to import for multi-config generators (default: the first)
+    #[arg(long)]
+    configuration : Option<String>,
+    #[command(flatten)]
+    output : ImportOutput,
+}
+
+#[derive(Args)]
+struct NinjaImportArgs {
+    /// Ninja build directory
+    #[arg(long)]
+    build_dir : String,
+    /// Only take edges built by these rules (default: every compile command)
+    #[arg(long)]
+    rule : Vec<String>,
+    /// Ninja executable
+    #[arg(long, default_value = "ninja")]
+    ninja : String,
+    #[command(flatten)]
+    output : ImportOutput,
+}
+
+#[derive(Args)]
+struct MakeImportArgs {
+    /// Directory make runs in, and that log commands are relative to
+    #[arg(long, default_value = ".")]
+    dir : String,
+    /// Parse this build log instead of running `make -nB`
+    #[arg(long)]
+    log : Option<String>,
+    /// Regex matched against the program name of compiler invocations
+    #[arg(long)]
+    compiler : Option<String>,
+    /// Make executable
+    #[arg(long, default_value = "make")]
+    make : String,
+    #[command(flatten)]
+    output : ImportOutput,
+    /// Extra arguments for make (targets, variables)
+    #[arg(last = true)]
+    make_args : Vec<String>,
+}
+
+#[derive(Args)]
+struct InitArgs {
+    /// Overwrite an existing compdb.toml
+    #[arg(long)]
+    force : bool,
+    /// Pick workspaces, compilers and the target pattern in the terminal first
+    #[arg(short, long)]
+    interactive : bool,
+}
+
+#[derive(Args)]
+struct InterceptArgs {
+    /// Output compilation database
+    #[arg(short, long, default_value = "compile_commands.json")]
+    output : String,
+    /// Merge the recorded entries into an existing output instead of replacing it
+    #[arg(long)]
+    merge : bool,
+    /// Also record this compiler (name or path), on top of cc, gcc, clang, the
+    /// usual cross compilers and those of the configuration found from here
+    #[arg(long, value_name = "NAME")]
+    compiler : Vec<String>,
+    /// Build command, e.g. `-- make -j8`
+    #[arg(last = true, required = true)]
+    build : Vec<String>,
+}
+
+#[derive(Args)]
+struct VcxprojImportArgs {
+    /// Project file
+    project : String,
+    /// Configuration to import, e.g. "Release|x64" (default: the first declared)
+    #[arg(long)]
+    configuration : Option<String>,
+    /// Compiler written into the entries
+    #[arg(long, default_value = "clang-cl")]
+    compiler : String,
+    #[command(flatten)]
+    output : ImportOutput,
+}
+
+#[derive(Args)]
+struct EclipseImportArgs {
+    /// Project directory containing .cproject
+    project : String,
+    /// Build configuration to import (default: the first)
+    #[arg(long)]
+    configuration : Option<String>,
+    /// Directory the written paths are relative to; common.root_dir of the configuration
+    #[arg(long, default_value = ".")]
+    root : String,
+    /// Configuration to write, - for stdout; merge it after the project's own
+    #[arg(short, long, default_value = "compdb.eclipse.toml")]
+    output : String,
+}
+
+#[derive(Args)]
+struct Stm32cubeImportArgs {
+    /// Project directory containing .cproject
+    project : String,
+    /// Build configuration to import (default: the first)
+    #[arg(long)]
+    configuration : Option<String>,
+    /// Compiler written into C entries
+    #[arg(long, default_value = "arm-none-eabi-gcc")]
+    c_compiler : String,
+    /// Compiler written into C++ entries
+    #[arg(long, default_value = "arm-none-eabi-g++")]
+    cpp_compiler : String,
+    #[command(flatten)]
+    output : ImportOutput,
+}
+
+#[derive(Args)]
+struct IarImportArgs {
+    /// Project file (.ewp)
+    project : String,
+    /// Configuration to import (default: the first)
+    #[arg(long)]
+    configuration : Option<String>,
+    /// Compiler written into the entries
+    #[arg(long, default_value = "clang")]
+    compiler : String,
+    /// IAR installation directory, substituted for $TOOLKIT_DIR$
+    #[arg(long)]
+    toolkit_dir : Option<String>,
+    #[command(flatten)]
+    output : ImportOutput,
+}
+
+#[derive(Args)]
+struct KeilImportArgs {
+    /// Project file (.uvprojx)
+    project : String,
+    /// Target to import (default: the first)
+    #[arg(long)]
+    target : Option<String>,
+    /// Compiler written into the entries
+    #[arg(long, default_value = "clang")]
+    compiler : String,
+    #[command(flatten)]
+    output : ImportOutput,
+}
+
+#[derive(Args)]
+struct DepfilesImportArgs {
+    /// Depfiles to read, e.g. 'build/**/*.d'
+    #[arg(long)]
+    glob : String,
+    /// Directory the build ran in, which the depfile paths are relative to
+    #[arg(long, default_value = ".")]
+    dir : String,
+    /// Compiler written into the entries
+    #[arg(long, default_value = "clang")]
+    compiler : String,
+    #[command(flatten)]
+    output : ImportOutput,
+}
+
+#[derive(Args)]
+struct AutosarImportArgs {
+    /// Directory the written paths are relative to; common.root_dir of the configuration
+    #[arg(long, default_value = ".")]
+    root : String,
+    /// Directory searched for .arxml files (ECU extract, SWC and BSW descriptions), relative to --root
+    #[arg(long)]
+    arxml_dir : String,
+    /// Directory holding a directory per SWC and BSW module, relative to --root
+    #[arg(long)]
+    src_dir : String,
+    /// RTE generation output (Rte_<Swc>.h, SchM_<Module>.h, Rte_Type.h), relative to --root
+    #[arg(long)]
+    rte_dir : String,
+    /// Configuration to write, - for stdout; merge it after the project's own
+    #[arg(short, long, default_value = "compdb.autosar.toml")]
+    output : String,
+}
+
+#[derive(Args)]
+struct BuildDirImportArgs {
+    /// Build directory
+    #[arg(long)]
+    build_dir : String,
+    #[command(flatten)]
+    output : ImportOutput,
+}
+
+#[derive(Args)]
+struct PlatformioImportArgs {
+    /// Project directory containing platformio.ini
+    #[arg(long, default_value = ".")]
+    project_dir : String,
+    /// Source directory, relative to the project directory
+    #[arg(long, default_value = "src")]
+    src_dir : String,
+    /// Environments to import (default: all); with several, each goes to
+    /// `<output stem>.<env>.json`
+    #[arg(long)]
+    env : Vec<String>,
+    /// Parse this saved `pio project metadata --json-output` instead of running pio
+    #[arg(long)]
+    metadata : Option<String>,
+    /// PlatformIO executable
+    #[arg(long, default_value = "pio")]
+    pio : String,
+    #[command(flatten)]
+    output : ImportOutput,
+}
+
+/// Runs the command line with the importers and exporters of `registry`
+/// available to `import plugin` and `generate --exporter`.
+pub fn main(registry : Registry) -> std::process::ExitCode {
+    if let Some(code) = intercept::wrapper_main() {
+        std::process::exit(code);
+    }
+
+    clap_complete::CompleteEnv::with_factory(Cli::command).complete();
+
+    let cli = Cli::parse();
+    init_logging(&cli);
+    let result = match &cli.command {
+        None => generate(&cli.generate, &registry),
+        Some(Command::Generate(args)) => generate(args, &registry),
+        Some(Command::List { what }) => list(what),
+        Some(Command::Config { what }) => config(what),
+        Some(Command::Import { what }) => import(what, &registry),
+        Some(Command::Report { what }) => report(what),
+        Some(Command::Export { what }) => export(what),
+        Some(Command::Db { what }) => db(what),
+        Some(Command::Verify(args)) => verify(args),
+        Some(Command::Run { what }) => run_tool(what),
+        Some(Command::Intercept(args)) => intercept(args),
+        Some(Command::Serve(args)) => serve(args),
+        Some(Command::Init(args)) => init::init(args.force, args.interactive),
+        Some(Command::Completions { shell }) => completions(shell),
+    };
+    match result {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(message) => {
+            diag::error(&message);
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
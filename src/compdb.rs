@@ -0,0 +1,537 @@
+use std::io::Write;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::shell;
+
+/// One entry of a JSON Compilation Database.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct CompilationEntry {
+    pub directory : String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments : Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command : Option<String>,
+    pub file : String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output : Option<String>,
+}
+
+impl CompilationEntry {
+    /// The source file with `directory` applied, for comparing entries that
+    /// spell the same file differently.
+    pub fn path(&self) -> PathBuf {
+        Path::new(&self.directory).join(&self.file)
+    }
+
+    /// The entry's argument vector, splitting `command` when the entry
+    /// comes from a database that uses the string form.
+    pub fn argv(&self) -> Vec<String> {
+        if self.arguments.is_empty() {
+            self.command.as_deref().map(shell::split).unwrap_or_default()
+        } else {
+            self.arguments.clone()
+        }
+    }
+
+    /// The entry with `rewrite` applied to its paths: `directory`, `file`,
+    /// `output`, the argument naming the file, and the values of `-o`, `-MF`,
+    /// `--sysroot` and the include options. Other arguments are left alone.
+    /// `command` entries get the `arguments` form.
+    pub fn rewrite_paths(self, rewrite : impl Fn(&str) -> String) -> CompilationEntry {
+        let entry = self.into_arguments_form();
+        let mut arguments = Vec::new();
+        for option in group_options(&entry.arguments) {
+            match option {
+                [flag, value] if PATH_FLAGS.contains(&flag.as_str()) || flag == "-o" || flag == "-MF" => arguments.extend([flag.clone(), rewrite(value)]),
+                [single] => match PATH_FLAGS.iter().chain(&["--sysroot="]).find(|flag| single.len() > flag.len() && single.starts_with(*flag)) {
+                    Some(flag) => arguments.push(format!("{}{}", flag, rewrite(&single[flag.len()..]))),
+                    None if *single == entry.file => arguments.push(rewrite(single)),
+                    None => arguments.push(single.clone()),
+                },
+                _ => arguments.extend(option.iter().cloned()),
+            }
+        }
+        CompilationEntry {
+            directory : rewrite(&entry.directory),
+            arguments,
+            command : None,
+            file : rewrite(&entry.file),
+            output : entry.output.as_deref().map(rewrite),
+        }
+    }
+
+    /// Moves the paths under a prefix of `map` (the longest one) under its
+    /// replacement.
+    pub fn map_paths(self, map : &BTreeMap<String, String>) -> CompilationEntry {
+        self.rewrite_paths(|path| {
+            map.iter()
+                .filter(|(from, _)| path.strip_prefix(from.trim_end_matches('/')).is_some_and(|rest| rest.is_empty() || rest.starts_with('/')))
+                .max_by_key(|(from, _)| from.trim_end_matches('/').len())
+                .map_or_else(|| path.to_string(), |(from, to)| format!("{}{}", to.trim_end_matches('/'), &path[from.trim_end_matches('/').len()..]))
+        })
+    }
+
+    /// Rewrites a `command` entry into the `arguments` form.
+    pub fn into_arguments_form(mut self) -> CompilationEntry {
+        self.arguments = self.argv();
+        self.command = None;
+        self
+    }
+
+    /// Rewrites an `arguments` entry into the `command` form.
+    pub fn into_command_form(mut self) -> CompilationEntry {
+        self.command = Some(shell::join(&self.argv()));
+        self.arguments = Vec::new();
+        self
+    }
+}
+
+const COMPILER_WRAPPERS : [&str; 4] = ["ccache", "sccache", "distcc", "icecc"];
+
+/// Flags whose value is the next argument; a flag and its value are one
+/// option when de-duplicating.
+pub const FLAGS_WITH_VALUE : [&str; 17] = [
+    "-include", "-include-pch", "-imacros", "-isystem", "-iquote", "-idirafter", "-isysroot",
+    "-I", "-D", "-U", "-x", "-o", "-MF", "-MT", "-MQ", "-Xclang", "-target",
+];
+
+/// Flags whose value is a path, given either as the next argument or
+/// joined to the flag.
+pub const PATH_FLAGS : [&str; 8] = ["-include-pch", "-include", "-imacros", "-isystem", "-iquote", "-idirafter", "-isysroot", "-I"];
+
+/// `options` split into single options, keeping flags and their values together.
+pub fn group_options(options : &[String]) -> Vec<&[String]> {
+    let mut grouped = Vec::new();
+    let mut i = 0;
+    while i < options.len() {
+        let len = if FLAGS_WITH_VALUE.contains(&options[i].as_str()) && i + 1 < options.len() { 2 } else { 1 };
+        grouped.push(&options[i..i + len]);
+        i += len;
+    }
+    grouped
+}
+
+/// `options` with repeated options dropped; the first occurrence keeps its place.
+pub fn dedup_options(options : &[String]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    group_options(options).into_iter().filter(|option| seen.insert(*option)).flatten().cloned().collect()
+}
+
+/// `arguments` without the compiler wrappers leading it, so that the first
+/// argument is the real compiler.
+pub fn strip_compiler_wrappers(arguments : &[String]) -> Vec<String> {
+    let is_wrapper = |argument : &String| {
+        Path::new(argument).file_stem().and_then(|s| s.to_str()).is_some_and(|s| COMPILER_WRAPPERS.contains(&s))
+    };
+    arguments.iter().skip_while(|a| is_wrapper(a)).cloned().collect()
+}
+
+/// Reads the file at `path`, or stdin when it is `-`.
+pub fn read_text(path : &Path) -> Result<String, String> {
+    if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin()).map_err(|e| format!("stdin: {}", e))
+    } else {
+        std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Reads the database at `path`, or from stdin when it is `-`.
+pub fn read_db(path : &Path) -> Result<Vec<CompilationEntry>, String> {
+    parse_db(&read_text(path)?).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn parse_db(content : &str) -> Result<Vec<CompilationEntry>, String> {
+    serde_json::from_str(content).map_err(|e| e.to_string())
+}
+
+/// Compression of a written database.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Compression {
+    Gz,
+    Zst,
+}
+
+impl Compression {
+    /// `compile_commands.json` becomes `compile_commands.json.gz`.
+    fn path_of(self, path : &Path) -> PathBuf {
+        let mut path = path.as_os_str().to_owned();
+        path.push(match self {
+            Compression::Gz => ".gz",
+            Compression::Zst => ".zst",
+        });
+        path.into()
+    }
+}
+
+/// Where the drives of Windows show up in the file system of a Unix-like
+/// environment running on it.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum PathTranslation {
+    /// `C:\x` is `/mnt/c/x`.
+    Wsl,
+    /// `C:\x` is `/c/x` (MSYS2, Git Bash, Cygwin with a `/` cygdrive prefix).
+    Msys,
+}
+
+impl PathTranslation {
+    fn mount(self) -> &'static str {
+        match self {
+            PathTranslation::Wsl => "/mnt/",
+            PathTranslation::Msys => "/",
+        }
+    }
+
+    /// `path` in the other form: a drive path (`C:\x`, `C:/x`) becomes a
+    /// mount path (`/mnt/c/x`) and a mount path a forward-slashed drive
+    /// path. Other paths are returned as they are.
+    pub fn translate(self, path : &str) -> String {
+        let bytes = path.as_bytes();
+        if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && (bytes.len() == 2 || bytes[2] == b'/' || bytes[2] == b'\\') {
+            let rest = path[2..].replace('\\', "/");
+            return format!("{}{}{}", self.mount(), path[..1].to_ascii_lowercase(), rest);
+        }
+        let Some(rest) = path.strip_prefix(self.mount()) else {
+            return path.into();
+        };
+        let bytes = rest.as_bytes();
+        if !bytes.is_empty() && bytes[0].is_ascii_alphabetic() && (bytes.len() == 1 || bytes[1] == b'/') {
+            let rest = if rest.len() == 1 { "/" } else { &rest[1..] };
+            return format!("{}:{}", (bytes[0] as char).to_ascii_uppercase(), rest);
+        }
+        path.into()
+    }
+}
+
+/// How `write_db` lays the database out.
+#[derive(Clone)]
+pub struct WriteOptions {
+    /// Spaces per nesting level; none writes a single line.
+    pub indent : Option<usize>,
+    /// Write `<path>.gz`/`<path>.zst` instead of `path`.
+    pub compress : Option<Compression>,
+    /// With `compress`, also write the plain `path`.
+    pub keep_plain : bool,
+    /// Split into `<stem>.partNN.<ext>` files of this many entries, listed
+    /// by `<stem>.manifest.json`.
+    pub chunk_size : Option<usize>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions { indent : Some(2), compress : None, keep_plain : false, chunk_size : None }
+    }
+}
+
+fn serialize(out : &mut dyn Write, entries : &[CompilationEntry], indent : Option<usize>) -> Result<(), String> {
+    match indent {
+        Some(indent) => {
+            let indent = " ".repeat(indent);
+            let mut serializer = serde_json::Serializer::with_formatter(out, serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes()));
+            entries.serialize(&mut serializer).map_err(|e| e.to_string())
+        }
+        None => serde_json::to_writer(out, entries).map_err(|e| e.to_string()),
+    }
+}
+
+/// Lists the parts of a database written with `chunk_size`.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    entries : usize,
+    /// File names, relative to the manifest.
+    parts : Vec<String>,
+}
+
+/// The lock of a database being written; its file is removed when dropped.
+#[derive(Debug)]
+pub struct OutputLock {
+    file : std::fs::File,
+    path : PathBuf,
+}
+
+impl Drop for OutputLock {
+    fn drop(&mut self) {
+        // Removed while still locked, so a run waiting on it sees it gone.
+        let _ = std::fs::remove_file(&self.path);
+        let _ = self.file.unlock();
+    }
+}
+
+/// Takes the advisory lock of the database at `path` (`<path>.lock`), so
+/// that two runs writing it do not interleave; it is held, and the lock
+/// file kept, until the lock returned is dropped. Waits for the run holding
+/// it with `wait`, fails otherwise. Stdout needs no lock.
+pub fn lock_output(path : &Path, wait : bool) -> Result<Option<OutputLock>, String> {
+    if path == Path::new("-") {
+        return Ok(None);
+    }
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let lock_path = PathBuf::from(lock_path);
+    loop {
+        let file = std::fs::OpenOptions::new().create(true).truncate(false).write(true).open(&lock_path).map_err(|e| format!("{}: {}", lock_path.display(), e))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(std::fs::TryLockError::WouldBlock) if wait => {
+                eprintln!("waiting for another run writing {} to finish", path.display());
+                file.lock().map_err(|e| format!("{}: {}", lock_path.display(), e))?;
+            }
+            Err(std::fs::TryLockError::WouldBlock) => {
+                return Err(format!("another run is writing {} (it holds {}); retry, or pass --wait-for-lock", path.display(), lock_path.display()));
+            }
+            Err(std::fs::TryLockError::Error(e)) => return Err(format!("{}: {}", lock_path.display(), e)),
+        }
+        // Locked after its holder removed it: another run may hold a new one.
+        if is_file_at(&file, &lock_path) {
+            return Ok(Some(OutputLock { file, path : lock_path }));
+        }
+    }
+}
+
+/// Whether `file` is the file at `path`, not one removed from there.
+#[cfg(unix)]
+fn is_file_at(file : &std::fs::File, path : &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(open), Ok(current)) => (open.dev(), open.ino()) == (current.dev(), current.ino()),
+        _ => false,
+    }
+}
+
+/// Whether `file` is the file at `path`, not one removed from there. Windows
+/// keeps the name of a removed file taken until its last handle is closed,
+/// so a file at the path is the one still open.
+#[cfg(not(unix))]
+fn is_file_at(_file : &std::fs::File, path : &Path) -> bool {
+    path.exists()
+}
+
+/// Writes `entries` to `path`, or to stdout when it is `-`.
+pub fn write_db(path : &Path, entries : &[CompilationEntry], options : &WriteOptions) -> Result<(), String> {
+    let started = std::time::Instant::now();
+    match options.chunk_size {
+        Some(chunk_size) => {
+            if path == Path::new("-") {
+                return Err("--chunk-size needs an output file, not stdout".into());
+            }
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+            let chunks : Vec<&[CompilationEntry]> = entries.chunks(chunk_size.max(1)).collect();
+            let width = (chunks.len().max(1) - 1).to_string().len().max(2);
+            let mut manifest = Manifest { entries : entries.len(), parts : Vec::new() };
+            for (i, chunk) in chunks.into_iter().enumerate() {
+                let part = format!("{}.part{:0width$}{}", stem, i, extension);
+                write_file(&path.with_file_name(&part), chunk, options)?;
+                manifest.parts.push(match options.compress {
+                    Some(compression) if !options.keep_plain => compression.path_of(Path::new(&part)).to_string_lossy().into(),
+                    _ => part,
+                });
+            }
+            let manifest_path = manifest_path(path);
+            let json = serde_json::to_string_pretty(&manifest).unwrap();
+            std::fs::write(&manifest_path, json).map_err(|e| format!("{}: {}", manifest_path.display(), e))?;
+        }
+        None => write_file(path, entries, options)?,
+    }
+    tracing::info!(path = %path.display(), entries = entries.len(), serialize_ms = started.elapsed().as_millis() as u64, "database written");
+    Ok(())
+}
+
+fn manifest_path(path : &Path) -> PathBuf {
+    path.with_file_name(format!("{}.manifest.json", path.file_stem().unwrap_or_default().to_string_lossy()))
+}
+
+/// The files `write_file` writes for `path`.
+fn file_outputs(path : &Path, options : &WriteOptions) -> Vec<PathBuf> {
+    match options.compress {
+        Some(compression) if options.keep_plain => vec![compression.path_of(path), path.to_path_buf()],
+        Some(compression) => vec![compression.path_of(path)],
+        None => vec![path.to_path_buf()],
+    }
+}
+
+/// The files of the database `write_db` wrote to `path` with `options`:
+/// the manifest and the parts it lists when chunked. None when one of them
+/// is missing.
+pub fn written_files(path : &Path, options : &WriteOptions) -> Option<Vec<PathBuf>> {
+    let files = match options.chunk_size {
+        Some(_) => {
+            let manifest_path = manifest_path(path);
+            let manifest : Manifest = serde_json::from_slice(&std::fs::read(&manifest_path).ok()?).ok()?;
+            let parts = manifest.parts.iter().map(|part| {
+                // The manifest lists the compressed part, or the plain one with keep_plain.
+                let plain = match options.compress {
+                    Some(_) if !options.keep_plain => Path::new(part).with_extension(""),
+                    _ => PathBuf::from(part),
+                };
+                file_outputs(&path.with_file_name(plain), options)
+            });
+            std::iter::once(manifest_path).chain(parts.flatten()).collect()
+        }
+        None => file_outputs(path, options),
+    };
+    files.iter().all(|file| file.exists()).then_some(files)
+}
+
+fn write_file(path : &Path, entries : &[CompilationEntry], options : &WriteOptions) -> Result<(), String> {
+    let to_stdout = path == Path::new("-");
+    let create = |path : &Path| -> Result<std::io::BufWriter<Box<dyn Write>>, String> {
+        let out : Box<dyn Write> = if to_stdout {
+            Box::new(std::io::stdout().lock())
+        } else {
+            Box::new(std::fs::File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?)
+        };
+        Ok(std::io::BufWriter::new(out))
+    };
+    fn io_error(path : &Path) -> impl Fn(std::io::Error) -> String + '_ {
+        move |e| format!("{}: {}", path.display(), e)
+    }
+
+    if let Some(compression) = options.compress {
+        let compressed = if to_stdout { path.to_path_buf() } else { compression.path_of(path) };
+        let out = create(&compressed)?;
+        let mut out = match compression {
+            Compression::Gz => {
+                let mut encoder = flate2::write::GzEncoder::new(out, flate2::Compression::default());
+                serialize(&mut encoder, entries, options.indent)?;
+                encoder.finish().map_err(io_error(&compressed))?
+            }
+            Compression::Zst => {
+                let mut encoder = zstd::Encoder::new(out, 0).map_err(io_error(&compressed))?;
+                serialize(&mut encoder, entries, options.indent)?;
+                encoder.finish().map_err(io_error(&compressed))?
+            }
+        };
+        out.flush().map_err(io_error(&compressed))?;
+    }
+    if options.compress.is_none() || (options.keep_plain && !to_stdout) {
+        let mut out = create(path)?;
+        serialize(&mut out, entries, options.indent)?;
+        out.flush().map_err(io_error(path))?;
+    }
+    Ok(())
+}
+
+/// `base` with the entries of the files in `fresh` put where the file's
+/// first entry stood; files new to `base` are appended.
+pub fn update(base : Vec<CompilationEntry>, fresh : Vec<CompilationEntry>) -> Vec<CompilationEntry> {
+    let mut by_path = HashMap::<PathBuf, Vec<usize>>::new();
+    for (i, entry) in fresh.iter().enumerate() {
+        by_path.entry(entry.path()).or_default().push(i);
+    }
+    let mut fresh : Vec<Option<CompilationEntry>> = fresh.into_iter().map(Some).collect();
+    let mut replaced = HashSet::new();
+    let mut updated = Vec::new();
+    for entry in base {
+        let path = entry.path();
+        if let Some(indices) = by_path.get(&path) {
+            if replaced.insert(path) {
+                updated.extend(indices.iter().filter_map(|&i| fresh[i].take()));
+            }
+        } else {
+            updated.push(entry);
+        }
+    }
+    updated.extend(fresh.into_iter().flatten());
+    updated
+}
+
+/// Adds `overlay` to `base`; an overlay entry replaces every base entry for
+/// the same file.
+pub fn merge(base : Vec<CompilationEntry>, overlay : Vec<CompilationEntry>) -> Vec<CompilationEntry> {
+    let overlaid : HashSet<PathBuf> = overlay.iter().map(|e| e.path()).collect();
+    let mut merged : Vec<CompilationEntry> = base.into_iter().filter(|e| !overlaid.contains(&e.path())).collect();
+    merged.extend(overlay);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    use super::{lock_output, write_db, written_files, CompilationEntry, Compression, PathTranslation, WriteOptions};
+
+    #[test]
+    fn waiting_run_takes_a_new_lock_after_the_holder_drops_its_own() {
+        let dir = std::env::temp_dir().join(format!("compdb-lock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("compile_commands.json");
+        let lock_path = dir.join("compile_commands.json.lock");
+
+        let first = lock_output(&output, false).unwrap();
+        assert!(lock_output(&output, false).unwrap_err().contains("another run is writing"));
+        let waiting = std::thread::spawn({
+            let output = output.clone();
+            move || lock_output(&output, true)
+        });
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        drop(first);
+        let second = waiting.join().unwrap().unwrap();
+        // The waiting run locked the removed file first, then a new one at the path.
+        assert!(lock_path.exists());
+        assert!(lock_output(&output, false).is_err());
+        drop(second);
+        assert!(!lock_path.exists());
+        assert!(lock_output(Path::new("-"), false).unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn written_files_lists_the_manifest_and_its_compressed_parts() {
+        let dir = std::env::temp_dir().join(format!("compdb-written-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("compile_commands.json");
+        let entry = |file : &str| CompilationEntry { directory : "/src".into(), arguments : vec!["cc".into(), "-c".into(), file.into()], command : None, file : file.into(), output : None };
+        let options = WriteOptions { compress : Some(Compression::Gz), chunk_size : Some(2), ..Default::default() };
+
+        assert_eq!(written_files(&output, &options), None);
+        write_db(&output, &[entry("a.c"), entry("b.c"), entry("c.c")], &options).unwrap();
+        let expected = ["compile_commands.manifest.json", "compile_commands.part00.json.gz", "compile_commands.part01.json.gz"].map(|name| dir.join(name));
+        assert_eq!(written_files(&output, &options).unwrap(), expected);
+        // The plain output was never written.
+        assert_eq!(written_files(&output, &WriteOptions::default()), None);
+        std::fs::remove_file(&expected[2]).unwrap();
+        assert_eq!(written_files(&output, &options), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn map_paths_moves_whole_directories_under_the_longest_prefix() {
+        let map = BTreeMap::from([("/src".to_string(), "/work".to_string()), ("/src/vendor/".to_string(), "/vendor".to_string())]);
+        let arguments = ["cc", "-I/src/inc", "-I", "/srcfoo/inc", "-isystem", "/src/vendor/x", "-DROOT=/src", "-o", "/src/a.o", "-c", "/src/a.c"];
+        let entry = CompilationEntry { directory : "/src".into(), arguments : arguments.map(String::from).to_vec(), command : None, file : "/src/a.c".into(), output : Some("/src/a.o".into()) };
+        let mapped = entry.map_paths(&map);
+        assert_eq!(mapped.directory, "/work");
+        assert_eq!(mapped.file, "/work/a.c");
+        assert_eq!(mapped.output.as_deref(), Some("/work/a.o"));
+        // Only path arguments move, and /srcfoo is not under /src.
+        assert_eq!(mapped.arguments, ["cc", "-I/work/inc", "-I", "/srcfoo/inc", "-isystem", "/vendor/x", "-DROOT=/src", "-o", "/work/a.o", "-c", "/work/a.c"]);
+    }
+
+    #[test]
+    fn translation_between_drive_and_mount_paths() {
+        for (translation, mount) in [(PathTranslation::Wsl, "/mnt/c"), (PathTranslation::Msys, "/c")] {
+            assert_eq!(translation.translate(r"C:\x\y.h"), format!("{}/x/y.h", mount));
+            assert_eq!(translation.translate("c:/x"), format!("{}/x", mount));
+            assert_eq!(translation.translate(&format!("{}/x", mount)), "C:/x");
+            assert_eq!(translation.translate(mount), "C:/");
+            assert_eq!(translation.translate("relative/x"), "relative/x");
+        }
+        // Not drive mounts.
+        assert_eq!(PathTranslation::Wsl.translate("/mnt/cdrom/x"), "/mnt/cdrom/x");
+        assert_eq!(PathTranslation::Msys.translate("/usr/include"), "/usr/include");
+        assert_eq!(PathTranslation::Msys.translate("cc:x"), "cc:x");
+
+        let arguments = [r"C:\bin\gcc.exe", r"-IC:\inc", "-isystem", "D:/sdk", r"--sysroot=C:\sys", "-DDIR=C:/x", "-c", r"C:\src\a.c"];
+        let entry = CompilationEntry { directory : r"C:\src".into(), arguments : arguments.map(String::from).to_vec(), command : None, file : r"C:\src\a.c".into(), output : None };
+        let translated = entry.rewrite_paths(|path| PathTranslation::Wsl.translate(path));
+        assert_eq!(translated.directory, "/mnt/c/src");
+        assert_eq!(translated.file, "/mnt/c/src/a.c");
+        // The compiler and defines are not paths to rewrite.
+        assert_eq!(translated.arguments, [r"C:\bin\gcc.exe", "-I/mnt/c/inc", "-isystem", "/mnt/d/sdk", "--sysroot=/mnt/c/sys", "-DDIR=C:/x", "-c", "/mnt/c/src/a.c"]);
+    }
+}
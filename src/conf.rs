@@ -0,0 +1,1096 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::compdb;
+use crate::diag::{KeyPath, located};
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct CompDBConf {
+    /// Schema version, see `CONF_VERSION`.
+    pub version : Option<i64>,
+    pub common : CommonConf,
+    #[serde(default)]
+    pub workspace : Vec<WorkSpaceConf>,
+    pub discover : Option<Vec<DiscoverConf>>,
+    #[serde(default)]
+    pub include_group : BTreeMap<String, IncludeConf>,
+    #[serde(default)]
+    pub option_group : BTreeMap<String, OptionConf>,
+    #[serde(default)]
+    pub policy : PolicyConf,
+    /// Path prefixes rewritten in the written database, e.g.
+    /// `"/workspace" = "/home/user/project"` for a database generated in a
+    /// build container and read by clangd on the host.
+    #[serde(default)]
+    pub path_map : BTreeMap<String, String>,
+    #[serde(default)]
+    pub hooks : HooksConf,
+    pub test : Option<TestConf>,
+    pub iwyu : Option<IwyuConf>,
+    pub clang_tidy : Option<ClangTidyConf>,
+}
+
+/// How `run clang-tidy` runs clang-tidy.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ClangTidyConf {
+    /// The `--checks` of every workspace, before the command line's.
+    pub checks : Option<String>,
+}
+
+/// How `run iwyu` runs include-what-you-use.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct IwyuConf {
+    /// `.imp` mapping files, relative to `common.root_dir`, e.g. for a
+    /// vendor SDK whose umbrella headers IWYU should suggest.
+    pub mapping_file : Option<Vec<String>>,
+}
+
+/// How the unit-test database (`generate --test`) differs from the normal
+/// one, the way CppUTest/GoogleTest harnesses build production sources.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct TestConf {
+    /// Include roots searched before every other one, e.g. `test/mocks`
+    /// and `test/stubs`; relative to `common.root_dir`.
+    pub include_root : Option<Vec<String>>,
+    /// Macros defined for every file, e.g. `UNIT_TEST`.
+    pub define : Option<Vec<String>>,
+}
+
+/// The include group `[test]` adds to every workspace.
+const TEST_INCLUDE_GROUP : &str = "[test]";
+
+/// Commands run around the generation, from `common.root_dir`, with the
+/// database path in `COMPDB_OUTPUT`; a failing one fails the run.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct HooksConf {
+    /// Before walking, e.g. to run code generators whose output is compiled.
+    pub pre : Option<Vec<String>>,
+    /// After the database is written, e.g. to rebuild an index.
+    pub post : Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PolicyLevel {
+    Ignore,
+    #[default]
+    Warn,
+    Error,
+}
+
+/// What each category of warning does; `--strict` turns `warn` into `error`.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct PolicyConf {
+    /// An include root that does not exist or holds no headers.
+    pub missing_include_dir : Option<PolicyLevel>,
+    /// A workspace without any target file.
+    pub empty_workspace : Option<PolicyLevel>,
+    /// A file claimed by several workspaces.
+    pub duplicate_file : Option<PolicyLevel>,
+    /// Contradictory options in an entry, e.g. `-O0` and `-O2`.
+    pub option_conflict : Option<PolicyLevel>,
+    /// Every other warning of the generation.
+    pub other : Option<PolicyLevel>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct CommonConf {
+    pub c_compiler : Vec<String>,
+    pub cpp_compiler : Vec<String>,
+    /// Compiler of `.s`/`.S` files; `c_compiler` by default.
+    pub asm_compiler : Option<Vec<String>>,
+    /// Compiler of Objective-C `.m` files; `c_compiler` by default.
+    pub objc_compiler : Option<Vec<String>>,
+    /// Compiler of Objective-C++ `.mm` files; `cpp_compiler` by default.
+    pub objcxx_compiler : Option<Vec<String>>,
+    /// Compiler of CUDA `.cu` files; `cpp_compiler` by default. Meant for
+    /// clang, which takes the `-x cuda` and `--cuda-path` the entries get.
+    pub cuda_compiler : Option<Vec<String>>,
+    /// CUDA toolkit installation passed as `--cuda-path`.
+    pub cuda_path : Option<String>,
+    /// Relative to the configuration file; its directory by default.
+    #[serde(default = "default_root_dir")]
+    pub root_dir : String,
+    pub target : TargetConf,
+    pub include : Option<IncludeConf>,
+    pub option : Option<OptionConf>,
+    pub pkg_config : Option<Vec<String>>,
+    pub packages : Option<PackageConf>,
+    /// Sysroot of a cross SDK (e.g. a Yocto SDK target sysroot), relative to `root_dir`.
+    pub sysroot : Option<String>,
+    /// Also pass the sysroot's `usr/include` (and the multiarch and GCC
+    /// include directories under it) as `-isystem`.
+    pub sysroot_system_includes : Option<bool>,
+    #[serde(flatten)]
+    pub machine : MachineConf,
+    /// Drop compiler wrappers (ccache, sccache, distcc, icecc) in front of
+    /// `c_compiler`/`cpp_compiler`; kept by default.
+    pub strip_wrappers : Option<bool>,
+    /// Version constraint for the compilers checked by `config check`,
+    /// e.g. `"12"` or `">=11.3, <13"`.
+    pub expected_version : Option<String>,
+    /// Walk dot-files and dot-directories (`.git`, editor metadata); skipped by default.
+    pub include_hidden : Option<bool>,
+    /// Where candidate files come from before `target` patterns apply.
+    pub source : Option<FileSource>,
+    /// What to do with include dirs no `#include` of the targets resolves through.
+    pub unused_include_dirs : Option<UnusedIncludeDirs>,
+    /// Leave `.s`/`.S` files out even when the target patterns match them.
+    pub exclude_assembly : Option<bool>,
+    /// What to do with unity (jumbo) files, which only `#include` other sources.
+    pub unity : Option<UnityMode>,
+    /// Layout of the generated arguments, e.g. `["{compiler}", "{defines}",
+    /// "{includes}", "{options}", "-c", "{file}", "-o", "{output}"]`.
+    /// Default: compiler, options, `-c`, file.
+    pub argument_template : Option<Vec<String>>,
+    /// What the `directory` of the entries is; the relative paths the
+    /// generator emits (files, include dirs, forced includes) follow it.
+    pub directory_mode : Option<DirectoryMode>,
+    /// Per-file option rules applied to every workspace, before its own.
+    pub file_option : Option<Vec<FileOptionConf>>,
+    /// Language standard of C entries, e.g. `"c11"`; emitted as `-std=`.
+    pub c_std : Option<String>,
+    /// Language standard of C++ entries, e.g. `"c++17"`.
+    pub cpp_std : Option<String>,
+    /// Headers injected into every file with `-include`, relative to `root_dir`.
+    pub force_include : Option<Vec<String>>,
+    /// Precompiled header passed with `-include-pch`, relative to `root_dir`.
+    pub pch : Option<String>,
+    /// What to do with a file that several workspaces produce an entry for.
+    pub duplicate_policy : Option<DuplicatePolicy>,
+    /// Bounds on the entries of the whole database; outside them the
+    /// generation fails rather than writing a half-empty database.
+    pub min_entries : Option<usize>,
+    pub max_entries : Option<usize>,
+    /// Spaces per nesting level of the written database (default 2).
+    pub indent : Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DirectoryMode {
+    /// `common.root_dir`.
+    #[default]
+    Root,
+    /// The directory of the entry's workspace.
+    Workspace,
+    /// The directory of the entry's file.
+    File,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileSource {
+    /// Walk the workspace directory.
+    #[default]
+    Walk,
+    /// Take the files `git ls-files` reports for the workspace directory;
+    /// untracked build output never shows up. `max_depth`, `prune_dirs`
+    /// and `include_hidden` do not apply.
+    Git,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnusedIncludeDirs {
+    /// Emit every discovered directory.
+    #[default]
+    Keep,
+    /// Emit every directory and list the unused ones on stderr.
+    Report,
+    /// Leave the unused directories out of the entries.
+    Drop,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnityMode {
+    /// Treat unity files like any other target.
+    #[default]
+    Keep,
+    /// Also emit an entry, with the unity file's flags, for each source it includes.
+    Expand,
+    /// Emit entries for the included sources instead of the unity file.
+    Replace,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicatePolicy {
+    /// Keep the entry of the workspace listed first.
+    First,
+    /// Keep the entry of the workspace listed last.
+    Last,
+    /// Fail the generation.
+    Error,
+    /// Write every entry (the behaviour before the setting existed).
+    #[default]
+    KeepAll,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct WorkSpaceConf {
+    pub name : Option<String>,
+    pub path : String,
+    /// Workspaces whose own include roots are added to this one, transitively.
+    pub uses : Option<Vec<String>>,
+    pub include_groups : Option<Vec<String>>,
+    pub option_groups : Option<Vec<String>>,
+    pub target : Option<TargetConf>,
+    pub include : Option<IncludeConf>,
+    pub option : Option<OptionConf>,
+    /// pkg-config packages whose `-I`/`-D` flags are appended to the entries.
+    pub pkg_config : Option<Vec<String>>,
+    pub packages : Option<PackageConf>,
+    /// Replace the common sysroot settings for this workspace.
+    pub sysroot : Option<String>,
+    pub sysroot_system_includes : Option<bool>,
+    /// Overrides the common machine settings key by key.
+    #[serde(flatten)]
+    pub machine : MachineConf,
+    pub strip_wrappers : Option<bool>,
+    pub include_hidden : Option<bool>,
+    pub source : Option<FileSource>,
+    pub unused_include_dirs : Option<UnusedIncludeDirs>,
+    pub exclude_assembly : Option<bool>,
+    pub unity : Option<UnityMode>,
+    pub argument_template : Option<Vec<String>>,
+    pub directory_mode : Option<DirectoryMode>,
+    pub file_option : Option<Vec<FileOptionConf>>,
+    pub c_std : Option<String>,
+    pub cpp_std : Option<String>,
+    /// Added to the common forced includes.
+    pub force_include : Option<Vec<String>>,
+    /// Replaces the common precompiled header.
+    pub pch : Option<String>,
+    /// The exact files to generate entries for, relative to the workspace;
+    /// replaces pattern matching.
+    pub files : Option<Vec<String>>,
+    /// A manifest listing the files, one per line (`#` starts a comment),
+    /// relative to the workspace; replaces pattern matching.
+    pub file_list : Option<String>,
+    /// Build outputs compiled as sources (e.g. protobuf or AUTOSAR RTE
+    /// code), relative to the workspace; added to the other targets.
+    pub generated_files : Option<Vec<String>>,
+    /// Emit entries for `generated_files` that do not exist yet instead of
+    /// failing, so the database is usable before the first build.
+    pub allow_missing : Option<bool>,
+    /// Only generated into the unit-test database (`generate --test`),
+    /// e.g. the tests themselves.
+    pub test_only : Option<bool>,
+    /// Appended to the clang-tidy checks for this workspace, e.g.
+    /// `-readability-*` for vendor code; later globs win.
+    pub clang_tidy_checks : Option<String>,
+    /// Bounds on the entries of this workspace.
+    pub min_entries : Option<usize>,
+    pub max_entries : Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct TargetConf {
+    pub match_pattern : Option<Vec<String>>,
+    pub ignore_pattern : Option<Vec<String>>,
+    /// How deep below the workspace directory files are looked for.
+    pub max_depth : Option<usize>,
+    /// Directory names (e.g. `build`, `.git`) not descended into.
+    pub prune_dirs : Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct IncludeConf {
+    pub root_dir : Option<Vec<String>>,
+    pub ignore_pattern : Option<Vec<String>>,
+    /// Roots of a higher priority are searched first (default 0); equal
+    /// priorities keep the order common, groups, workspace, used workspaces.
+    pub priority : Option<i64>,
+    /// How deep below each root directories are emitted.
+    pub max_depth : Option<usize>,
+    /// Directory names not descended into.
+    pub prune_dirs : Option<Vec<String>>,
+    /// The roots may be absent, e.g. headers generated into the build
+    /// directory: such a root is emitted as it is, without a warning.
+    pub optional : Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct OptionConf {
+    pub arg : Option<Vec<String>>,
+    /// Options inherited from earlier layers (common, then groups) that
+    /// this layer drops, e.g. `-Werror` for a module that cannot build with it.
+    pub remove_arg : Option<Vec<String>>,
+}
+
+/// Options for the files whose path (relative to `root_dir`) matches `pattern`.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct FileOptionConf {
+    pub pattern : String,
+    /// Appended to the options of the matching files.
+    pub arg : Option<Vec<String>>,
+    /// Options the matching files do not get, e.g. `-Werror`.
+    pub remove_arg : Option<Vec<String>>,
+}
+
+/// Package-manager outputs whose include directories and defines are added
+/// to the entries. Relative paths are resolved against `common.root_dir`.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Hash)]
+pub struct PackageConf {
+    /// `conanbuildinfo.json` written by Conan 1's `json` generator, or the
+    /// folder Conan 2's `CMakeDeps` generator wrote to (its `conan_toolchain.cmake`)
+    pub conan : Option<String>,
+    /// vcpkg installed tree for one triplet, e.g. `vcpkg_installed/x64-linux`
+    pub vcpkg : Option<String>,
+}
+
+/// The machine the code is compiled for, so that clangd analyzes it as the
+/// embedded target rather than the host.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct MachineConf {
+    /// `--target=`, e.g. `arm-none-eabi`
+    pub target_triple : Option<String>,
+    /// `-mcpu=`, e.g. `cortex-m4`
+    pub cpu : Option<String>,
+    /// `-march=`, e.g. `armv7e-m`
+    pub arch : Option<String>,
+    /// `-mfpu=`, e.g. `fpv4-sp-d16`
+    pub fpu : Option<String>,
+    /// `-mfloat-abi=`: `soft`, `softfp` or `hard`
+    pub float_abi : Option<String>,
+}
+
+impl MachineConf {
+    fn or(&self, fallback : &MachineConf) -> MachineConf {
+        MachineConf {
+            target_triple : self.target_triple.clone().or_else(|| fallback.target_triple.clone()),
+            cpu : self.cpu.clone().or_else(|| fallback.cpu.clone()),
+            arch : self.arch.clone().or_else(|| fallback.arch.clone()),
+            fpu : self.fpu.clone().or_else(|| fallback.fpu.clone()),
+            float_abi : self.float_abi.clone().or_else(|| fallback.float_abi.clone()),
+        }
+    }
+
+    pub fn flags(&self) -> Vec<String> {
+        self.keyed_flags().into_iter().map(|(_, flag)| flag).collect()
+    }
+
+    /// The flags with the keys they come from.
+    fn keyed_flags(&self) -> Vec<(&'static str, String)> {
+        [("target_triple", "--target=", &self.target_triple), ("cpu", "-mcpu=", &self.cpu), ("arch", "-march=", &self.arch), ("fpu", "-mfpu=", &self.fpu), ("float_abi", "-mfloat-abi=", &self.float_abi)]
+            .into_iter()
+            .filter_map(|(key, flag, value)| value.as_ref().map(|v| (key, format!("{}{}", flag, v))))
+            .collect()
+    }
+}
+
+/// Finds workspaces by marker file: every directory under `root` that
+/// contains `marker` becomes a workspace with the settings given here.
+#[derive(Serialize, Deserialize, Default)]
+pub struct DiscoverConf {
+    pub root : Option<String>,
+    pub marker : String,
+    pub include_groups : Option<Vec<String>>,
+    pub option_groups : Option<Vec<String>>,
+    pub target : Option<TargetConf>,
+    pub include : Option<IncludeConf>,
+    pub option : Option<OptionConf>,
+}
+
+impl WorkSpaceConf {
+    /// Whether `id` refers to this workspace, either by its name or its path.
+    pub fn is_called(&self, id : &str) -> bool {
+        self.name.as_deref() == Some(id) || self.path == id
+    }
+}
+
+/// The configuration a single workspace is generated from: the common
+/// settings merged with the workspace's own, include roots resolved to paths.
+#[derive(Serialize, Default)]
+pub struct EffectiveConf {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name : Option<String>,
+    pub path : String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub uses : Vec<String>,
+    pub c_compiler : Vec<String>,
+    pub cpp_compiler : Vec<String>,
+    pub asm_compiler : Vec<String>,
+    pub objc_compiler : Vec<String>,
+    pub objcxx_compiler : Vec<String>,
+    pub cuda_compiler : Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cuda_path : Option<String>,
+    /// `common.root_dir` resolved: absolute, the paths below are relative to it.
+    pub root_dir : String,
+    pub target : TargetConf,
+    pub include : IncludeConf,
+    /// The roots of `include` that may be absent.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub optional_include_roots : Vec<String>,
+    pub option : OptionConf,
+    /// The layer each argument of `option.arg`, the `-std=` flags and the
+    /// machine and sysroot flags come from, e.g. `option_group.fast` or
+    /// `workspace.cpu`; the last one when several add it.
+    #[serde(skip)]
+    pub option_origin : HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub argument_template : Option<Vec<String>>,
+    pub directory_mode : DirectoryMode,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub file_option : Vec<FileOptionConf>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pkg_config : Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub packages : Vec<PackageConf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sysroot : Option<String>,
+    pub sysroot_system_includes : bool,
+    pub include_hidden : bool,
+    pub source : FileSource,
+    pub unused_include_dirs : UnusedIncludeDirs,
+    pub exclude_assembly : bool,
+    pub unity : UnityMode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub c_std : Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpp_std : Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub force_include : Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pch : Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files : Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_list : Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub generated_files : Vec<String>,
+    pub allow_missing : bool,
+    #[serde(flatten)]
+    pub machine : MachineConf,
+}
+
+fn default_root_dir() -> String {
+    ".".into()
+}
+
+/// Names looked for when no configuration file is given, in this order.
+pub const CONFIG_FILE_NAMES : [&str; 8] = [
+    "compdb.toml", "compdb.yaml", "compdb.yml", "compdb.json",
+    ".compdbgen.toml", ".compdbgen.yaml", ".compdbgen.yml", ".compdbgen.json",
+];
+
+/// The nearest configuration file in the current directory or above it.
+pub fn find_conf() -> Result<PathBuf, String> {
+    let current_dir = std::env::current_dir().map_err(|e| e.to_string())?;
+    current_dir.ancestors()
+        .flat_map(|dir| CONFIG_FILE_NAMES.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+        .ok_or_else(|| format!("No configuration file given and no {} found in {} or above", CONFIG_FILE_NAMES.join(" or "), current_dir.display()))
+}
+
+/// Later tables are merged key by key; any other later value replaces the earlier one.
+fn merge_tables(base : &mut toml::Table, overlay : toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge_tables(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Applies a `--set` override, `key.path=value` or `key.path+=value` (appended
+/// to an array). The value is read as TOML, or as a plain string otherwise.
+fn apply_override(conf : &mut toml::Table, set : &str) -> Result<(), String> {
+    let (path, value) = set.split_once('=').ok_or_else(|| format!("--set {}: expected KEY=VALUE", set))?;
+    let (path, append) = match path.strip_suffix('+') {
+        Some(path) => (path, true),
+        None => (path, false),
+    };
+    let value = toml::from_str::<toml::Table>(&format!("value = {}", value)).ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.into()));
+
+    let mut keys : Vec<&str> = path.trim().split('.').collect();
+    let last = keys.pop().filter(|key| !key.is_empty()).ok_or_else(|| format!("--set {}: missing key", set))?;
+    let mut table = conf;
+    for key in keys {
+        table = table.entry(key).or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut().ok_or_else(|| format!("--set {}: {} is not a table", set, key))?;
+    }
+    if !append {
+        table.insert(last.into(), value);
+        return Ok(());
+    }
+    let array = table.entry(last).or_insert_with(|| toml::Value::Array(Vec::new()))
+        .as_array_mut().ok_or_else(|| format!("--set {}: {} is not an array", set, last))?;
+    match value {
+        toml::Value::Array(values) => array.extend(values),
+        value => array.push(value),
+    }
+    Ok(())
+}
+
+/// Developer-specific overrides merged last when found beside the first
+/// configuration file, the first of them that exists; meant to be gitignored.
+pub const LOCAL_CONFIG_FILE_NAMES : [&str; 4] = ["compdb.local.toml", "compdb.local.yaml", "compdb.local.yml", "compdb.local.json"];
+
+/// Current schema version. Files without `version` predate versioning and
+/// count as version 1.
+pub const CONF_VERSION : i64 = 1;
+
+/// `MIGRATIONS[n]` rewrites a version `n + 1` configuration into version `n + 2`.
+/// No key has been renamed or removed yet, so there is none: migrating a
+/// configuration only stamps it with `CONF_VERSION`. A breaking change bumps
+/// `CONF_VERSION` and adds the rewrite here.
+const MIGRATIONS : [fn(&mut toml::Table); CONF_VERSION as usize - 1] = [];
+
+/// Brings `conf` to `CONF_VERSION` and stamps it; false when its schema
+/// already was current.
+pub fn migrate(conf : &mut toml::Table, input : &str) -> Result<bool, String> {
+    let version = match conf.get("version") {
+        None => 1,
+        Some(toml::Value::Integer(version)) => *version,
+        Some(version) => return Err(format!("{}: version must be an integer, not {}", input, version)),
+    };
+    if !(1..=CONF_VERSION).contains(&version) {
+        return Err(format!("{}: configuration version {} is not supported; this build reads versions 1 to {}", input, version, CONF_VERSION));
+    }
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(conf);
+    }
+    conf.insert("version".into(), toml::Value::Integer(CONF_VERSION));
+    Ok(version != CONF_VERSION)
+}
+
+/// The configuration file `input`, read as `format`, migrated by `migrate`
+/// and written back as text; false when it was current and stamped already.
+pub fn migrate_file(input : &str, format : ConfigFormat) -> Result<(String, bool), String> {
+    let mut table : toml::Table = format.parse(input)?;
+    let stamped = table.contains_key("version");
+    let migrated = migrate(&mut table, input)? || !stamped;
+    Ok((format.write(&table)?, migrated))
+}
+
+/// Keys whose values are regexes.
+const PATTERN_KEYS : [&str; 3] = ["match_pattern", "ignore_pattern", "pattern"];
+
+/// Fails on the first pattern of `conf` that is not a valid regex, or the
+/// first `c_std`/`cpp_std` that is not a known standard, pointing at it in
+/// `text`, the TOML source of `input`, when given.
+fn check_values(input : &str, text : Option<&str>, conf : &toml::Table) -> Result<(), String> {
+    fn first_invalid(value : &toml::Value, path : KeyPath) -> Option<(KeyPath, String)> {
+        match value {
+            toml::Value::Table(table) => table.iter().find_map(|(key, value)| first_invalid(value, path.key(key))),
+            toml::Value::Array(values) => values.iter().enumerate().find_map(|(i, value)| first_invalid(value, path.index(i))),
+            toml::Value::String(pattern) if path.last_key().is_some_and(|key| PATTERN_KEYS.contains(&key)) => {
+                let error = regex::Regex::new(pattern).err()?.to_string();
+                // The last line of a parse error names the problem; the ones above repeat the pattern.
+                let reason = error.lines().last().unwrap_or_default();
+                Some((path, format!("invalid regex '{}': {}", pattern, reason.strip_prefix("error: ").unwrap_or(reason))))
+            }
+            toml::Value::String(standard) => {
+                let allowed : &[&str] = match path.last_key() {
+                    Some("c_std") => &C_STANDARDS,
+                    Some("cpp_std") => &CPP_STANDARDS,
+                    _ => return None,
+                };
+                (!allowed.contains(&standard.as_str())).then(|| (path, format!("\"{}\" is not one of {}", standard, allowed.join(", "))))
+            }
+            _ => None,
+        }
+    }
+
+    match conf.iter().find_map(|(key, value)| first_invalid(value, KeyPath::default().key(key))) {
+        Some((path, message)) => Err(located(input, text, &path, &message)),
+        None => Ok(()),
+    }
+}
+
+/// Syntax of a configuration file; all of them share the TOML schema.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Picked by extension, TOML unless `.yaml`/`.yml` or `.json`.
+    pub fn of(path : &str) -> ConfigFormat {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    pub fn write(self, conf : &toml::Table) -> Result<String, String> {
+        match self {
+            ConfigFormat::Toml => toml::to_string_pretty(conf).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml::to_string(conf).map_err(|e| e.to_string()),
+            ConfigFormat::Json => serde_json::to_string_pretty(conf).map(|json| json + "\n").map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn parse<T : serde::de::DeserializeOwned>(self, input : &str) -> Result<T, String> {
+        let text = std::fs::read_to_string(input).map_err(|e| format!("{}: {}", input, e))?;
+        self.parse_str(input, &text)
+    }
+
+    fn parse_str<T : serde::de::DeserializeOwned>(self, input : &str, text : &str) -> Result<T, String> {
+        match self {
+            ConfigFormat::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml::from_str(text).map_err(|e| e.to_string()),
+            ConfigFormat::Json => serde_json::from_str(text).map_err(|e| e.to_string()),
+        }.map_err(|e| format!("{}: {}", input, e))
+    }
+}
+
+/// Loads `inputs` merged left to right, then `compdb.local.*` if present,
+/// then the `overrides`. Each input is read as `format`, or as its extension
+/// suggests; the local file always as its extension suggests. Relative paths are resolved against the directory of the first
+/// file.
+pub fn load_conf(inputs : &[String], format : Option<ConfigFormat>, overrides : &[String]) -> Result<CompDBConf, String> {
+    let (input, _) = inputs.split_first().ok_or("No configuration file given")?;
+    let mut inputs : Vec<(String, ConfigFormat)> = inputs.iter().map(|input| (input.clone(), format.unwrap_or_else(|| ConfigFormat::of(input)))).collect();
+    let local = LOCAL_CONFIG_FILE_NAMES.iter().map(|name| Path::new(input).with_file_name(name)).find(|local| local.is_file());
+    if let Some(local) = local.filter(|local| !inputs.iter().any(|(input, _)| Path::new(input) == local)) {
+        tracing::info!(path = %local.display(), "merging local overrides");
+        let local = local.to_str().ok_or_else(|| format!("{}: path is not UTF-8", local.display()))?;
+        inputs.push((local.into(), ConfigFormat::of(local)));
+    }
+    let mut tables = Vec::new();
+    let mut migrated = false;
+    for (input, format) in &inputs {
+        let text = std::fs::read_to_string(input).map_err(|e| format!("{}: {}", input, e))?;
+        let mut table = format.parse_str(input, &text)?;
+        check_values(input, matches!(format, ConfigFormat::Toml).then_some(text.as_str()), &table)?;
+        migrated |= migrate(&mut table, input)?;
+        tables.push(table);
+    }
+    let mut conf : CompDBConf = if let ([(input, format)], [], false) = (inputs.as_slice(), overrides, migrated) {
+        // Parsed again directly so that errors point at the line of the file.
+        format.parse(input)?
+    } else {
+        let mut merged = toml::Table::new();
+        for table in tables {
+            merge_tables(&mut merged, table);
+        }
+        for set in overrides {
+            apply_override(&mut merged, set)?;
+        }
+        if !overrides.is_empty() {
+            check_values("--set", None, &merged)?;
+        }
+        let names : Vec<&str> = inputs.iter().map(|(input, _)| input.as_str()).collect();
+        toml::Value::Table(merged).try_into().map_err(|e| format!("{}: {}", names.join(" + "), e))?
+    };
+    conf.resolve_paths(Path::new(input))?;
+    conf.expand_workspaces()?;
+    Ok(conf)
+}
+
+/// `path` with a leading `~` replaced by the home directory.
+pub fn expand_home(path : &str) -> String {
+    let home = || std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).ok();
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => match home() {
+            Some(home) => format!("{}{}", home, rest),
+            None => path.into(),
+        },
+        _ => path.into(),
+    }
+}
+
+fn relative_slashed(path : &Path, root : &Path) -> String {
+    let relative = crate::generate::lossy(&crate::generate::get_slashed_path_without_prefix(path, root));
+    if relative.is_empty() { ".".into() } else { relative }
+}
+
+impl CompDBConf {
+    /// Expands `~` and makes `common.root_dir` absolute against the directory
+    /// of the configuration file, so the tool can run from anywhere. Paths
+    /// relative to the root stay relative.
+    fn resolve_paths(&mut self, config_file : &Path) -> Result<(), String> {
+        let config_dir = std::path::absolute(config_file).map_err(|e| format!("{}: {}", config_file.display(), e))?;
+        let config_dir = config_dir.parent().unwrap_or(Path::new("/"));
+        let root_dir : PathBuf = config_dir.join(expand_home(&self.common.root_dir)).components().filter(|c| !matches!(c, std::path::Component::CurDir)).collect();
+        self.common.root_dir = crate::generate::slashed(&root_dir);
+
+        let expand_all = |paths : &mut Option<Vec<String>>| paths.iter_mut().flatten().for_each(|p| *p = expand_home(p));
+        for include in self.common.include.iter_mut().chain(self.include_group.values_mut()).chain(self.workspace.iter_mut().filter_map(|w| w.include.as_mut())) {
+            expand_all(&mut include.root_dir);
+        }
+        for sysroot in self.common.sysroot.iter_mut().chain(self.workspace.iter_mut().filter_map(|w| w.sysroot.as_mut())) {
+            *sysroot = expand_home(sysroot);
+        }
+        if let Some(cuda_path) = &mut self.common.cuda_path {
+            *cuda_path = expand_home(cuda_path);
+        }
+        Ok(())
+    }
+
+    fn include_groups_of<'a>(&'a self, workspace : &'a WorkSpaceConf) -> impl Iterator<Item = &'a IncludeConf> {
+        workspace.include_groups.iter().flatten().filter_map(|g| self.include_group.get(g))
+    }
+
+    fn option_groups_of<'a>(&'a self, workspace : &'a WorkSpaceConf) -> impl Iterator<Item = &'a OptionConf> {
+        workspace.option_groups.iter().flatten().filter_map(|g| self.option_group.get(g))
+    }
+
+    /// Turns the configuration into that of the unit-test database with
+    /// `test`; either way leaves out the workspaces that are not for it.
+    pub fn select_variant(&mut self, test : bool) -> Result<(), String> {
+        self.workspace.retain(|workspace| test || !workspace.test_only.unwrap_or(false));
+        if !test {
+            return Ok(());
+        }
+        let Some(test_conf) = self.test.clone() else {
+            return Err("--test needs a [test] table".into());
+        };
+        if let Some(include_root) = test_conf.include_root {
+            let include = IncludeConf { root_dir : Some(include_root), priority : Some(i64::MAX), ..Default::default() };
+            self.include_group.insert(TEST_INCLUDE_GROUP.into(), include);
+            for workspace in &mut self.workspace {
+                workspace.include_groups.get_or_insert_default().push(TEST_INCLUDE_GROUP.into());
+            }
+        }
+        let defines = test_conf.define.iter().flatten().map(|define| format!("-D{}", define));
+        self.common.option.get_or_insert_default().arg.get_or_insert_default().extend(defines);
+        Ok(())
+    }
+
+    /// Replaces glob workspace paths (`apps/*/src`) with one workspace per
+    /// matching directory and appends the workspaces found by `[[discover]]`.
+    fn expand_workspaces(&mut self) -> Result<(), String> {
+        let common_root = PathBuf::from(&self.common.root_dir);
+
+        let mut expanded = Vec::<WorkSpaceConf>::new();
+        for workspace in self.workspace.drain(..) {
+            if !workspace.path.contains(['*', '?', '[']) {
+                expanded.push(workspace);
+                continue;
+            }
+
+            let pattern = common_root.join(&workspace.path);
+            let paths = glob::glob(&crate::generate::lossy(&pattern)).map_err(|e| format!("workspace path \"{}\": {}", workspace.path, e))?;
+            let mut matched = false;
+            for path in paths.filter_map(|p| p.ok()).filter(|p| p.is_dir()) {
+                matched = true;
+                expanded.push(WorkSpaceConf {
+                    name : workspace.name.as_ref().map(|name| format!("{}:{}", name, relative_slashed(&path, &common_root))),
+                    path : relative_slashed(&path, &common_root),
+                    ..workspace.clone()
+                });
+            }
+            if !matched {
+                crate::diag::warn(&format!("workspace path \"{}\" matches no directory", workspace.path));
+            }
+        }
+
+        for discover in self.discover.iter().flatten() {
+            let discover_root = common_root.join(discover.root.as_deref().unwrap_or(""));
+            for entry in walkdir::WalkDir::new(&discover_root).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+                if !entry.file_type().is_file() || entry.file_name() != discover.marker.as_str() {
+                    continue;
+                }
+                let dir = entry.path().parent().unwrap();
+                expanded.push(WorkSpaceConf {
+                    name : dir.file_name().map(|n| n.to_string_lossy().into()),
+                    path : relative_slashed(dir, &common_root),
+                    uses : None,
+                    include_groups : discover.include_groups.clone(),
+                    option_groups : discover.option_groups.clone(),
+                    target : discover.target.clone(),
+                    include : discover.include.clone(),
+                    option : discover.option.clone(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        self.workspace = expanded;
+
+        for workspace in &self.workspace {
+            for used in workspace.uses.iter().flatten() {
+                if !self.workspace.iter().any(|w| w.is_called(used)) {
+                    return Err(format!("workspace \"{}\" uses unknown workspace \"{}\"", workspace.path, used));
+                }
+            }
+            for group in workspace.include_groups.iter().flatten() {
+                if !self.include_group.contains_key(group) {
+                    return Err(format!("workspace \"{}\" references unknown include group \"{}\"", workspace.path, group));
+                }
+            }
+            for group in workspace.option_groups.iter().flatten() {
+                if !self.option_group.contains_key(group) {
+                    return Err(format!("workspace \"{}\" references unknown option group \"{}\"", workspace.path, group));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub const C_STANDARDS : [&str; 16] = [
+    "c89", "c90", "c99", "c11", "c17", "c18", "c23", "c2x",
+    "gnu89", "gnu90", "gnu99", "gnu11", "gnu17", "gnu18", "gnu23", "gnu2x",
+];
+
+pub const CPP_STANDARDS : [&str; 22] = [
+    "c++98", "c++03", "c++11", "c++14", "c++17", "c++20", "c++23", "c++26", "c++2a", "c++2b", "c++2c",
+    "gnu++98", "gnu++03", "gnu++11", "gnu++14", "gnu++17", "gnu++20", "gnu++23", "gnu++26", "gnu++2a", "gnu++2b", "gnu++2c",
+];
+
+fn merge_lists<'a>(layers : impl IntoIterator<Item = Option<&'a Vec<String>>>) -> Vec<String> {
+    let mut merged = Vec::<String>::new();
+    for layer in layers {
+        merged.extend(layer.cloned().unwrap_or_default());
+    }
+    merged
+}
+
+/// The include roots of `workspace` in search order, each with whether it is optional.
+fn build_include_roots(conf : &CompDBConf, workspace : &WorkSpaceConf) -> Vec<(String, bool)> {
+    fn add_include_roots_from_include_conf(org : &mut Vec<(i64, String, bool)>, root : &Path, include_conf : Option<&IncludeConf>) {
+        if let Some(include_conf) = include_conf {
+            if let Some(include_roots) = include_conf.root_dir.as_ref() {
+                for include_root in include_roots {
+                    let include_root_as_path = if include_root == "." {
+                        PathBuf::from("")
+                    } else {
+                        PathBuf::from(include_root)
+                    };
+                    let priority = include_conf.priority.unwrap_or(0);
+                    let optional = include_conf.optional.unwrap_or(false);
+                    if include_root_as_path.is_relative() {
+                        org.push((priority, crate::generate::lossy(&root.join(include_root_as_path)), optional));
+                    } else {
+                        org.push((priority, crate::generate::lossy(&include_root_as_path), optional));
+                    }
+                }
+            }
+        }
+    }
+
+    let common_root = Path::new(&conf.common.root_dir);
+    let mut include_roots = Vec::<(i64, String, bool)>::new();
+    add_include_roots_from_include_conf(&mut include_roots, common_root, conf.common.include.as_ref());
+    for group in conf.include_groups_of(workspace) {
+        add_include_roots_from_include_conf(&mut include_roots, common_root, Some(group));
+    }
+    add_include_roots_from_include_conf(&mut include_roots, &common_root.join(&workspace.path), workspace.include.as_ref());
+
+    let mut visited = vec![workspace.path.as_str()];
+    let mut pending : Vec<&String> = workspace.uses.iter().flatten().rev().collect();
+    while let Some(used) = pending.pop() {
+        let Some(dependency) = conf.workspace.iter().find(|w| w.is_called(used)) else { continue };
+        if visited.contains(&dependency.path.as_str()) {
+            continue;
+        }
+        visited.push(&dependency.path);
+
+        add_include_roots_from_include_conf(&mut include_roots, &common_root.join(&dependency.path), dependency.include.as_ref());
+        pending.extend(dependency.uses.iter().flatten().rev());
+    }
+
+    // Stable, so equal priorities keep their order; the first copy of a root wins.
+    include_roots.sort_by_key(|(priority, _, _)| std::cmp::Reverse(*priority));
+    let mut seen = std::collections::HashSet::new();
+    include_roots.into_iter().map(|(_, root, optional)| (root, optional)).filter(|(root, _)| seen.insert(root.clone())).collect()
+}
+
+/// `EffectiveConf::option_origin` of `workspace`.
+fn option_origin(conf : &CompDBConf, workspace : &WorkSpaceConf) -> HashMap<String, String> {
+    let named_groups = workspace.option_groups.iter().flatten().filter_map(|g| conf.option_group.get(g).map(|group| (format!("option_group.{}", g), group)));
+    let layers = conf.common.option.iter().map(|option| ("common.option".to_string(), option))
+        .chain(named_groups)
+        .chain(workspace.option.iter().map(|option| ("workspace.option".to_string(), option)));
+    let mut origin = HashMap::new();
+    for (layer, option) in layers {
+        origin.extend(option.arg.iter().flatten().map(|arg| (arg.clone(), layer.clone())));
+    }
+    for (key, common, own) in [("c_std", &conf.common.c_std, &workspace.c_std), ("cpp_std", &conf.common.cpp_std, &workspace.cpp_std)] {
+        match (own, common) {
+            (Some(std), _) => origin.insert(format!("-std={}", std), format!("workspace.{}", key)),
+            (None, Some(std)) => origin.insert(format!("-std={}", std), format!("common.{}", key)),
+            (None, None) => None,
+        };
+    }
+    // A workspace overrides the common machine key by key.
+    for (layer, machine) in [("common", &conf.common.machine), ("workspace", &workspace.machine)] {
+        origin.extend(machine.keyed_flags().into_iter().map(|(key, flag)| (flag, format!("{}.{}", layer, key))));
+    }
+    let sysroot = workspace.sysroot.as_ref().map(|sysroot| ("workspace.sysroot", sysroot)).or(conf.common.sysroot.as_ref().map(|sysroot| ("common.sysroot", sysroot)));
+    if let Some((layer, sysroot)) = sysroot {
+        origin.insert(format!("--sysroot={}", resolve_sysroot(&conf.common.root_dir, sysroot)), layer.into());
+    }
+    origin
+}
+
+/// `sysroot` made absolute against `root_dir`.
+fn resolve_sysroot(root_dir : &str, sysroot : &str) -> String {
+    crate::generate::lossy(&Path::new(root_dir).join(sysroot))
+}
+
+impl EffectiveConf {
+    pub fn resolve(conf : &CompDBConf, workspace : &WorkSpaceConf) -> EffectiveConf {
+        let common = &conf.common;
+        let strip_wrappers = workspace.strip_wrappers.or(common.strip_wrappers).unwrap_or(false);
+        let compiler = |command : &Vec<String>| if strip_wrappers { compdb::strip_compiler_wrappers(command) } else { command.clone() };
+        let workspace_target = workspace.target.as_ref();
+        let include_roots = build_include_roots(conf, workspace);
+        let include_layers : Vec<&IncludeConf> = common.include.iter().chain(conf.include_groups_of(workspace)).chain(workspace.include.as_ref()).collect();
+        let option_layers : Vec<&OptionConf> = common.option.iter().chain(conf.option_groups_of(workspace)).chain(workspace.option.as_ref()).collect();
+
+        EffectiveConf {
+            name : workspace.name.clone(),
+            path : workspace.path.clone(),
+            uses : workspace.uses.clone().unwrap_or_default(),
+            c_compiler : compiler(&common.c_compiler),
+            cpp_compiler : compiler(&common.cpp_compiler),
+            asm_compiler : compiler(common.asm_compiler.as_ref().unwrap_or(&common.c_compiler)),
+            objc_compiler : compiler(common.objc_compiler.as_ref().unwrap_or(&common.c_compiler)),
+            objcxx_compiler : compiler(common.objcxx_compiler.as_ref().unwrap_or(&common.cpp_compiler)),
+            cuda_compiler : compiler(common.cuda_compiler.as_ref().unwrap_or(&common.cpp_compiler)),
+            cuda_path : common.cuda_path.clone(),
+            root_dir : common.root_dir.clone(),
+            target : TargetConf {
+                match_pattern : Some(merge_lists([common.target.match_pattern.as_ref(), workspace_target.and_then(|t| t.match_pattern.as_ref())])),
+                ignore_pattern : Some(merge_lists([common.target.ignore_pattern.as_ref(), workspace_target.and_then(|t| t.ignore_pattern.as_ref())])),
+                max_depth : workspace_target.and_then(|t| t.max_depth).or(common.target.max_depth),
+                prune_dirs : Some(merge_lists([common.target.prune_dirs.as_ref(), workspace_target.and_then(|t| t.prune_dirs.as_ref())])),
+            },
+            include : IncludeConf {
+                root_dir : Some(include_roots.iter().map(|(root, _)| root.clone()).collect()),
+                ignore_pattern : Some(merge_lists(include_layers.iter().map(|i| i.ignore_pattern.as_ref()))),
+                // Already applied to the order of root_dir.
+                priority : None,
+                max_depth : include_layers.iter().rev().find_map(|i| i.max_depth),
+                prune_dirs : Some(merge_lists(include_layers.iter().map(|i| i.prune_dirs.as_ref()))),
+                // Kept per root in optional_include_roots.
+                optional : None,
+            },
+            optional_include_roots : include_roots.iter().filter(|(_, optional)| *optional).map(|(root, _)| root.clone()).collect(),
+            option : OptionConf {
+                arg : Some(option_layers.iter().fold(Vec::new(), |mut args, layer| {
+                    args.retain(|arg| !layer.remove_arg.iter().flatten().any(|removed| removed == arg));
+                    args.extend(layer.arg.iter().flatten().cloned());
+                    args
+                })),
+                remove_arg : None,
+            },
+            option_origin : option_origin(conf, workspace),
+            argument_template : workspace.argument_template.clone().or(common.argument_template.clone()),
+            directory_mode : workspace.directory_mode.or(common.directory_mode).unwrap_or_default(),
+            file_option : common.file_option.iter().chain(workspace.file_option.iter()).flatten().cloned().collect(),
+            pkg_config : merge_lists([common.pkg_config.as_ref(), workspace.pkg_config.as_ref()]),
+            packages : common.packages.iter().chain(workspace.packages.as_ref()).cloned().collect(),
+            sysroot : workspace.sysroot.as_ref().or(common.sysroot.as_ref()).map(|sysroot| resolve_sysroot(&common.root_dir, sysroot)),
+            sysroot_system_includes : workspace.sysroot_system_includes.or(common.sysroot_system_includes).unwrap_or(false),
+            include_hidden : workspace.include_hidden.or(common.include_hidden).unwrap_or(false),
+            source : workspace.source.or(common.source).unwrap_or_default(),
+            unused_include_dirs : workspace.unused_include_dirs.or(common.unused_include_dirs).unwrap_or_default(),
+            exclude_assembly : workspace.exclude_assembly.or(common.exclude_assembly).unwrap_or(false),
+            unity : workspace.unity.or(common.unity).unwrap_or_default(),
+            c_std : workspace.c_std.clone().or(common.c_std.clone()),
+            cpp_std : workspace.cpp_std.clone().or(common.cpp_std.clone()),
+            force_include : merge_lists([common.force_include.as_ref(), workspace.force_include.as_ref()]),
+            pch : workspace.pch.clone().or(common.pch.clone()),
+            files : workspace.files.clone(),
+            file_list : workspace.file_list.clone(),
+            generated_files : workspace.generated_files.clone().unwrap_or_default(),
+            allow_missing : workspace.allow_missing.unwrap_or(false),
+            machine : workspace.machine.or(&common.machine),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CONF_VERSION, CompDBConf, ConfigFormat, EffectiveConf, load_conf, migrate_file};
+
+    const VARIANT_CONF : &str = r#"
+        [common]
+        root_dir = "/work"
+        c_compiler = ["gcc"]
+        cpp_compiler = ["g++"]
+        [common.target]
+        match_pattern = ['[.]c$']
+        [common.include]
+        root_dir = ["inc"]
+        priority = 5
+        [common.option]
+        arg = ["-O2"]
+        [test]
+        include_root = ["test/mocks", "test/stubs"]
+        define = ["UNIT_TEST"]
+        [[workspace]]
+        path = "src"
+        [[workspace]]
+        path = "test"
+        test_only = true
+    "#;
+
+    #[test]
+    fn migrated_file_loads_at_the_current_version() {
+        let dir = std::env::temp_dir().join(format!("compdb-migrate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("compdb.toml").to_string_lossy().into_owned();
+        std::fs::write(&input, "[common]\nc_compiler = [\"gcc\"]\ncpp_compiler = [\"g++\"]\n[common.target]\nmatch_pattern = ['[.]c$']\n[[workspace]]\npath = \"src\"\n").unwrap();
+
+        let (text, migrated) = migrate_file(&input, ConfigFormat::Toml).unwrap();
+        assert!(migrated);
+        std::fs::write(&input, text).unwrap();
+        let conf = load_conf(std::slice::from_ref(&input), None, &[]).unwrap();
+        assert_eq!(conf.version, Some(CONF_VERSION));
+        assert_eq!(conf.workspace.len(), 1);
+        assert_eq!(conf.workspace[0].path, "src");
+        assert!(!migrate_file(&input, ConfigFormat::Toml).unwrap().1);
+
+        std::fs::write(&input, format!("version = {}\n[common]\n", CONF_VERSION + 1)).unwrap();
+        assert!(migrate_file(&input, ConfigFormat::Toml).unwrap_err().contains("is not supported"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn local_overrides_are_read_in_their_own_format() {
+        let dir = std::env::temp_dir().join(format!("compdb-local-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("compdb.toml").to_string_lossy().into_owned();
+        std::fs::write(&input, "[common]\nc_compiler = [\"gcc\"]\ncpp_compiler = [\"g++\"]\n[common.target]\nmatch_pattern = ['[.]c$']\n[[workspace]]\npath = \"src\"\n").unwrap();
+        std::fs::write(dir.join("compdb.local.yaml"), "common:\n  c_compiler: [clang]\n").unwrap();
+
+        let conf = load_conf(std::slice::from_ref(&input), None, &[]).unwrap();
+        assert_eq!(conf.common.c_compiler, ["clang"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn production_variant_drops_test_only_workspaces() {
+        let mut conf : CompDBConf = toml::from_str(VARIANT_CONF).unwrap();
+        conf.select_variant(false).unwrap();
+        assert_eq!(conf.workspace.iter().map(|w| w.path.as_str()).collect::<Vec<_>>(), ["src"]);
+        let effective = EffectiveConf::resolve(&conf, &conf.workspace[0]);
+        assert_eq!(effective.include.root_dir.unwrap(), ["/work/inc"]);
+        assert_eq!(effective.option.arg.unwrap(), ["-O2"]);
+    }
+
+    #[test]
+    fn test_variant_puts_mocks_first_and_adds_defines() {
+        let mut conf : CompDBConf = toml::from_str(VARIANT_CONF).unwrap();
+        conf.select_variant(true).unwrap();
+        assert_eq!(conf.workspace.iter().map(|w| w.path.as_str()).collect::<Vec<_>>(), ["src", "test"]);
+        for workspace in &conf.workspace {
+            let effective = EffectiveConf::resolve(&conf, workspace);
+            assert_eq!(effective.include.root_dir.unwrap(), ["/work/test/mocks", "/work/test/stubs", "/work/inc"]);
+            assert_eq!(effective.option.arg.unwrap(), ["-O2", "-DUNIT_TEST"]);
+        }
+        let mut without_test_table : CompDBConf = toml::from_str(VARIANT_CONF).unwrap();
+        without_test_table.test = None;
+        assert!(without_test_table.select_variant(true).unwrap_err().contains("[test]"));
+    }
+}
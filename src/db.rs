@@ -0,0 +1,349 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use crate::compdb::{self, CompilationEntry};
+use crate::generate::slashed;
+
+/// `entries` without the later entries of a file; with `options`, repeated
+/// options of each entry are dropped as well.
+pub fn dedup(entries : Vec<CompilationEntry>, options : bool) -> Vec<CompilationEntry> {
+    let mut seen = HashSet::new();
+    entries.into_iter()
+        .filter(|entry| seen.insert(clean(&entry.path())))
+        .map(|entry| {
+            if !options {
+                return entry;
+            }
+            let entry = entry.into_arguments_form();
+            let Some((compiler, rest)) = entry.arguments.split_first() else {
+                return entry;
+            };
+            let arguments = std::iter::once(compiler.clone()).chain(compdb::dedup_options(rest)).collect();
+            CompilationEntry { arguments, ..entry }
+        })
+        .collect()
+}
+
+/// `entries` without the options matching one of `patterns`; a flag taking
+/// a value goes with it. The compiler is kept.
+pub fn strip(entries : Vec<CompilationEntry>, patterns : &regex::RegexSet) -> Vec<CompilationEntry> {
+    entries.into_iter().map(|entry| {
+        let entry = entry.into_arguments_form();
+        let Some((compiler, rest)) = entry.arguments.split_first() else {
+            return entry;
+        };
+        let mut arguments = vec![compiler.clone()];
+        arguments.extend(compdb::group_options(rest).into_iter().filter(|option| !patterns.is_match(&option[0])).flatten().cloned());
+        CompilationEntry { arguments, ..entry }
+    }).collect()
+}
+
+/// `path` with `.` removed and `..` applied, without touching the file system.
+pub fn clean(path : &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(cleaned.components().next_back(), Some(Component::Normal(_))) => {
+                cleaned.pop();
+            }
+            component => cleaned.push(component),
+        }
+    }
+    cleaned
+}
+
+/// The options naming a file that is looked up along the include path as
+/// well as in the working directory, so it is not made absolute.
+const FORCED_INCLUDES : [&str; 3] = ["-include", "-include-pch", "-imacros"];
+
+/// `entries` in the `arguments` form, with absolute, clean, forward-slashed
+/// directories, files (also where the arguments name the file), outputs and
+/// include directories, relative ones taken from the entry's directory.
+/// Forced includes are only cleaned and slashed.
+pub fn normalize(entries : Vec<CompilationEntry>) -> Result<Vec<CompilationEntry>, String> {
+    entries.into_iter().map(|entry| {
+        let entry = entry.into_arguments_form();
+        let directory = clean(&std::path::absolute(&entry.directory).map_err(|e| format!("{}: {}", entry.directory, e))?);
+        let absolute = |path : &str| slashed(&clean(&directory.join(path)));
+        let value = |flag : &str, path : &str| if FORCED_INCLUDES.contains(&flag) { slashed(&clean(Path::new(path))) } else { absolute(path) };
+        let file = absolute(&entry.file);
+        let mut arguments = Vec::new();
+        for option in compdb::group_options(&entry.arguments) {
+            match option {
+                [flag, path] if compdb::PATH_FLAGS.contains(&flag.as_str()) || flag == "-o" || flag == "-MF" => arguments.extend([flag.clone(), value(flag, path)]),
+                [single] => match compdb::PATH_FLAGS.iter().chain(&["--sysroot="]).find(|flag| single.len() > flag.len() && single.starts_with(*flag)) {
+                    Some(flag) => arguments.push(format!("{}{}", flag, value(flag, &single[flag.len()..]))),
+                    None if *single == entry.file => arguments.push(file.clone()),
+                    None => arguments.push(single.clone()),
+                },
+                _ => arguments.extend(option.iter().cloned()),
+            }
+        }
+        Ok(CompilationEntry {
+            directory : slashed(&directory),
+            arguments,
+            output : entry.output.as_deref().map(absolute),
+            file,
+            ..entry
+        })
+    }).collect()
+}
+
+/// Which entries `merge` keeps for a file listed by several databases.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum MergePolicy {
+    /// Those of the first database listing the file.
+    PreferFirst,
+    /// Those of the last database listing the file.
+    PreferLast,
+    /// All of them.
+    KeepDuplicates,
+}
+
+/// The entries of `databases` in order, a file's entries coming from the
+/// database `policy` picks.
+pub fn merge(databases : Vec<Vec<CompilationEntry>>, policy : MergePolicy) -> Vec<CompilationEntry> {
+    let mut owner = HashMap::<PathBuf, usize>::new();
+    for (i, entries) in databases.iter().enumerate() {
+        for entry in entries {
+            let path = clean(&entry.path());
+            match policy {
+                MergePolicy::PreferFirst => {
+                    owner.entry(path).or_insert(i);
+                }
+                MergePolicy::PreferLast => {
+                    owner.insert(path, i);
+                }
+                MergePolicy::KeepDuplicates => {}
+            }
+        }
+    }
+    databases.into_iter().enumerate()
+        .flat_map(|(i, entries)| entries.into_iter().map(move |entry| (i, entry)))
+        .filter(|(i, entry)| owner.get(&clean(&entry.path())).is_none_or(|owner| owner == i))
+        .map(|(_, entry)| entry)
+        .collect()
+}
+
+/// How a file's entry differs between two databases.
+pub enum Change {
+    Added(PathBuf),
+    Removed(PathBuf),
+    /// Options (a flag with its value) only the old or only the new entry
+    /// has; both empty when only their order changed.
+    Changed { path : PathBuf, removed : Vec<String>, added : Vec<String> },
+}
+
+/// The files `new` adds, drops or compiles differently from `old`, by
+/// path. A file's first entry stands for it.
+pub fn diff(old : &[CompilationEntry], new : &[CompilationEntry]) -> Vec<Change> {
+    fn by_path(entries : &[CompilationEntry]) -> BTreeMap<PathBuf, Vec<String>> {
+        let mut files = BTreeMap::new();
+        for entry in entries {
+            files.entry(clean(&entry.path())).or_insert_with(|| entry.argv());
+        }
+        files
+    }
+    fn options(argv : &[String]) -> Vec<String> {
+        compdb::group_options(argv).into_iter().map(|option| option.join(" ")).collect()
+    }
+
+    let old = by_path(old);
+    let new = by_path(new);
+    let mut changes = Vec::new();
+    for (path, old_argv) in &old {
+        match new.get(path) {
+            None => changes.push(Change::Removed(path.clone())),
+            Some(new_argv) if new_argv != old_argv => {
+                let (old_options, new_options) = (options(old_argv), options(new_argv));
+                changes.push(Change::Changed {
+                    path : path.clone(),
+                    removed : old_options.iter().filter(|o| !new_options.contains(o)).cloned().collect(),
+                    added : new_options.iter().filter(|o| !old_options.contains(o)).cloned().collect(),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+    changes.extend(new.into_keys().filter(|path| !old.contains_key(path)).map(Change::Added));
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    changes
+}
+
+impl Change {
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Added(path) | Change::Removed(path) | Change::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// The ways the JSON entries of `content` break the JSON Compilation
+/// Database format, each with the index of its entry. Relative files are
+/// looked up under their entry's `directory`.
+pub fn validate(content : &str) -> Result<Vec<(usize, String)>, String> {
+    let value : serde_json::Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    let serde_json::Value::Array(entries) = value else {
+        return Err("the database is not a JSON array".into());
+    };
+    let mut violations = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let mut violation = |message : String| violations.push((index, message));
+        let Some(entry) = entry.as_object() else {
+            violation("not an object".into());
+            continue;
+        };
+        let string = |key : &str| entry.get(key).map(|value| value.as_str().ok_or(format!("\"{}\" is not a string", key)));
+
+        match string("directory") {
+            None => violation("\"directory\" is missing".into()),
+            Some(Err(message)) => violation(message),
+            Some(Ok(directory)) if !Path::new(directory).is_absolute() => violation(format!("\"directory\" {} is not absolute", directory)),
+            Some(Ok(directory)) if !Path::new(directory).is_dir() => violation(format!("\"directory\" {} does not exist", directory)),
+            Some(Ok(_)) => {}
+        }
+        match string("file") {
+            None => violation("\"file\" is missing".into()),
+            Some(Err(message)) => violation(message),
+            Some(Ok(file)) => {
+                let directory = entry.get("directory").and_then(|d| d.as_str()).unwrap_or_default();
+                let path = Path::new(directory).join(file);
+                if !path.is_file() {
+                    violation(format!("\"file\" {} does not exist", path.display()));
+                }
+            }
+        }
+        match (entry.get("arguments"), string("command")) {
+            (None, None) => violation("neither \"arguments\" nor \"command\" is given".into()),
+            (Some(_), Some(_)) => violation("both \"arguments\" and \"command\" are given".into()),
+            (_, Some(Err(message))) => violation(message),
+            (Some(arguments), _) => match arguments.as_array() {
+                Some(arguments) if arguments.is_empty() => violation("\"arguments\" is empty".into()),
+                Some(arguments) if arguments.iter().all(|a| a.is_string()) => {}
+                _ => violation("\"arguments\" is not an array of strings".into()),
+            },
+            (None, Some(Ok(command))) if command.trim().is_empty() => violation("\"command\" is empty".into()),
+            (None, Some(Ok(_))) => {}
+        }
+        if let Some(Err(message)) = string("output") {
+            violation(message);
+        }
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compdb::CompilationEntry;
+
+    use super::{dedup, diff, merge, normalize, strip, validate, Change, MergePolicy};
+
+    fn entry(directory : &str, arguments : &[&str], file : &str) -> CompilationEntry {
+        CompilationEntry { directory : directory.into(), arguments : arguments.iter().map(|a| a.to_string()).collect(), command : None, file : file.into(), output : None }
+    }
+
+    #[test]
+    fn dedup_keeps_the_first_entry_of_a_file_and_optionally_its_first_options() {
+        let entries = vec![
+            entry("/src", &["cc", "-I", "inc", "-DA", "-I", "inc", "-DA", "-c", "a.c"], "a.c"),
+            entry("/src/sub", &["cc", "-c", "../a.c"], "../a.c"),
+            CompilationEntry { command : Some("cc -DB -DB -c b.c".into()), ..entry("/src", &[], "b.c") },
+        ];
+        let files = dedup(entries.clone(), false);
+        assert_eq!(files, vec![entries[0].clone(), entries[2].clone()]);
+        let options = dedup(entries, true);
+        assert_eq!(options[0].arguments, ["cc", "-I", "inc", "-DA", "-c", "a.c"]);
+        assert_eq!(options[1].arguments, ["cc", "-DB", "-c", "b.c"]);
+        assert_eq!(options[1].command, None);
+    }
+
+    #[test]
+    fn strip_drops_matching_options_with_their_values() {
+        let patterns = regex::RegexSet::new(["^-fno-.*$", "^-include$"]).unwrap();
+        let entries = vec![entry("/src", &["-fno-x", "-fno-common", "-include", "cfg.h", "-fPIC", "-c", "a.c"], "a.c")];
+        // The first argument is the compiler, even when it looks like an option.
+        assert_eq!(strip(entries, &patterns)[0].arguments, ["-fno-x", "-fPIC", "-c", "a.c"]);
+    }
+
+    #[test]
+    fn normalize_makes_paths_absolute_against_the_directory() {
+        let entries = vec![CompilationEntry {
+            output : Some("out/../a.o".into()),
+            ..entry("/src/./build/..", &["cc", "-I", "inc/./x", "-I../sdk", "-isystem", "/usr/include/", "-include", "./cfg.h", "--sysroot=sys", "-o", "a.o", "-c", "./a.c"], "./a.c")
+        }];
+        let normalized = normalize(entries).unwrap();
+        assert_eq!(normalized[0].directory, "/src");
+        assert_eq!(normalized[0].file, "/src/a.c");
+        assert_eq!(normalized[0].output.as_deref(), Some("/src/a.o"));
+        assert_eq!(normalized[0].arguments, [
+            "cc", "-I", "/src/inc/x", "-I/sdk", "-isystem", "/usr/include", "-include", "cfg.h", "--sysroot=/src/sys", "-o", "/src/a.o", "-c", "/src/a.c",
+        ]);
+    }
+
+    #[test]
+    fn merge_keeps_the_entries_of_the_database_the_policy_picks() {
+        let first = vec![entry("/src", &["cc", "-DFIRST", "-c", "a.c"], "a.c"), entry("/src", &["cc", "-c", "b.c"], "b.c")];
+        // The same file, spelled from another directory.
+        let last = vec![entry("/src/sub", &["cc", "-DLAST", "-c", "../a.c"], "../a.c"), entry("/src", &["cc", "-c", "c.c"], "c.c")];
+        let files = |entries : Vec<CompilationEntry>| entries.into_iter().map(|e| format!("{} {}", e.file, e.arguments[1])).collect::<Vec<_>>();
+
+        assert_eq!(files(merge(vec![first.clone(), last.clone()], MergePolicy::PreferFirst)), ["a.c -DFIRST", "b.c -c", "c.c -c"]);
+        assert_eq!(files(merge(vec![first.clone(), last.clone()], MergePolicy::PreferLast)), ["b.c -c", "../a.c -DLAST", "c.c -c"]);
+        assert_eq!(merge(vec![first.clone(), last.clone()], MergePolicy::KeepDuplicates), [first, last].concat());
+    }
+
+    #[test]
+    fn diff_compares_options_whatever_form_the_entries_use() {
+        let command = |file : &str, command : &str| CompilationEntry { command : Some(command.into()), ..entry("/src", &[], file) };
+        let old = vec![
+            command("a.c", "cc -I inc -O2 -c a.c"),
+            command("b.c", "cc -DA -DB -c b.c"),
+            command("c.c", "cc -c c.c"),
+            command("same.c", "cc '-DNAME=\"x y\"' -c same.c"),
+        ];
+        let new = vec![
+            entry("/src", &["cc", "-I", "inc", "-O3", "-DX", "-c", "a.c"], "a.c"),
+            entry("/src", &["cc", "-DB", "-DA", "-c", "b.c"], "b.c"),
+            entry("/src", &["cc", "-DNAME=\"x y\"", "-c", "same.c"], "same.c"),
+            entry("/src", &["cc", "-c", "d.c"], "d.c"),
+        ];
+        let changes : Vec<String> = diff(&old, &new).into_iter().map(|change| match change {
+            Change::Added(path) => format!("+ {}", path.display()),
+            Change::Removed(path) => format!("- {}", path.display()),
+            Change::Changed { path, removed, added } => format!("~ {} -[{}] +[{}]", path.display(), removed.join(", "), added.join(", ")),
+        }).collect();
+        assert_eq!(changes, ["~ /src/a.c -[-O2] +[-O3, -DX]", "~ /src/b.c -[] +[]", "- /src/c.c", "+ /src/d.c"]);
+    }
+
+    #[test]
+    fn validate_reports_each_broken_entry() {
+        let dir = std::env::temp_dir().join(format!("compdb-validate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.c"), "").unwrap();
+        let dir = dir.to_string_lossy().replace('\\', "/");
+        let content = serde_json::json!([
+            { "directory" : dir, "file" : "a.c", "arguments" : ["cc", "-c", "a.c"] },
+            { "directory" : dir, "file" : "a.c", "command" : "cc -c a.c", "output" : "a.o" },
+            { "directory" : dir, "file" : "a.c" },
+            { "directory" : dir, "file" : "a.c", "arguments" : ["cc"], "command" : "cc" },
+            { "file" : "missing.c", "arguments" : [] },
+            { "directory" : "relative", "file" : 1, "command" : " " },
+            "cc -c a.c",
+        ]).to_string();
+        let violations = validate(&content).unwrap();
+        assert_eq!(violations, [
+            (2, "neither \"arguments\" nor \"command\" is given".to_string()),
+            (3, "both \"arguments\" and \"command\" are given".to_string()),
+            (4, "\"directory\" is missing".to_string()),
+            (4, format!("\"file\" {} does not exist", std::path::Path::new("missing.c").display())),
+            (4, "\"arguments\" is empty".to_string()),
+            (5, "\"directory\" relative is not absolute".to_string()),
+            (5, "\"file\" is not a string".to_string()),
+            (5, "\"command\" is empty".to_string()),
+            (6, "not an object".to_string()),
+        ]);
+        assert!(validate("{}").unwrap_err().contains("not a JSON array"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+use std::ops::Range;
+use std::sync::Mutex;
+
+use console::style;
+
+/// The warnings printed so far with their kind, for `--sarif`.
+static WARNINGS : Mutex<Vec<(&str, String)>> = Mutex::new(Vec::new());
+
+/// Prints `message` as a warning, colored when stderr is a terminal.
+pub fn warn(message : &str) {
+    warn_kind("warning", message);
+}
+
+/// Prints `message` as a warning of `kind`, e.g. `missing-include-dir`.
+pub fn warn_kind(kind : &'static str, message : &str) {
+    eprintln!("{}: {}", style("warning").for_stderr().yellow().bold(), message);
+    WARNINGS.lock().unwrap().push((kind, message.into()));
+}
+
+/// The warnings printed so far with their kind, in order.
+pub fn warnings() -> Vec<(&'static str, String)> {
+    WARNINGS.lock().unwrap().clone()
+}
+
+/// Prints `message` as an error, colored when stderr is a terminal.
+pub fn error(message : &str) {
+    eprintln!("{}: {}", style("error").for_stderr().red().bold(), message);
+}
+
+#[derive(Clone)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Location of a value in a configuration, e.g. `workspace[3].include.ignore_pattern[7]`.
+#[derive(Clone, Default)]
+pub struct KeyPath(Vec<Segment>);
+
+impl KeyPath {
+    pub fn key(&self, key : &str) -> KeyPath {
+        let mut path = self.clone();
+        path.0.push(Segment::Key(key.into()));
+        path
+    }
+
+    pub fn index(&self, index : usize) -> KeyPath {
+        let mut path = self.clone();
+        path.0.push(Segment::Index(index));
+        path
+    }
+
+    pub fn last_key(&self) -> Option<&str> {
+        self.0.iter().rev().find_map(|segment| match segment {
+            Segment::Key(key) => Some(key.as_str()),
+            Segment::Index(_) => None,
+        })
+    }
+
+    /// Byte range of the value in the TOML `text`.
+    fn span(&self, text : &str) -> Option<Range<usize>> {
+        let document = toml_edit::ImDocument::parse(text).ok()?;
+        let mut item = document.as_item();
+        for segment in &self.0 {
+            item = match segment {
+                Segment::Key(key) => item.get(key.as_str())?,
+                Segment::Index(index) => item.get(*index)?,
+            };
+        }
+        item.span()
+    }
+}
+
+impl fmt::Display for KeyPath {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if i == 0 => write!(f, "{}", key)?,
+                Segment::Key(key) => write!(f, ".{}", key)?,
+                Segment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `message` about the value at `path` of `file`, followed by the offending
+/// line when `text` is the file's TOML source:
+///
+/// ```text
+/// workspace[3].include.ignore_pattern[7]: regex parse error: ...
+///   --> compdb.toml:12:23
+///    |
+/// 12 | ignore_pattern = ['a', '(']
+///    |                        ^^^
+/// ```
+pub fn located(file : &str, text : Option<&str>, path : &KeyPath, message : &str) -> String {
+    let mut out = format!("{}: {}", style(path).for_stderr().bold(), message);
+    let Some((text, span)) = text.and_then(|text| Some((text, path.span(text)?))) else {
+        out += &format!("\n  {} {}", style("-->").for_stderr().blue().bold(), file);
+        return out;
+    };
+    let line_start = text[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[span.start..].find('\n').map_or(text.len(), |i| span.start + i);
+    let line_number = text[..span.start].matches('\n').count() + 1;
+    let column = text[line_start..span.start].chars().count() + 1;
+    let width = line_number.to_string().len();
+    let gutter = style(format!("{} |", " ".repeat(width))).for_stderr().blue().bold();
+    let underline = "^".repeat(text[span.start..span.end.min(line_end)].chars().count().max(1));
+
+    out += &format!("\n{}{} {}:{}:{}", " ".repeat(width), style("-->").for_stderr().blue().bold(), file, line_number, column);
+    out += &format!("\n{}", gutter);
+    out += &format!("\n{} {}", style(format!("{} |", line_number)).for_stderr().blue().bold(), &text[line_start..line_end]);
+    out += &format!("\n{} {}{}", gutter, " ".repeat(column - 1), style(underline).for_stderr().red().bold());
+    out
+}
@@ -0,0 +1,163 @@
+//! Configurations of other tools written from the generated entries, so
+//! that they do not drift from the editor's.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::compdb::{self, CompilationEntry};
+use crate::db;
+use crate::generate::{HEADER_EXTENSIONS, slashed};
+
+/// Static analyzers `export lint` writes a project for.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum LintTool {
+    /// PC-lint / PC-lint Plus `.lnt` file: options, then the files.
+    Pclint,
+    /// Polyspace options file (`-options-file`).
+    Polyspace,
+    /// Shell script running `cov-translate` on every entry, for `cov-analyze`.
+    Coverity,
+}
+
+/// What the lint tools need of the entries: include dirs and macros (all
+/// entries' together, in order of appearance) and the files, absolute.
+#[derive(Default)]
+struct Project {
+    include_dirs : Vec<String>,
+    /// `NAME` or `NAME=VALUE`.
+    defines : Vec<String>,
+    undefines : Vec<String>,
+    files : Vec<String>,
+}
+
+fn push_new(list : &mut Vec<String>, value : String) {
+    if !list.contains(&value) {
+        list.push(value);
+    }
+}
+
+impl Project {
+    fn of(entries : &[CompilationEntry]) -> Project {
+        let mut project = Project::default();
+        for entry in entries {
+            let absolute = |path : &str| slashed(&Path::new(&entry.directory).join(path));
+            let argv = entry.argv();
+            for option in compdb::group_options(argv.get(1..).unwrap_or_default()) {
+                match option {
+                    [flag, value] if ["-I", "-isystem", "-iquote", "-idirafter"].contains(&flag.as_str()) => push_new(&mut project.include_dirs, absolute(value)),
+                    [flag, value] if flag == "-D" => push_new(&mut project.defines, value.clone()),
+                    [flag, value] if flag == "-U" => push_new(&mut project.undefines, value.clone()),
+                    [single] if single.starts_with("-I") => push_new(&mut project.include_dirs, absolute(&single[2..])),
+                    [single] if single.starts_with("-D") => push_new(&mut project.defines, single[2..].to_string()),
+                    [single] if single.starts_with("-U") => push_new(&mut project.undefines, single[2..].to_string()),
+                    _ => {}
+                }
+            }
+            push_new(&mut project.files, absolute(&entry.file));
+        }
+        project
+    }
+}
+
+/// The project file of `tool` for `entries`.
+pub fn lint(entries : &[CompilationEntry], tool : LintTool) -> String {
+    let mut text = String::new();
+    let (include, define, undefine, source) = match tool {
+        LintTool::Pclint => ("-i", "-d", "-u", ""),
+        LintTool::Polyspace => ("-I ", "-D ", "-U ", "-sources "),
+        LintTool::Coverity => {
+            let quote = |word : &str| format!("'{}'", word.replace('\'', r"'\''"));
+            writeln!(text, "#!/bin/sh").unwrap();
+            writeln!(text, "# Translates every entry into $COV_IDIR (./idir by default) for cov-analyze.").unwrap();
+            writeln!(text, "set -e").unwrap();
+            writeln!(text, "COV_IDIR=\"${{COV_IDIR:-$PWD/idir}}\"").unwrap();
+            for entry in entries {
+                let argv : Vec<String> = entry.argv().iter().map(|a| quote(a)).collect();
+                writeln!(text, "(cd {} && cov-translate --dir \"$COV_IDIR\" {})", quote(&entry.directory), argv.join(" ")).unwrap();
+            }
+            return text;
+        }
+    };
+    // PC-lint takes quoted names; Polyspace options files do not.
+    let path = |path : &String| if matches!(tool, LintTool::Pclint) { format!("\"{}\"", path) } else { path.clone() };
+    let project = Project::of(entries);
+    for dir in &project.include_dirs {
+        writeln!(text, "{}{}", include, path(dir)).unwrap();
+    }
+    for name in &project.defines {
+        writeln!(text, "{}{}", define, name).unwrap();
+    }
+    for name in &project.undefines {
+        writeln!(text, "{}{}", undefine, name).unwrap();
+    }
+    for file in &project.files {
+        writeln!(text, "{}{}", source, path(file)).unwrap();
+    }
+    text
+}
+
+/// The sources and headers of a workspace for tag generators: `targets`,
+/// then the headers directly in `include_dirs` and beside the targets, all
+/// relative to `root` like the arguments.
+pub fn tag_files(root : &Path, targets : &[std::path::PathBuf], include_dirs : &[std::path::PathBuf]) -> Vec<String> {
+    let mut files : Vec<String> = targets.iter().map(|target| slashed(target)).collect();
+    let mut seen : std::collections::HashSet<String> = files.iter().cloned().collect();
+    let target_dirs = targets.iter().map(|target| target.parent().unwrap_or(Path::new("")).to_path_buf());
+    for dir in include_dirs.iter().cloned().chain(target_dirs) {
+        let Ok(read_dir) = std::fs::read_dir(root.join(&dir)) else {
+            continue;
+        };
+        let mut headers : Vec<String> = read_dir.filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+            .filter(|e| HEADER_EXTENSIONS.contains(&e.path().extension().unwrap_or_default().to_str().unwrap_or_default()))
+            .map(|e| slashed(&dir.join(e.file_name())))
+            .collect();
+        headers.sort();
+        files.extend(headers.into_iter().filter(|header| seen.insert(header.clone())));
+    }
+    files
+}
+
+/// A Doxyfile fragment documenting `inputs` (directories) the way `entries`
+/// compile them; `exclude_patterns` are globs such as `*/build/*`.
+pub fn doxygen(inputs : &[String], exclude_patterns : &[String], entries : &[CompilationEntry]) -> String {
+    let project = Project::of(entries);
+    let mut text = String::new();
+    writeln!(text, "# Generated from the compile database configuration; @INCLUDE it from the Doxyfile.").unwrap();
+    for (key, values) in [("INPUT", inputs), ("EXCLUDE_PATTERNS", exclude_patterns), ("INCLUDE_PATH", &project.include_dirs), ("PREDEFINED", &project.defines)] {
+        let values : Vec<String> = values.iter().map(|value| format!("\"{}\"", value.replace('"', "\\\""))).collect();
+        writeln!(text, "{:<16} = {}", key, values.join(&format!(" \\\n{:<18} ", ""))).unwrap();
+    }
+    text
+}
+
+/// `entries` the way Sourcetrail and older indexers want them: one entry per
+/// file, in the `command` form, with the directory, the file, the output and
+/// the include paths absolute.
+pub fn sourcetrail(entries : Vec<CompilationEntry>) -> Result<Vec<CompilationEntry>, String> {
+    db::dedup(entries, false).into_iter().map(|entry| {
+        let entry = entry.into_arguments_form();
+        let directory = db::clean(&std::path::absolute(&entry.directory).map_err(|e| format!("{}: {}", entry.directory, e))?);
+        let absolute = |path : &str| slashed(&db::clean(&directory.join(path)));
+        let file = absolute(&entry.file);
+        let mut arguments = Vec::new();
+        for option in compdb::group_options(&entry.arguments) {
+            match option {
+                [flag, value] if compdb::PATH_FLAGS.contains(&flag.as_str()) || flag == "-o" => arguments.extend([flag.clone(), absolute(value)]),
+                [single] if *single == entry.file => arguments.push(file.clone()),
+                [single] => match compdb::PATH_FLAGS.iter().find(|flag| single.len() > flag.len() && single.starts_with(*flag)) {
+                    Some(flag) => arguments.push(format!("{}{}", flag, absolute(&single[flag.len()..]))),
+                    None => arguments.push(single.clone()),
+                },
+                _ => arguments.extend(option.iter().cloned()),
+            }
+        }
+        Ok(CompilationEntry {
+            directory : slashed(&directory),
+            arguments,
+            command : None,
+            file,
+            output : entry.output.as_deref().map(absolute),
+        }.into_command_form())
+    }).collect()
+}
@@ -0,0 +1,1033 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use serde::Serialize;
+
+use crate::compdb::{self, CompilationEntry};
+use crate::conf::{CompDBConf, DirectoryMode, DuplicatePolicy, EffectiveConf, FileSource, PackageConf, PolicyConf, PolicyLevel, UnityMode, UnusedIncludeDirs, WorkSpaceConf};
+use crate::db::clean;
+use crate::includes;
+use crate::packages;
+use crate::pkg_config;
+
+/// `path` with forward slashes. Windows verbatim prefixes are dropped
+/// (`\\?\C:\x` is `C:/x`, `\\?\UNC\host\share` is `//host/share`) and drive
+/// letters upper-cased, so that the spellings of one path compare equal.
+pub fn slashed(path : &Path) -> String {
+    let path = lossy(path);
+    let path = path.as_str();
+    let path = if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else {
+        path.strip_prefix(r"\\?\").unwrap_or(path).to_string()
+    };
+    let mut path = path.replace("\\", "/");
+    if path.len() >= 2 && path.as_bytes()[1] == b':' && path.as_bytes()[0].is_ascii_alphabetic() {
+        path[..1].make_ascii_uppercase();
+    }
+    path
+}
+
+/// `path` as a string for the database. Paths are kept as `OsStr` until
+/// this point; one that is not UTF-8 is converted lossily, with a warning.
+pub fn lossy(path : &Path) -> String {
+    match path.to_str() {
+        Some(path) => path.into(),
+        None => {
+            crate::diag::warn(&format!("{} is not valid UTF-8; written with replacement characters", path.display()));
+            path.to_string_lossy().into_owned()
+        }
+    }
+}
+
+/// `path` relative to `prefix` if it lies under it, otherwise whole (e.g.
+/// on another drive or share); slashed either way. Paths that are not
+/// UTF-8 are only made relative, which loses nothing.
+pub fn get_slashed_path_without_prefix(path : &Path, prefix: &Path) -> PathBuf {
+    if path.to_str().is_none() || prefix.to_str().is_none() {
+        return path.strip_prefix(prefix).unwrap_or(path).to_path_buf();
+    }
+    let (path, prefix) = (slashed(path), slashed(prefix));
+    let prefix = prefix.trim_end_matches('/');
+    let under = |path : &str| {
+        let head = path.get(..prefix.len())?;
+        let matches = if cfg!(windows) { head.eq_ignore_ascii_case(prefix) } else { head == prefix };
+        let rest = &path[prefix.len()..];
+        (matches && (rest.is_empty() || rest.starts_with('/'))).then(|| rest.trim_start_matches('/').to_string())
+    };
+    match under(&path) {
+        Some(relative) if !prefix.is_empty() => relative.into(),
+        _ => path.into(),
+    }
+}
+
+/// Categories of warnings, configured by the `[policy]` table.
+#[derive(Clone, Copy)]
+pub enum Warning {
+    MissingIncludeDir,
+    EmptyWorkspace,
+    DuplicateFile,
+    OptionConflict,
+    Other,
+}
+
+impl Warning {
+    /// The kind of the warning in reports, e.g. the SARIF rule.
+    pub fn kind(self) -> &'static str {
+        match self {
+            Warning::MissingIncludeDir => "missing-include-dir",
+            Warning::EmptyWorkspace => "empty-workspace",
+            Warning::DuplicateFile => "duplicate-file",
+            Warning::OptionConflict => "option-conflict",
+            Warning::Other => "warning",
+        }
+    }
+}
+
+/// State shared by the workspaces of one generation run.
+#[derive(Default)]
+pub struct Generation {
+    /// Fail instead of warning about suspicious configuration.
+    pub strict : bool,
+    pub policy : PolicyConf,
+    /// Every pattern seen, keyed by its configuration key, and whether it
+    /// matched at least one path.
+    pub patterns : BTreeMap<(&'static str, String), bool>,
+    /// Where the per-workspace progress bars are drawn; none when quiet.
+    pub progress : Option<MultiProgress>,
+    bar : Option<ProgressBar>,
+    /// One record per generated workspace, in generation order.
+    pub stats : Vec<WorkspaceStats>,
+    /// Include-root walks already done, keyed by (root, prefix, ignore patterns).
+    include_walks : HashMap<IncludeWalkKey, IncludeWalk>,
+    /// `pkg-config --cflags` output already read, keyed by package list.
+    pkg_config_flags : HashMap<Vec<String>, Vec<String>>,
+    /// Package-manager flags already read, keyed by root and packages.
+    package_flags : HashMap<(String, Vec<PackageConf>), Vec<String>>,
+    /// With `--changed-since`, the ref and the canonical paths of the files
+    /// changed since it; only those get entries, and the entry-count bounds,
+    /// which are for the whole tree, are not checked.
+    pub changed_since : Option<(String, HashSet<PathBuf>)>,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct IncludeWalkKey {
+    root : String,
+    prefix : PathBuf,
+    ignore_pattern : Vec<String>,
+    max_depth : Option<usize>,
+    include_hidden : bool,
+    prune_dirs : Vec<String>,
+}
+
+#[derive(Clone)]
+struct IncludeWalk {
+    has_headers : bool,
+    dirs : Vec<PathBuf>,
+}
+
+/// What generating one workspace produced and took.
+#[derive(Serialize)]
+pub struct WorkspaceStats {
+    pub name : String,
+    /// Paths visited while walking sources and include roots.
+    pub walked : usize,
+    /// Files matching `target.match_pattern` that got an entry.
+    pub matched : usize,
+    /// Files matching `target.match_pattern` dropped by `target.ignore_pattern`.
+    pub ignored : usize,
+    pub include_dirs : usize,
+    pub entries : usize,
+    /// Time spent matching paths against the patterns, part of `elapsed`.
+    #[serde(skip)]
+    pub match_time : Duration,
+    #[serde(rename = "elapsed_ms", serialize_with = "serialize_millis")]
+    pub elapsed : Duration,
+    #[serde(skip)]
+    started : Instant,
+}
+
+fn serialize_millis<S : serde::Serializer>(duration : &Duration, serializer : S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+impl Generation {
+    pub fn new(strict : bool, policy : PolicyConf) -> Self {
+        Generation { strict, policy, ..Default::default() }
+    }
+
+    fn pattern_set(&mut self, key : &'static str, patterns : &Option<Vec<String>>) -> Result<PatternSet, String> {
+        let patterns : Vec<String> = patterns.iter().flatten().cloned().collect();
+        let set = regex::RegexSet::new(&patterns).map_err(|e| format!("{}: {}", key, e))?;
+        for pattern in &patterns {
+            self.patterns.entry((key, pattern.clone())).or_default();
+        }
+        Ok(PatternSet { key, set, patterns })
+    }
+
+    /// Whether `path` matches `set`, recording which patterns did.
+    fn is_match(&mut self, set : &PatternSet, path : &str) -> bool {
+        let started = Instant::now();
+        let matches = set.set.matches(path);
+        if let Some(stats) = self.stats.last_mut() {
+            stats.match_time += started.elapsed();
+        }
+        for i in matches.iter() {
+            self.patterns.insert((set.key, set.patterns[i].clone()), true);
+        }
+        matches.matched_any()
+    }
+
+    /// Starts the statistics and progress bar of the workspace `name`.
+    pub fn start_workspace(&mut self, name : &str) {
+        self.stats.push(WorkspaceStats { name : name.into(), walked : 0, matched : 0, ignored : 0, include_dirs : 0, entries : 0, match_time : Duration::ZERO, elapsed : Duration::ZERO, started : Instant::now() });
+        if let Some(progress) = &self.progress {
+            let bar = progress.add(ProgressBar::new_spinner());
+            bar.set_style(ProgressStyle::with_template("{spinner} {prefix:.bold} {pos} paths walked, {msg} [{elapsed}]").unwrap());
+            bar.set_prefix(name.to_string());
+            bar.enable_steady_tick(Duration::from_millis(100));
+            self.bar = Some(bar);
+        }
+    }
+
+    /// Ends the statistics and progress bar of the current workspace.
+    pub fn finish_workspace(&mut self, entries : usize) {
+        if let Some(stats) = self.stats.last_mut() {
+            stats.entries = entries;
+            stats.elapsed = stats.started.elapsed();
+            tracing::info!(
+                workspace = stats.name, walked = stats.walked, entries,
+                walk_ms = (stats.elapsed - stats.match_time).as_millis() as u64, match_ms = stats.match_time.as_millis() as u64,
+                "workspace generated");
+        }
+        if let Some(bar) = self.bar.take() {
+            bar.finish_with_message(format!("{} entries", entries));
+        }
+    }
+
+    fn set_phase(&self, phase : &'static str) {
+        tracing::debug!(phase, "walking");
+        if let Some(bar) = &self.bar {
+            bar.set_message(phase);
+        }
+    }
+
+    fn walked(&mut self) {
+        if let Some(stats) = self.stats.last_mut() {
+            stats.walked += 1;
+        }
+        if let Some(bar) = &self.bar {
+            bar.inc(1);
+        }
+    }
+
+    /// Prints a warning without tearing the progress bars, or fails when the
+    /// policy of its category (or `strict`) makes it an error.
+    pub fn warn(&self, warning : Warning, message : &str) -> Result<(), String> {
+        let level = match warning {
+            Warning::MissingIncludeDir => self.policy.missing_include_dir,
+            Warning::EmptyWorkspace => self.policy.empty_workspace,
+            Warning::DuplicateFile => self.policy.duplicate_file,
+            Warning::OptionConflict => self.policy.option_conflict,
+            Warning::Other => self.policy.other,
+        }.unwrap_or_default();
+        match level {
+            PolicyLevel::Ignore => Ok(()),
+            PolicyLevel::Error => Err(message.into()),
+            PolicyLevel::Warn if self.strict => Err(format!("{} (--strict)", message)),
+            PolicyLevel::Warn => {
+                match &self.progress {
+                    Some(progress) => progress.suspend(|| crate::diag::warn_kind(warning.kind(), message)),
+                    None => crate::diag::warn_kind(warning.kind(), message),
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Patterns that never matched anything, as `key "pattern"`.
+    pub fn dead_patterns(&self) -> Vec<String> {
+        self.patterns.iter().filter(|(_, &matched)| !matched).map(|((key, pattern), _)| format!("{} \"{}\"", key, pattern)).collect()
+    }
+}
+
+struct PatternSet {
+    key : &'static str,
+    set : regex::RegexSet,
+    patterns : Vec<String>,
+}
+
+/// Walks `root` with a thread pool. Each directory's entries are sorted so
+/// the output does not depend on which thread got there first. Directories
+/// named in `prune_dirs` are dropped before they are read, and dot-entries
+/// unless `include_hidden` is set.
+pub fn walk(root : &Path, max_depth : Option<usize>, prune_dirs : &[String], include_hidden : bool) -> jwalk::WalkDir {
+    let prune_dirs = prune_dirs.to_vec();
+    let walk = jwalk::WalkDir::new(root).sort(true).skip_hidden(!include_hidden)
+        .process_read_dir(move |_, _, _, children| {
+            children.retain(|child| child.as_ref().map_or(true, |e| !(e.file_type().is_dir() && prune_dirs.iter().any(|d| e.file_name() == d.as_str()))));
+        });
+    match max_depth {
+        Some(max_depth) => walk.max_depth(max_depth),
+        None => walk,
+    }
+}
+
+/// Extensions of the files an include root is expected to contain.
+pub const HEADER_EXTENSIONS : [&str; 7] = ["h", "hh", "hpp", "hxx", "H", "inc", "inl"];
+
+/// The directories emitted as `-I`. An include root that does not exist or
+/// holds no headers is most likely a typo: a `missing_include_dir` warning,
+/// unless the root is optional; an absent optional root is emitted alone.
+pub fn list_include_dirs(effective : &EffectiveConf, generation : &mut Generation) -> Result<Vec<PathBuf>, String> {
+    let common_root = Path::new(&effective.root_dir);
+
+    /// The directories below `root_dir`, and whether the same walk met a
+    /// header in one of them.
+    fn walk_include_root(root_dir : &Path, prefix : &Path, effective : &EffectiveConf, ignore_set : &PatternSet, generation : &mut Generation) -> IncludeWalk {
+        let prune_dirs = effective.include.prune_dirs.as_deref().unwrap_or_default();
+        let max_depth = effective.include.max_depth;
+        let mut walked = IncludeWalk { has_headers : false, dirs : Vec::new() };
+        // One level deeper than the directories, for the files in the deepest ones.
+        for entry in walk(root_dir, max_depth.map(|depth| depth + 1), prune_dirs, effective.include_hidden).into_iter().filter_map(|e| e.ok()) {
+            if entry.file_type().is_file() {
+                walked.has_headers |= HEADER_EXTENSIONS.contains(&entry.path().extension().unwrap_or_default().to_str().unwrap_or_default());
+                continue;
+            }
+            if !entry.file_type().is_dir() || max_depth.is_some_and(|depth| entry.depth() > depth) {
+                continue;
+            }
+            generation.walked();
+            let path = get_slashed_path_without_prefix(&entry.path(), prefix);
+            if !generation.is_match(ignore_set, &path.to_string_lossy()) {
+                walked.dirs.push(path);
+            }
+        }
+        walked
+    }
+
+    generation.set_phase("include roots");
+    let mut include_dirs = Vec::<PathBuf>::new();
+    let ignore_set = generation.pattern_set("include.ignore_pattern", &effective.include.ignore_pattern)?;
+    for include_root in effective.include.root_dir.iter().flatten() {
+        // Workspaces often share vendor trees; each is walked once per run.
+        let key = IncludeWalkKey {
+            root : include_root.clone(),
+            prefix : common_root.to_path_buf(),
+            ignore_pattern : ignore_set.patterns.clone(),
+            max_depth : effective.include.max_depth,
+            include_hidden : effective.include_hidden,
+            prune_dirs : effective.include.prune_dirs.clone().unwrap_or_default(),
+        };
+        let walk = match generation.include_walks.get(&key) {
+            Some(walk) => walk.clone(),
+            None => {
+                let walk = walk_include_root(Path::new(include_root), common_root, effective, &ignore_set, generation);
+                generation.include_walks.insert(key, walk.clone());
+                walk
+            }
+        };
+
+        let optional = effective.optional_include_roots.contains(include_root);
+        if optional && !Path::new(include_root).is_dir() {
+            // Not generated yet; the compiler is told about it all the same.
+            include_dirs.push(get_slashed_path_without_prefix(Path::new(include_root), common_root));
+            continue;
+        }
+        let problem = if !Path::new(include_root).is_dir() {
+            Some("does not exist")
+        } else if !walk.has_headers {
+            Some("contains no headers")
+        } else {
+            None
+        };
+        if let Some(problem) = problem.filter(|_| !optional) {
+            let message = format!("workspace {}: include root {} {}", effective.name.as_deref().unwrap_or(&effective.path), include_root, problem);
+            generation.warn(Warning::MissingIncludeDir, &message)?;
+        }
+        include_dirs.extend(walk.dirs);
+    }
+
+    // Overlapping roots yield the same directory twice; the first, highest
+    // priority position is the one the compiler would use.
+    let mut seen = HashSet::new();
+    include_dirs.retain(|dir| seen.insert(dir.clone()));
+
+    if let Some(stats) = generation.stats.last_mut() {
+        stats.include_dirs = include_dirs.len();
+    }
+    Ok(include_dirs)
+}
+
+pub fn list_target_files(effective : &EffectiveConf, generation : &mut Generation) -> Result<Vec<PathBuf>, String> {
+    let mut target_files = matched_target_files(effective, generation)?;
+    let workspace_abs_path = Path::new(&effective.root_dir).join(&effective.path);
+    for file in &effective.generated_files {
+        let path = workspace_abs_path.join(file);
+        if !effective.allow_missing && !path.is_file() {
+            return Err(format!("workspace {}: generated file {} does not exist (allow_missing = true emits it anyway)", effective.name.as_deref().unwrap_or(&effective.path), path.display()));
+        }
+        let path = get_slashed_path_without_prefix(&path, Path::new(&effective.root_dir));
+        if !target_files.contains(&path) {
+            target_files.push(path);
+        }
+    }
+    Ok(target_files)
+}
+
+/// The files of a workspace found by `files`/`file_list` or by its patterns.
+fn matched_target_files(effective : &EffectiveConf, generation : &mut Generation) -> Result<Vec<PathBuf>, String> {
+    let common_root = Path::new(&effective.root_dir);
+    let workspace_abs_path = common_root.join(&effective.path);
+
+    if effective.files.is_some() || effective.file_list.is_some() {
+        let target_files = listed_files(effective, &workspace_abs_path)?;
+        if let Some(stats) = generation.stats.last_mut() {
+            stats.matched = target_files.len();
+        }
+        return Ok(target_files);
+    }
+
+    let mut target_files = Vec::<PathBuf>::new();
+    let target_set = generation.pattern_set("target.match_pattern", &effective.target.match_pattern)?;
+    let ignore_set = generation.pattern_set("target.ignore_pattern", &effective.target.ignore_pattern)?;
+
+    generation.set_phase("sources");
+    let candidates : Vec<PathBuf> = match effective.source {
+        FileSource::Walk => {
+            let prune_dirs = effective.target.prune_dirs.as_deref().unwrap_or_default();
+            walk(&workspace_abs_path, effective.target.max_depth, prune_dirs, effective.include_hidden).into_iter().filter_map(|e| e.ok()).map(|e| e.path()).collect()
+        }
+        FileSource::Git => git_ls_files(&workspace_abs_path)?,
+    };
+    for candidate in candidates {
+        generation.walked();
+        let file_str = get_slashed_path_without_prefix(&candidate, common_root);
+        if !generation.is_match(&target_set, &file_str.to_string_lossy()) {
+            continue;
+        }
+        let ignored = generation.is_match(&ignore_set, &file_str.to_string_lossy());
+        if let Some(stats) = generation.stats.last_mut() {
+            if ignored { stats.ignored += 1 } else { stats.matched += 1 }
+        }
+        if !ignored {
+            target_files.push(file_str);
+        }
+    }
+
+    Ok(target_files)
+}
+
+/// The files git tracks under `dir`.
+fn git_ls_files(dir : &Path) -> Result<Vec<PathBuf>, String> {
+    let output = std::process::Command::new("git").arg("-C").arg(dir).args(["ls-files", "-z"]).output().map_err(|e| format!("git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("git ls-files in {}: {}", dir.display(), String::from_utf8_lossy(&output.stderr).trim_end()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).split('\0').filter(|f| !f.is_empty()).map(|f| dir.join(f)).collect())
+}
+
+/// The files under the git checkout containing `dir` that differ from
+/// `git_ref` (committed, staged or not), plus untracked ones; canonical paths.
+pub fn changed_files(dir : &Path, git_ref : &str) -> Result<HashSet<PathBuf>, String> {
+    let git = |args : &[&str]| {
+        let output = std::process::Command::new("git").arg("-C").arg(dir).args(args).output().map_err(|e| format!("git: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("git {}: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim_end()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+
+    let toplevel = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim_end());
+    let mut changed = git(&["diff", "--name-only", "-z", git_ref, "--"])?;
+    changed += &git(&["ls-files", "-z", "--others", "--exclude-standard", "--full-name", ":/"])?;
+    Ok(changed.split('\0').filter(|f| !f.is_empty()).filter_map(|f| std::fs::canonicalize(toplevel.join(f)).ok()).collect())
+}
+
+/// The files of a workspace given by `files`/`file_list`. Every one of them
+/// must exist: the list is the authority on what gets compiled.
+fn listed_files(effective : &EffectiveConf, workspace_abs_path : &Path) -> Result<Vec<PathBuf>, String> {
+    let mut listed = effective.files.clone().unwrap_or_default();
+    if let Some(file_list) = &effective.file_list {
+        let path = workspace_abs_path.join(file_list);
+        let content = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        listed.extend(content.lines()
+            .map(|line| line.split('#').next().unwrap().trim())
+            .filter(|line| !line.is_empty())
+            .map(String::from));
+    }
+
+    let mut target_files = Vec::<PathBuf>::new();
+    for file in listed {
+        let path = workspace_abs_path.join(&file);
+        if !path.is_file() {
+            return Err(format!("workspace {}: listed file {} does not exist", effective.name.as_deref().unwrap_or(&effective.path), path.display()));
+        }
+        target_files.push(get_slashed_path_without_prefix(&path, Path::new(&effective.root_dir)));
+    }
+    Ok(target_files)
+}
+
+/// `--sysroot` and, when asked for, the sysroot's system include directories.
+fn sysroot_flags(sysroot : &str, system_includes : bool) -> Vec<String> {
+    let mut flags = vec![format!("--sysroot={}", sysroot)];
+    if system_includes {
+        let root = Path::new(sysroot);
+        let mut include_dirs = vec![root.join("usr/include")];
+        for pattern in ["usr/include/*-linux-*", "usr/lib/gcc/*/*/include"] {
+            include_dirs.extend(glob::glob(&root.join(pattern).to_string_lossy()).into_iter().flatten().filter_map(|p| p.ok()).filter(|p| p.is_dir()));
+        }
+        for include_dir in include_dirs.into_iter().filter(|d| d.is_dir()) {
+            flags.extend(["-isystem".into(), slashed(&include_dir)]);
+        }
+    }
+    flags
+}
+
+/// How a target file is compiled, from its extension.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Language {
+    C,
+    Cpp,
+    /// `.s`: plain assembly, not preprocessed.
+    Asm,
+    /// `.S`/`.sx`: assembly run through the preprocessor.
+    AsmWithCpp,
+    /// `.m`
+    ObjC,
+    /// `.mm`/`.M`
+    ObjCpp,
+    /// `.cu`
+    Cuda,
+}
+
+impl Language {
+    pub fn of(path : &Path) -> Language {
+        match path.extension().unwrap_or_default().to_str().unwrap_or_default() {
+            "cc" | "CC" | "cpp" | "CPP" | "cxx" | "CXX" => Language::Cpp,
+            "s" => Language::Asm,
+            "S" | "sx" => Language::AsmWithCpp,
+            "m" => Language::ObjC,
+            "mm" | "M" => Language::ObjCpp,
+            "cu" => Language::Cuda,
+            _ => Language::C,
+        }
+    }
+
+    pub fn is_assembly(self) -> bool {
+        matches!(self, Language::Asm | Language::AsmWithCpp)
+    }
+
+    /// The command and the flags that go right after it.
+    fn compiler(self, effective : &EffectiveConf) -> (&Vec<String>, Vec<String>) {
+        let standard = |std : &Option<String>| std.iter().map(|s| format!("-std={}", s)).collect::<Vec<String>>();
+        let language = |x : &str, std : &Option<String>| [vec!["-x".into(), x.into()], standard(std)].concat();
+        match self {
+            Language::C => (&effective.c_compiler, standard(&effective.c_std)),
+            Language::Cpp => (&effective.cpp_compiler, standard(&effective.cpp_std)),
+            Language::Asm => (&effective.asm_compiler, vec!["-x".into(), "assembler".into()]),
+            Language::AsmWithCpp => (&effective.asm_compiler, vec!["-x".into(), "assembler-with-cpp".into()]),
+            Language::ObjC => (&effective.objc_compiler, language("objective-c", &effective.c_std)),
+            Language::ObjCpp => (&effective.objcxx_compiler, language("objective-c++", &effective.cpp_std)),
+            Language::Cuda => {
+                let mut flags = language("cuda", &effective.cpp_std);
+                flags.extend(effective.cuda_path.iter().map(|p| format!("--cuda-path={}", p)));
+                (&effective.cuda_compiler, flags)
+            }
+        }
+    }
+}
+
+/// Compilation entries for the files of one workspace.
+pub fn workspace_entries(effective : &EffectiveConf, generation : &mut Generation) -> Result<Vec<CompilationEntry>, String> {
+    let targets = list_target_files(effective, generation)?;
+
+    let mut target_flags = effective.machine.flags();
+    target_flags.extend(effective.sysroot.iter().flat_map(|s| sysroot_flags(s, effective.sysroot_system_includes)));
+    let mut include_dirs = list_include_dirs(effective, generation)?;
+    if effective.unused_include_dirs != UnusedIncludeDirs::Keep {
+        let used = includes::resolve_usage(Path::new(&effective.root_dir), &targets, &include_dirs).used_dirs();
+        let unused : Vec<String> = include_dirs.iter().enumerate().filter(|(i, _)| !used.contains(i)).map(|(_, d)| lossy(d)).collect();
+        if !unused.is_empty() {
+            let name = effective.name.as_deref().unwrap_or(&effective.path);
+            match effective.unused_include_dirs {
+                UnusedIncludeDirs::Report => generation.warn(Warning::Other, &format!("workspace {}: {} of {} include dirs are never included from: {}", name, unused.len(), include_dirs.len(), unused.join(", ")))?,
+                _ => tracing::info!(workspace = name, dropped = unused.len(), kept = include_dirs.len() - unused.len(), "dropped unused include dirs"),
+            }
+        }
+        if effective.unused_include_dirs == UnusedIncludeDirs::Drop {
+            include_dirs = include_dirs.into_iter().enumerate().filter(|(i, _)| used.contains(i)).map(|(_, d)| d).collect();
+        }
+    }
+    // Each file with the target whose flags it gets: the sources of a unity
+    // file get the unity file's, of the first one when several include them.
+    let mut sources = Vec::<(PathBuf, PathBuf)>::new();
+    let mut unity_included = HashSet::<PathBuf>::new();
+    for target in &targets {
+        let unity_sources = match effective.unity {
+            UnityMode::Keep => None,
+            _ => includes::unity_sources(Path::new(&effective.root_dir), target, &include_dirs),
+        };
+        let Some(unity_sources) = unity_sources else {
+            sources.push((target.clone(), target.clone()));
+            continue;
+        };
+        if effective.unity == UnityMode::Expand {
+            sources.push((target.clone(), target.clone()));
+        }
+        sources.extend(unity_sources.into_iter()
+            .filter(|source| !targets.contains(source) && unity_included.insert(source.clone()))
+            .map(|source| (source, target.clone())));
+    }
+    let mut options : Vec<String> = include_dirs.into_iter().map(|d| format!("-I{}", lossy(&d))).collect();
+    for header in &effective.force_include {
+        options.extend(["-include".into(), header.clone()]);
+    }
+    if let Some(pch) = &effective.pch {
+        options.extend(["-include-pch".into(), pch.clone()]);
+    }
+    options.extend(effective.option.arg.iter().flatten().cloned());
+    let mut origin = effective.option_origin.clone();
+    // Workspaces usually share their packages; each list is read once per run.
+    let pkg_config_flags = match generation.pkg_config_flags.get(&effective.pkg_config) {
+        Some(flags) => flags.clone(),
+        None => {
+            let flags = pkg_config::cflags(&effective.pkg_config)?;
+            generation.pkg_config_flags.insert(effective.pkg_config.clone(), flags.clone());
+            flags
+        }
+    };
+    let packages_key = (effective.root_dir.clone(), effective.packages.clone());
+    let package_flags = match generation.package_flags.get(&packages_key) {
+        Some(flags) => flags.clone(),
+        None => {
+            let flags = packages::flags(Path::new(&effective.root_dir), &effective.packages)?;
+            generation.package_flags.insert(packages_key, flags.clone());
+            flags
+        }
+    };
+    for (layer, flags) in [("pkg_config", pkg_config_flags), ("packages", package_flags)] {
+        origin.extend(flags.iter().map(|flag| (flag.clone(), layer.to_string())));
+        options.extend(flags);
+    }
+
+    let file_options = effective.file_option.iter()
+        .map(|rule| regex::Regex::new(&rule.pattern).map(|pattern| (pattern, rule)).map_err(|e| format!("file_option pattern: {}", e)))
+        .collect::<Result<Vec<_>, String>>()?;
+    for (_, rule) in &file_options {
+        origin.extend(rule.arg.iter().flatten().map(|arg| (arg.clone(), format!("file_option \"{}\"", rule.pattern))));
+    }
+    let mut conflicts = BTreeMap::<String, usize>::new();
+    if let Some(template) = &effective.argument_template {
+        check_template(template)?;
+    }
+
+    let mut entries = Vec::<CompilationEntry>::new();
+    for (target, flags_of) in sources {
+        let language = Language::of(&flags_of);
+        if language.is_assembly() && effective.exclude_assembly {
+            continue;
+        }
+        let target_str = lossy(&target);
+        let target_str = target_str.as_str();
+        let mut compilation_entry = CompilationEntry {file: target_str.into(), ..Default::default()};
+        //println!("{}", target.display());
+        let (compiler, language_flags) = language.compiler(effective);
+        let mut file_specific_options = [target_flags.clone(), language_flags, options.clone()].concat();
+        let flags_of = lossy(&flags_of);
+        for (_, rule) in file_options.iter().filter(|(pattern, _)| pattern.is_match(&flags_of)) {
+            file_specific_options.retain(|option| !rule.remove_arg.iter().flatten().any(|removed| removed == option));
+            file_specific_options.extend(rule.arg.iter().flatten().cloned());
+        }
+        let file_specific_options = compdb::dedup_options(&file_specific_options);
+        for conflict in option_conflicts(&file_specific_options) {
+            let described : Vec<String> = conflict.iter().map(|(shown, token)| format!("{} ({})", shown, origin.get(token).map_or("generated", String::as_str))).collect();
+            *conflicts.entry(described.join(" and ")).or_default() += 1;
+        }
+        match &effective.argument_template {
+            Some(template) => {
+                let output = lossy(&target.with_extension("o"));
+                compilation_entry.arguments = expand_template(template, compiler, &file_specific_options, target_str, &output);
+                if template.iter().any(|token| token.contains("{output}")) {
+                    compilation_entry.output = Some(output);
+                }
+            }
+            None => {
+                compilation_entry.arguments.extend(compiler.clone());
+                compilation_entry.arguments.extend(file_specific_options);
+                compilation_entry.arguments.extend(["-c".into(), target_str.into()]);
+            }
+        }
+        compilation_entry.directory = effective.root_dir.clone();
+        match effective.directory_mode {
+            DirectoryMode::Root => {}
+            DirectoryMode::Workspace => rebase_entry(&mut compilation_entry, Path::new(&effective.path)),
+            DirectoryMode::File => rebase_entry(&mut compilation_entry, target.parent().unwrap_or(Path::new(""))),
+        }
+
+        entries.push(compilation_entry);
+    }
+    let name = effective.name.as_deref().unwrap_or(&effective.path);
+    for (conflict, files) in conflicts {
+        generation.warn(Warning::OptionConflict, &format!("workspace {}: conflicting options {} in {} files; the last one wins", name, conflict, files))?;
+    }
+
+    Ok(entries)
+}
+
+/// The level of an optimization option, `-O` being `-O1`; none for the
+/// options that only start like one, e.g. `-ObjC`.
+fn optimization_level(option : &str) -> Option<&str> {
+    match option.strip_prefix("-O")? {
+        "" => Some("1"),
+        level @ ("s" | "z" | "g" | "fast") => Some(level),
+        level if level.bytes().all(|b| b.is_ascii_digit()) => Some(level),
+        _ => None,
+    }
+}
+
+/// Options of `options` that contradict each other, as they are written
+/// and the argument their origin is looked up by: different values of one
+/// macro or of a flag that takes one (`-O`, `-std=`, `-march=`...), in
+/// command line order.
+fn option_conflicts(options : &[String]) -> Vec<Vec<(String, String)>> {
+    const SINGLE_VALUED : [&str; 6] = ["-std=", "-march=", "-mcpu=", "-mtune=", "-mfpu=", "-mfloat-abi="];
+    // Per key, the distinct values with how the first of each was written.
+    let mut values = Vec::<(String, Vec<(String, String, String)>)>::new();
+    for option in compdb::group_options(options) {
+        let (key, value, shown, token) = match option {
+            [flag, definition] if flag == "-D" => {
+                let (name, value) = definition.split_once('=').unwrap_or((definition, "1"));
+                (format!("-D{}", name), value.to_string(), format!("-D{}", definition), definition.clone())
+            }
+            [single] if single.starts_with("-D") => {
+                let (name, value) = single[2..].split_once('=').unwrap_or((&single[2..], "1"));
+                (format!("-D{}", name), value.to_string(), single.clone(), single.clone())
+            }
+            [single] => match optimization_level(single) {
+                Some(level) => ("-O".into(), level.to_string(), single.clone(), single.clone()),
+                None => match SINGLE_VALUED.iter().find(|prefix| single.starts_with(*prefix)) {
+                    Some(prefix) => (prefix.to_string(), single[prefix.len()..].to_string(), single.clone(), single.clone()),
+                    None => continue,
+                },
+            },
+            _ => continue,
+        };
+        let index = match values.iter().position(|(k, _)| *k == key) {
+            Some(index) => index,
+            None => {
+                values.push((key, Vec::new()));
+                values.len() - 1
+            }
+        };
+        if !values[index].1.iter().any(|(v, _, _)| *v == value) {
+            values[index].1.push((value, shown, token));
+        }
+    }
+    values.into_iter()
+        .filter(|(_, distinct)| distinct.len() > 1)
+        .map(|(_, distinct)| distinct.into_iter().map(|(_, shown, token)| (shown, token)).collect())
+        .collect()
+}
+
+/// `path`, relative to the root, as seen from `dir`, also relative to the root.
+fn relative_from(path : &str, dir : &Path) -> String {
+    fn components(p : &Path) -> Vec<std::path::Component<'_>> {
+        p.components().filter(|c| !matches!(c, std::path::Component::CurDir)).collect()
+    }
+    let (path_components, dir_components) = (components(Path::new(path)), components(dir));
+    let common = path_components.iter().zip(&dir_components).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..dir_components.len() {
+        relative.push("..");
+    }
+    relative.extend(&path_components[common..]);
+    let relative = slashed(&relative);
+    if relative.is_empty() { ".".into() } else { relative }
+}
+
+/// Moves `entry` from the root to `dir` (relative to the root, or
+/// absolute), rewriting the relative paths the generator put into it. The
+/// ones that do not stay below the root, or all of them when `dir` is not
+/// below it, are made absolute instead.
+fn rebase_entry(entry : &mut CompilationEntry, dir : &Path) {
+    let root = PathBuf::from(&entry.directory);
+    let dir = clean(&root.join(dir));
+    let dir_below_root = dir.strip_prefix(&root).ok();
+    let rebase = |path : &str| {
+        if Path::new(path).is_absolute() {
+            return path.to_string();
+        }
+        let absolute = clean(&root.join(path));
+        match (absolute.strip_prefix(&root), dir_below_root) {
+            (Ok(relative), Some(dir)) => relative_from(&lossy(relative), dir),
+            _ => slashed(&absolute),
+        }
+    };
+    let mut arguments = Vec::<String>::new();
+    for option in compdb::group_options(&entry.arguments) {
+        match option {
+            [flag, value] if ["-include", "-include-pch", "-imacros", "-isystem", "-iquote", "-idirafter", "-I", "-o"].contains(&flag.as_str()) => {
+                arguments.extend([flag.clone(), rebase(value)]);
+            }
+            [single] if single.starts_with("-I") => arguments.push(format!("-I{}", rebase(&single[2..]))),
+            [single] if *single == entry.file => arguments.push(rebase(single)),
+            _ => arguments.extend(option.iter().cloned()),
+        }
+    }
+    entry.arguments = arguments;
+    entry.output = entry.output.as_deref().map(rebase);
+    entry.file = rebase(&entry.file);
+    entry.directory = slashed(&dir);
+}
+
+/// Placeholders expanding to any number of arguments, which must be whole tokens.
+const LIST_PLACEHOLDERS : [&str; 4] = ["compiler", "defines", "includes", "options"];
+
+/// Fails on a `{name}` in `argument_template` that is no placeholder, or a
+/// list placeholder inside a token. Braces around nothing or anything but
+/// a name are taken literally, as paths may contain them.
+fn check_template(template : &[String]) -> Result<(), String> {
+    let placeholder = regex::Regex::new(r"\{([a-z_]+)\}").unwrap();
+    for token in template {
+        for name in placeholder.captures_iter(token).map(|captures| captures.get(1).unwrap().as_str()) {
+            if LIST_PLACEHOLDERS.contains(&name) {
+                if *token != format!("{{{}}}", name) {
+                    return Err(format!("argument_template: {{{}}} must be a whole argument, not part of \"{}\"", name, token));
+                }
+            } else if name != "file" && name != "output" {
+                return Err(format!("argument_template: unknown placeholder {{{}}} in \"{}\"", name, token));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Arguments laid out by `argument_template`, checked by `check_template`.
+/// `{compiler}`, `{defines}`, `{includes}` and `{options}` are whole tokens
+/// and expand to any number of arguments; `{file}` and `{output}` may sit
+/// inside a token.
+fn expand_template(template : &[String], compiler : &[String], options : &[String], file : &str, output : &str) -> Vec<String> {
+    let (mut defines, mut includes, mut others) = (Vec::<String>::new(), Vec::<String>::new(), Vec::<String>::new());
+    for option in compdb::group_options(options) {
+        let flag = option[0].as_str();
+        if flag.starts_with("-D") || flag.starts_with("-U") {
+            defines.extend_from_slice(option);
+        } else if flag.starts_with("-I") || ["-isystem", "-iquote", "-idirafter", "-include", "-include-pch", "-imacros"].iter().any(|f| flag.starts_with(f)) {
+            includes.extend_from_slice(option);
+        } else {
+            others.extend_from_slice(option);
+        }
+    }
+
+    let mut arguments = Vec::<String>::new();
+    for token in template {
+        match token.as_str() {
+            "{compiler}" => arguments.extend_from_slice(compiler),
+            "{defines}" => arguments.extend(defines.iter().cloned()),
+            "{includes}" => arguments.extend(includes.iter().cloned()),
+            "{options}" => arguments.extend(others.iter().cloned()),
+            _ => arguments.push(token.replace("{file}", file).replace("{output}", output)),
+        }
+    }
+    arguments
+}
+
+/// Fails when `count` entries fall outside `[min, max]`.
+fn check_entry_count(what : &str, count : usize, min : Option<usize>, max : Option<usize>) -> Result<(), String> {
+    match (min, max) {
+        (Some(min), _) if count < min => Err(format!("{} has {} entries, fewer than min_entries = {}", what, count, min)),
+        (_, Some(max)) if count > max => Err(format!("{} has {} entries, more than max_entries = {}", what, count, max)),
+        _ => Ok(()),
+    }
+}
+
+/// Why a workspace got no entries, to point at the likely typo.
+fn empty_reason(effective : &EffectiveConf, stats : Option<&WorkspaceStats>) -> String {
+    let workspace_dir = Path::new(&effective.root_dir).join(&effective.path);
+    let (matched, ignored) = stats.map_or((0, 0), |s| (s.matched, s.ignored));
+    if effective.files.is_some() || effective.file_list.is_some() {
+        "its files / file_list name no file".into()
+    } else if !workspace_dir.is_dir() {
+        format!("{} does not exist", slashed(&workspace_dir))
+    } else if matched > 0 {
+        format!("none of its {} matching file(s) got an entry (exclude_assembly?)", matched)
+    } else if ignored > 0 {
+        format!("target.ignore_pattern drops all {} file(s) matching target.match_pattern", ignored)
+    } else {
+        format!("no file matches target.match_pattern {:?}", effective.target.match_pattern.as_deref().unwrap_or_default())
+    }
+}
+
+/// FNV-1a of `bytes`: unlike `std`'s hashers, the same on every platform
+/// and toolchain, so it can be stored.
+fn stable_hash(bytes : &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Hash of what the database depends on apart from the contents of the
+/// sources: the configuration files, the command line, and the target
+/// files and include dirs of `workspaces`, and with `--changed-since` the
+/// ref and the files changed since it. A database is up to date while
+/// it stays the same. The include-root walks are left in the cache of
+/// `generation`, which the generation that may follow reuses.
+pub fn fingerprint(config_files : &[String], conf : &CompDBConf, workspaces : &[&WorkSpaceConf], generation : &mut Generation) -> Result<String, String> {
+    let mut config = Vec::new();
+    for config_file in config_files {
+        config.push(String::from_utf8_lossy(&std::fs::read(config_file).map_err(|e| format!("{}: {}", config_file, e))?).into_owned());
+    }
+    // Warnings are for the generation; this only looks.
+    let ignore = Some(PolicyLevel::Ignore);
+    let mut silent = Generation::new(false, PolicyConf { missing_include_dir : ignore, empty_workspace : ignore, duplicate_file : ignore, option_conflict : ignore, other : ignore });
+    silent.include_walks = std::mem::take(&mut generation.include_walks);
+    let mut inputs = Vec::new();
+    for workspace in workspaces {
+        let effective = EffectiveConf::resolve(conf, workspace);
+        let targets = list_target_files(&effective, &mut silent)?;
+        let include_dirs = list_include_dirs(&effective, &mut silent)?;
+        inputs.push(serde_json::json!({
+            "targets" : targets.iter().map(|path| slashed(path)).collect::<Vec<_>>(),
+            "include_dirs" : include_dirs.iter().map(|path| slashed(path)).collect::<Vec<_>>(),
+        }));
+    }
+    generation.include_walks = silent.include_walks;
+    let changed_since = generation.changed_since.as_ref().map(|(git_ref, changed)| {
+        let mut changed : Vec<_> = changed.iter().map(|path| slashed(path)).collect();
+        changed.sort();
+        serde_json::json!({ "ref" : git_ref, "files" : changed })
+    });
+    let serialized = serde_json::json!({
+        "config" : config,
+        "args" : std::env::args_os().skip(1).map(|arg| arg.to_string_lossy().into_owned()).collect::<Vec<_>>(),
+        "workspaces" : inputs,
+        "changed_since" : changed_since,
+    }).to_string();
+    Ok(format!("{:016x}", stable_hash(serialized.as_bytes())))
+}
+
+pub fn generate_entries(conf : &CompDBConf, workspaces : &[&WorkSpaceConf], generation : &mut Generation) -> Result<Vec<CompilationEntry>, String> {
+    let policy = conf.common.duplicate_policy.unwrap_or_default();
+    let mut compilation_db = Vec::<CompilationEntry>::new();
+    let mut claimed = HashMap::<PathBuf, (usize, &str)>::new();
+    for workspace in workspaces {
+        let name = workspace.name.as_deref().unwrap_or(&workspace.path);
+        generation.start_workspace(name);
+        let effective = EffectiveConf::resolve(conf, workspace);
+        let mut entries = workspace_entries(&effective, generation)?;
+        if let Some((_, changed)) = &generation.changed_since {
+            entries.retain(|entry| std::fs::canonicalize(entry.path()).is_ok_and(|path| changed.contains(&path)));
+        }
+        generation.finish_workspace(entries.len());
+        if generation.changed_since.is_none() {
+            if entries.is_empty() {
+                let reason = empty_reason(&effective, generation.stats.last());
+                generation.warn(Warning::EmptyWorkspace, &format!("workspace {} has no target files: {}", name, reason))?;
+            }
+            check_entry_count(&format!("workspace {}", name), entries.len(), workspace.min_entries, workspace.max_entries)?;
+        }
+        for entry in entries {
+            let Some(&(index, owner)) = claimed.get(&entry.path()) else {
+                claimed.insert(entry.path(), (compilation_db.len(), name));
+                compilation_db.push(entry);
+                continue;
+            };
+            let message = format!("{} is claimed by workspaces {} and {}", entry.path().display(), owner, name);
+            match policy {
+                DuplicatePolicy::Error => return Err(format!("{} (duplicate_policy = \"error\")", message)),
+                DuplicatePolicy::First => generation.warn(Warning::DuplicateFile, &format!("{}; keeping {}", message, owner))?,
+                DuplicatePolicy::Last => {
+                    generation.warn(Warning::DuplicateFile, &format!("{}; keeping {}", message, name))?;
+                    claimed.insert(entry.path(), (index, name));
+                    compilation_db[index] = entry;
+                }
+                DuplicatePolicy::KeepAll => {
+                    generation.warn(Warning::DuplicateFile, &message)?;
+                    compilation_db.push(entry);
+                }
+            }
+        }
+    }
+    if generation.changed_since.is_none() {
+        check_entry_count("the database", compilation_db.len(), conf.common.min_entries, conf.common.max_entries)?;
+    }
+    Ok(compilation_db)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{check_template, expand_template, get_slashed_path_without_prefix, option_conflicts, slashed, Generation};
+
+    #[test]
+    fn slashed_normalizes_windows_spellings() {
+        assert_eq!(slashed(Path::new(r"c:\work\src\main.c")), "C:/work/src/main.c");
+        assert_eq!(slashed(Path::new(r"\\?\C:\work\src")), "C:/work/src");
+        assert_eq!(slashed(Path::new(r"\\?\UNC\server\share\inc")), "//server/share/inc");
+        assert_eq!(slashed(Path::new(r"\\server\share\inc")), "//server/share/inc");
+        assert_eq!(slashed(Path::new("/usr/include")), "/usr/include");
+    }
+
+    #[test]
+    fn relative_under_the_root() {
+        assert_eq!(get_slashed_path_without_prefix(Path::new(r"C:\work\src\a.c"), Path::new(r"C:\work")), PathBuf::from("src/a.c"));
+        assert_eq!(get_slashed_path_without_prefix(Path::new(r"\\?\C:\work\inc"), Path::new(r"C:\work\")), PathBuf::from("inc"));
+        assert_eq!(get_slashed_path_without_prefix(Path::new(r"\\server\share\proj\inc"), Path::new(r"\\server\share\proj")), PathBuf::from("inc"));
+        assert_eq!(get_slashed_path_without_prefix(Path::new("/work"), Path::new("/work")), PathBuf::from(""));
+    }
+
+    #[test]
+    fn whole_path_on_another_drive_or_share() {
+        assert_eq!(get_slashed_path_without_prefix(Path::new(r"D:\sdk\include"), Path::new(r"C:\work")), PathBuf::from("D:/sdk/include"));
+        assert_eq!(get_slashed_path_without_prefix(Path::new(r"\\server\share\inc"), Path::new(r"C:\work")), PathBuf::from("//server/share/inc"));
+        assert_eq!(get_slashed_path_without_prefix(Path::new(r"\\?\UNC\server\other\inc"), Path::new(r"\\server\share")), PathBuf::from("//server/other/inc"));
+        // A common string prefix is not a common directory.
+        assert_eq!(get_slashed_path_without_prefix(Path::new("/work2/inc"), Path::new("/work")), PathBuf::from("/work2/inc"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths_are_only_stripped() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let root = Path::new(OsStr::from_bytes(b"/work/\xff"));
+        assert_eq!(get_slashed_path_without_prefix(&root.join("inc"), root), PathBuf::from("inc"));
+        let elsewhere = Path::new(OsStr::from_bytes(b"/sdk/\xff/inc"));
+        assert_eq!(get_slashed_path_without_prefix(elsewhere, root), elsewhere);
+    }
+
+    #[test]
+    fn template_placeholders_are_checked_before_expansion() {
+        let template = |tokens : &[&str]| tokens.iter().map(|token| token.to_string()).collect::<Vec<_>>();
+        assert!(check_template(&template(&["{compiler}", "{options}", "-o", "{output}", "{file}"])).is_ok());
+        assert!(check_template(&template(&["{compiler}", "-I{}/inc", "-DX={", "{file}"])).is_ok());
+        assert!(check_template(&template(&["{compiler}", "{flags}"])).unwrap_err().contains("unknown placeholder {flags}"));
+        assert!(check_template(&template(&["{compiler}", "-x{defines}"])).unwrap_err().contains("whole argument"));
+        // A file name looking like a placeholder is not one.
+        assert_eq!(expand_template(&template(&["{compiler}", "-c", "{file}"]), &["cc".into()], &[], "{name}.c", "a.o"), ["cc", "-c", "{name}.c"]);
+    }
+
+    #[test]
+    fn invalid_patterns_are_errors_naming_their_key() {
+        let mut generation = Generation::default();
+        let error = generation.pattern_set("target.match_pattern", &Some(vec!["[.]c$".into(), "(".into()])).err().unwrap();
+        assert!(error.starts_with("target.match_pattern: "));
+        assert!(generation.patterns.is_empty());
+        let set = generation.pattern_set("target.ignore_pattern", &None).unwrap();
+        assert!(set.patterns.is_empty());
+    }
+
+    #[test]
+    fn optimization_levels_conflict_but_objc_is_not_one() {
+        let options = |options : &[&str]| options.iter().map(|o| o.to_string()).collect::<Vec<_>>();
+        let conflicts = option_conflicts(&options(&["-O2", "-ObjC", "-ObjC++", "-Os", "-O", "-O1"]));
+        let shown : Vec<Vec<&str>> = conflicts.iter().map(|conflict| conflict.iter().map(|(shown, _)| shown.as_str()).collect()).collect();
+        // -O and -O1 are the same level.
+        assert_eq!(shown, [["-O2", "-Os", "-O"]]);
+        assert!(option_conflicts(&options(&["-ObjC", "-Ofast", "-Ofast"])).is_empty());
+    }
+}
@@ -0,0 +1,70 @@
+//! GraphViz rendering of the workspaces, their include roots and the
+//! workspaces they use.
+
+use std::fmt::Write;
+
+/// A workspace as `report graph` draws it; paths relative to `common.root_dir`.
+pub struct Node {
+    pub name : String,
+    pub path : String,
+    /// Names of the workspaces it uses, directly.
+    pub uses : Vec<String>,
+    pub include_roots : Vec<String>,
+}
+
+fn quoted(text : &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Whether `path` is `dir` or below it.
+fn is_under(path : &str, dir : &str) -> bool {
+    dir == "." || path == dir || path.strip_prefix(dir).is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// The names of `start` and of the workspaces it uses, directly or not.
+fn uses_closure<'a>(nodes : &'a [Node], start : &'a Node) -> Vec<&'a str> {
+    let mut reached = vec![start.name.as_str()];
+    let mut pending : Vec<&str> = start.uses.iter().map(String::as_str).collect();
+    while let Some(used) = pending.pop() {
+        if reached.contains(&used) {
+            continue;
+        }
+        reached.push(used);
+        if let Some(node) = nodes.iter().find(|node| node.name == used) {
+            pending.extend(node.uses.iter().map(String::as_str));
+        }
+    }
+    reached
+}
+
+/// `nodes` as a DOT digraph: workspaces are boxes with a bold edge to the
+/// workspaces they use and an edge to each of their include roots. A root
+/// inside another workspace the workspace does not use, even indirectly,
+/// is a red edge: a dependency between modules nobody declared.
+pub fn dot(nodes : &[Node]) -> String {
+    let owner = |root : &str| nodes.iter().filter(|node| is_under(root, &node.path)).max_by_key(|node| node.path.len());
+    let mut text = String::from("digraph workspaces {\n    rankdir=LR;\n    node [fontname=\"Helvetica\"];\n");
+    for node in nodes {
+        writeln!(text, "    {} [shape=box, style=filled, fillcolor=lightblue, label={}];", quoted(&format!("ws:{}", node.name)), quoted(&node.name)).unwrap();
+    }
+    let mut roots = Vec::<&str>::new();
+    for root in nodes.iter().flat_map(|node| &node.include_roots) {
+        if !roots.contains(&root.as_str()) {
+            roots.push(root);
+            writeln!(text, "    {} [shape=folder, label={}];", quoted(&format!("dir:{}", root)), quoted(root)).unwrap();
+        }
+    }
+    for node in nodes {
+        for used in &node.uses {
+            writeln!(text, "    {} -> {} [style=bold, label=\"uses\"];", quoted(&format!("ws:{}", node.name)), quoted(&format!("ws:{}", used))).unwrap();
+        }
+        let allowed = uses_closure(nodes, node);
+        for root in &node.include_roots {
+            let undeclared = owner(root).is_some_and(|owner| !allowed.contains(&owner.name.as_str()));
+            let attributes = if undeclared { " [color=red, fontcolor=red, label=\"undeclared\"]" } else { "" };
+            writeln!(text, "    {} -> {}{};", quoted(&format!("ws:{}", node.name)), quoted(&format!("dir:{}", root)), attributes).unwrap();
+        }
+    }
+    text.push_str("}\n");
+    text
+}
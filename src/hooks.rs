@@ -0,0 +1,23 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::shell;
+
+/// Runs the `[hooks]` commands of `stage` (`pre` or `post`) one after the
+/// other from `root_dir`, with `COMPDB_OUTPUT` set to `output`.
+/// Commands are split like a POSIX shell would, but not run by one.
+pub fn run(stage : &str, commands : &[String], root_dir : &Path, output : &str) -> Result<(), String> {
+    for command in commands {
+        let words = shell::split(command);
+        let Some((program, arguments)) = words.split_first() else {
+            continue;
+        };
+        tracing::info!(stage, command, "running hook");
+        let status = Command::new(program).args(arguments).current_dir(root_dir).env("COMPDB_OUTPUT", output).status()
+            .map_err(|e| format!("hooks.{}: {}: {}", stage, program, e))?;
+        if !status.success() {
+            return Err(format!("hooks.{}: \"{}\" failed ({})", stage, command, status));
+        }
+    }
+    Ok(())
+}
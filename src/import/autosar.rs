@@ -0,0 +1,153 @@
+//! Turns an AUTOSAR project into workspaces: the software components and
+//! BSW modules its ARXML describes, each with the directory of its RTE
+//! contract header (`Rte_<Swc>.h`, `SchM_<Module>.h`).
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::conf::{IncludeConf, WorkSpaceConf};
+use crate::generate::slashed;
+
+use super::Fragment;
+
+const SWC_TYPES : [&str; 5] = [
+    "APPLICATION-SW-COMPONENT-TYPE", "SENSOR-ACTUATOR-SW-COMPONENT-TYPE", "COMPLEX-DEVICE-DRIVER-SW-COMPONENT-TYPE",
+    "ECU-ABSTRACTION-SW-COMPONENT-TYPE", "SERVICE-SW-COMPONENT-TYPE",
+];
+
+/// The headers every component includes through `Rte_<Swc>.h`.
+const COMMON_RTE_HEADERS : [&str; 3] = ["Rte.h", "Rte_Type.h", "Std_Types.h"];
+
+fn common_dirs_of(fragment : &Fragment) -> &[String] {
+    fragment.include_group.get("rte").and_then(|group| group.root_dir.as_deref()).unwrap_or_default()
+}
+
+/// Short names of the components (`Rte_` prefix) and BSW modules (`SchM_`)
+/// described in `arxml`.
+fn components(arxml : &Path) -> Result<Vec<(&'static str, String)>, String> {
+    let content = std::fs::read_to_string(arxml).map_err(|e| format!("{}: {}", arxml.display(), e))?;
+    let document = roxmltree::Document::parse(&content).map_err(|e| format!("{}: {}", arxml.display(), e))?;
+    Ok(document.descendants()
+        .filter_map(|node| {
+            let prefix = match node.tag_name().name() {
+                name if SWC_TYPES.contains(&name) => "Rte_",
+                "BSW-MODULE-DESCRIPTION" => "SchM_",
+                _ => return None,
+            };
+            let short_name = node.children().find(|n| n.has_tag_name("SHORT-NAME"))?.text()?.trim();
+            Some((prefix, short_name.to_string()))
+        })
+        .collect())
+}
+
+/// Workspaces for the components described by the `.arxml` files under
+/// `arxml_dir`, found as directories of the same name (in any case) under
+/// `src_dir`, with their contract headers looked up under `rte_dir`. Paths
+/// are relative to `root`, which is meant to be `common.root_dir`.
+pub fn import(root : &Path, arxml_dir : &Path, src_dir : &Path, rte_dir : &Path) -> Result<Fragment, String> {
+    let files = |dir : &Path| walkdir::WalkDir::new(root.join(dir)).sort_by_file_name().into_iter().filter_map(|e| e.ok()).collect::<Vec<_>>();
+    let relative = |path : &Path| slashed(path.strip_prefix(root).unwrap_or(path));
+
+    let mut described = Vec::new();
+    for arxml in files(arxml_dir).iter().filter(|e| e.path().extension().is_some_and(|x| x.eq_ignore_ascii_case("arxml"))) {
+        described.extend(components(arxml.path())?);
+    }
+    let mut source_dirs = HashMap::<String, PathBuf>::new();
+    // The RTE output has directories named after the components too.
+    for dir in files(src_dir).into_iter().filter(|e| e.file_type().is_dir() && !e.path().starts_with(root.join(rte_dir))) {
+        source_dirs.entry(dir.file_name().to_string_lossy().to_lowercase()).or_insert(dir.into_path());
+    }
+    let mut header_dirs = HashMap::<String, PathBuf>::new();
+    for header in files(rte_dir).into_iter().filter(|e| e.file_type().is_file()) {
+        header_dirs.entry(header.file_name().to_string_lossy().into_owned()).or_insert(header.path().parent().unwrap().to_path_buf());
+    }
+
+    let mut common_dirs = Vec::<String>::new();
+    for header in COMMON_RTE_HEADERS {
+        if let Some(dir) = header_dirs.get(header).map(|dir| relative(dir)).filter(|dir| !common_dirs.contains(dir)) {
+            common_dirs.push(dir);
+        }
+    }
+    let mut fragment = Fragment { include_group : BTreeMap::new(), workspace : Vec::new() };
+    if !common_dirs.is_empty() {
+        fragment.include_group.insert("rte".into(), IncludeConf { root_dir : Some(common_dirs), ..Default::default() });
+    }
+
+    described.sort_by(|a, b| a.1.cmp(&b.1));
+    described.dedup();
+    for (prefix, name) in described {
+        let Some(dir) = source_dirs.get(&name.to_lowercase()) else {
+            crate::diag::warn(&format!("{}: no directory named like it under {}", name, slashed(&root.join(src_dir))));
+            continue;
+        };
+        let path = relative(dir);
+        let contract = header_dirs.get(&format!("{}{}.h", prefix, name)).map(|header_dir| relative(header_dir));
+        if contract.is_none() {
+            crate::diag::warn(&format!("{}: no {}{}.h under {}", name, prefix, name, root.join(rte_dir).display()));
+        }
+        // A group rather than the workspace's include, whose roots would be
+        // relative to the workspace.
+        let mut include_groups = Vec::<String>::new();
+        if let Some(contract) = contract.filter(|dir| !common_dirs_of(&fragment).contains(dir)) {
+            let group = format!("contract-{}", name);
+            fragment.include_group.insert(group.clone(), IncludeConf { root_dir : Some(vec![contract]), ..Default::default() });
+            include_groups.push(group);
+        }
+        if !common_dirs_of(&fragment).is_empty() {
+            include_groups.push("rte".into());
+        }
+        fragment.workspace.push(WorkSpaceConf {
+            name : Some(name),
+            path,
+            include_groups : Some(include_groups).filter(|groups| !groups.is_empty()),
+            ..Default::default()
+        });
+    }
+    Ok(fragment)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::import;
+
+    const ARXML : &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<AUTOSAR xmlns="http://autosar.org/schema/r4.0">
+  <AR-PACKAGES>
+    <AR-PACKAGE>
+      <SHORT-NAME>Components</SHORT-NAME>
+      <ELEMENTS>
+        <APPLICATION-SW-COMPONENT-TYPE><SHORT-NAME>SeatHeating</SHORT-NAME></APPLICATION-SW-COMPONENT-TYPE>
+        <SENSOR-ACTUATOR-SW-COMPONENT-TYPE><SHORT-NAME>NoSources</SHORT-NAME></SENSOR-ACTUATOR-SW-COMPONENT-TYPE>
+        <BSW-MODULE-DESCRIPTION><SHORT-NAME>Can</SHORT-NAME></BSW-MODULE-DESCRIPTION>
+      </ELEMENTS>
+    </AR-PACKAGE>
+  </AR-PACKAGES>
+</AUTOSAR>
+"#;
+
+    #[test]
+    fn workspaces_with_their_contract_headers() {
+        let root = std::env::temp_dir().join(format!("compdb-autosar-{}", std::process::id()));
+        let files = [
+            "arxml/system.arxml", "src/app/seatheating/seat.c", "src/bsw/Can/Can.c",
+            "gen/rte/Rte.h", "gen/rte/Rte_Type.h", "gen/rte/contract/Rte_SeatHeating.h", "gen/rte/SchM_Can.h",
+        ];
+        for file in files {
+            std::fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            std::fs::write(root.join(file), if file.ends_with(".arxml") { ARXML } else { "" }).unwrap();
+        }
+
+        let fragment = import(&root, Path::new("arxml"), Path::new("src"), Path::new("gen/rte")).unwrap();
+        let groups : Vec<_> = fragment.include_group.iter().map(|(name, group)| (name.as_str(), group.root_dir.clone().unwrap())).collect();
+        assert_eq!(groups, [("contract-SeatHeating", vec!["gen/rte/contract".to_string()]), ("rte", vec!["gen/rte".to_string()])]);
+        let workspaces : Vec<_> = fragment.workspace.iter().map(|w| (w.name.as_deref().unwrap(), w.path.as_str(), w.include_groups.clone().unwrap())).collect();
+        // SchM_Can.h is with the common headers; NoSources has no directory.
+        assert_eq!(workspaces, [
+            ("Can", "src/bsw/Can", vec!["rte".to_string()]),
+            ("SeatHeating", "src/app/seatheating", vec!["contract-SeatHeating".to_string(), "rte".to_string()]),
+        ]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
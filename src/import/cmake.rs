@@ -0,0 +1,253 @@
+//! Reads the CMake File API reply (codemodel v2, toolchains v1) of a
+//! configured build directory.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::compdb::CompilationEntry;
+use crate::generate::slashed;
+use crate::shell;
+
+#[derive(Deserialize)]
+struct Index {
+    objects : Vec<ReplyObject>,
+}
+
+#[derive(Deserialize)]
+struct ReplyObject {
+    kind : String,
+    #[serde(rename = "jsonFile")]
+    json_file : String,
+}
+
+#[derive(Deserialize)]
+struct Codemodel {
+    paths : CodemodelPaths,
+    configurations : Vec<Configuration>,
+}
+
+#[derive(Deserialize)]
+struct CodemodelPaths {
+    source : String,
+    build : String,
+}
+
+#[derive(Deserialize)]
+struct Configuration {
+    name : String,
+    targets : Vec<TargetRef>,
+}
+
+#[derive(Deserialize)]
+struct TargetRef {
+    #[serde(rename = "jsonFile")]
+    json_file : String,
+}
+
+#[derive(Deserialize)]
+struct Target {
+    paths : TargetPaths,
+    #[serde(default)]
+    sources : Vec<Source>,
+    #[serde(default, rename = "compileGroups")]
+    compile_groups : Vec<CompileGroup>,
+}
+
+#[derive(Deserialize)]
+struct TargetPaths {
+    build : String,
+}
+
+#[derive(Deserialize)]
+struct Source {
+    path : String,
+    #[serde(rename = "compileGroupIndex")]
+    compile_group_index : Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct CompileGroup {
+    language : String,
+    #[serde(default, rename = "compileCommandFragments")]
+    compile_command_fragments : Vec<Fragment>,
+    #[serde(default)]
+    includes : Vec<Include>,
+    #[serde(default)]
+    defines : Vec<Define>,
+}
+
+#[derive(Deserialize)]
+struct Fragment {
+    fragment : String,
+}
+
+#[derive(Deserialize)]
+struct Include {
+    path : String,
+    #[serde(default, rename = "isSystem")]
+    is_system : bool,
+}
+
+#[derive(Deserialize)]
+struct Define {
+    define : String,
+}
+
+#[derive(Deserialize)]
+struct Toolchains {
+    toolchains : Vec<Toolchain>,
+}
+
+#[derive(Deserialize)]
+struct Toolchain {
+    language : String,
+    compiler : ToolchainCompiler,
+}
+
+#[derive(Deserialize)]
+struct ToolchainCompiler {
+    path : Option<String>,
+}
+
+fn read_json<T : for<'de> Deserialize<'de>>(path : &Path) -> Result<T, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Writes the File API queries this importer needs, so the next CMake
+/// configure run produces a reply.
+fn write_queries(build_dir : &Path) -> Result<(), String> {
+    let query_dir = build_dir.join(".cmake/api/v1/query");
+    std::fs::create_dir_all(&query_dir).map_err(|e| format!("{}: {}", query_dir.display(), e))?;
+    for query in ["codemodel-v2", "toolchains-v1"] {
+        std::fs::write(query_dir.join(query), "").map_err(|e| format!("{}: {}", query_dir.display(), e))?;
+    }
+    Ok(())
+}
+
+fn latest_index(reply_dir : &Path) -> Option<PathBuf> {
+    let mut indices : Vec<PathBuf> = std::fs::read_dir(reply_dir).ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.file_name().unwrap().to_str().is_some_and(|n| n.starts_with("index-") && n.ends_with(".json")))
+        .collect();
+    indices.sort();
+    indices.pop()
+}
+
+fn default_compiler(language : &str) -> &'static str {
+    if language == "CXX" { "c++" } else { "cc" }
+}
+
+/// Entries for every source of every target in `configuration` (the first
+/// configuration if `None`).
+pub fn import(build_dir : &Path, configuration : Option<&str>) -> Result<Vec<CompilationEntry>, String> {
+    let reply_dir = build_dir.join(".cmake/api/v1/reply");
+    let Some(index_path) = latest_index(&reply_dir) else {
+        write_queries(build_dir)?;
+        return Err(format!("{}: no CMake File API reply; queries have been written, re-run CMake on this build directory", build_dir.display()));
+    };
+    let index : Index = read_json(&index_path)?;
+
+    let object = |kind : &str| index.objects.iter().find(|o| o.kind == kind).map(|o| reply_dir.join(&o.json_file));
+    let Some(codemodel_path) = object("codemodel") else {
+        write_queries(build_dir)?;
+        return Err(format!("{}: the CMake File API reply has no codemodel; re-run CMake on this build directory", build_dir.display()));
+    };
+    let codemodel : Codemodel = read_json(&codemodel_path)?;
+    let toolchains : Vec<Toolchain> = match object("toolchains") {
+        Some(path) => read_json::<Toolchains>(&path)?.toolchains,
+        None => Vec::new(),
+    };
+    let compiler_of = |language : &str| -> String {
+        toolchains.iter().find(|t| t.language == language).and_then(|t| t.compiler.path.clone()).unwrap_or_else(|| default_compiler(language).into())
+    };
+
+    let configuration = match configuration {
+        Some(name) => codemodel.configurations.iter().find(|c| c.name == name).ok_or_else(|| format!("CMake configuration \"{}\" not found", name))?,
+        None => codemodel.configurations.first().ok_or("CMake codemodel has no configuration")?,
+    };
+
+    let source_root = PathBuf::from(&codemodel.paths.source);
+    let build_root = PathBuf::from(&codemodel.paths.build);
+    let mut entries = Vec::<CompilationEntry>::new();
+    for target_ref in &configuration.targets {
+        let target : Target = read_json(&reply_dir.join(&target_ref.json_file))?;
+        for source in &target.sources {
+            let Some(group) = source.compile_group_index.and_then(|i| target.compile_groups.get(i)) else { continue };
+
+            let mut arguments = vec![compiler_of(&group.language)];
+            arguments.extend(group.defines.iter().map(|d| format!("-D{}", d.define)));
+            for include in &group.includes {
+                if include.is_system {
+                    arguments.extend(["-isystem".into(), include.path.clone()]);
+                } else {
+                    arguments.push(format!("-I{}", include.path));
+                }
+            }
+            arguments.extend(group.compile_command_fragments.iter().flat_map(|f| shell::split(&f.fragment)));
+
+            let file = slashed(&source_root.join(&source.path));
+            arguments.extend(["-c".into(), file.clone()]);
+            entries.push(CompilationEntry {
+                directory : slashed(&build_root.join(&target.paths.build)),
+                arguments,
+                file,
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::import;
+
+    #[test]
+    fn entries_from_a_codemodel_reply() {
+        let build_dir = std::env::temp_dir().join(format!("compdb-cmake-{}", std::process::id()));
+        let reply_dir = build_dir.join(".cmake/api/v1/reply");
+        assert!(import(&build_dir, None).unwrap_err().contains("queries have been written"));
+        assert!(build_dir.join(".cmake/api/v1/query/codemodel-v2").exists());
+
+        std::fs::create_dir_all(&reply_dir).unwrap();
+        let write = |name : &str, json : serde_json::Value| std::fs::write(reply_dir.join(name), json.to_string()).unwrap();
+        write("index-2024-01-01T00-00-00-0000.json", serde_json::json!({ "objects" : [
+            { "kind" : "codemodel", "jsonFile" : "codemodel-v2-1.json" },
+            { "kind" : "toolchains", "jsonFile" : "toolchains-v1-1.json" },
+        ] }));
+        write("codemodel-v2-1.json", serde_json::json!({
+            "paths" : { "source" : "/src", "build" : "/src/build" },
+            "configurations" : [{ "name" : "Debug", "targets" : [{ "jsonFile" : "target-app.json" }] }],
+        }));
+        write("toolchains-v1-1.json", serde_json::json!({ "toolchains" : [{ "language" : "C", "compiler" : { "path" : "/usr/bin/arm-none-eabi-gcc" } }] }));
+        write("target-app.json", serde_json::json!({
+            "paths" : { "build" : "app" },
+            "sources" : [{ "path" : "app/main.c", "compileGroupIndex" : 0 }, { "path" : "app/main.cpp", "compileGroupIndex" : 1 }, { "path" : "app/app.h" }],
+            "compileGroups" : [
+                {
+                    "language" : "C",
+                    "compileCommandFragments" : [{ "fragment" : "-O2 -mcpu=cortex-m4" }, { "fragment" : "'-DNAME=\"x y\"'" }],
+                    "includes" : [{ "path" : "/src/app/inc" }, { "path" : "/opt/sdk/include", "isSystem" : true }],
+                    "defines" : [{ "define" : "USE_HAL=1" }],
+                },
+                { "language" : "CXX" },
+            ],
+        }));
+
+        let entries = import(&build_dir, Some("Debug")).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].directory, "/src/build/app");
+        assert_eq!(entries[0].file, "/src/app/main.c");
+        assert_eq!(entries[0].arguments, [
+            "/usr/bin/arm-none-eabi-gcc", "-DUSE_HAL=1", "-I/src/app/inc", "-isystem", "/opt/sdk/include", "-O2", "-mcpu=cortex-m4", "-DNAME=\"x y\"", "-c", "/src/app/main.c",
+        ]);
+        // No toolchain for C++.
+        assert_eq!(entries[1].arguments, ["c++", "-c", "/src/app/main.cpp"]);
+        assert!(import(&build_dir, Some("Release")).unwrap_err().contains("\"Release\" not found"));
+        std::fs::remove_dir_all(&build_dir).unwrap();
+    }
+}
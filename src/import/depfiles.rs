@@ -0,0 +1,131 @@
+//! Derives entries from the GCC-style `.d` files (`-MD`/`-MMD`) a previous
+//! build left behind: each source gets only the include directories its
+//! headers were actually found through.
+
+use std::path::{Path, PathBuf};
+
+use crate::compdb::CompilationEntry;
+use crate::generate::slashed;
+use crate::includes::scan_includes;
+
+/// The words of a depfile rule, with `\ ` and `$$` unescaped.
+fn words(text : &str) -> Vec<String> {
+    let mut words = Vec::<String>::new();
+    let mut word = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|n| *n == ' ' || *n == '#') => word.push(chars.next().unwrap()),
+            '$' if chars.peek() == Some(&'$') => word.push(chars.next().unwrap()),
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// The prerequisites of the first rule of `depfile`: the source, then the
+/// headers it read. The phony rules `-MP` adds after it are ignored.
+fn prerequisites(depfile : &str) -> Vec<String> {
+    let joined = depfile.replace("\\\r\n", " ").replace("\\\n", " ");
+    let Some(rule) = joined.lines().find(|line| !line.trim().is_empty()) else {
+        return Vec::new();
+    };
+    // `: ` rather than `:`, so that drive letters stay part of the path.
+    match rule.split_once(": ").or_else(|| rule.strip_suffix(':').map(|target| (target, ""))) {
+        Some((_, prerequisites)) => words(prerequisites),
+        None => Vec::new(),
+    }
+}
+
+/// The entry of the source a depfile describes, its paths relative to
+/// `directory` as the build saw them. Headers outside `directory` are
+/// taken for the compiler's own.
+fn entry(directory : &Path, prerequisites : &[String], compiler : &str) -> Option<CompilationEntry> {
+    let (source, headers) = prerequisites.split_first()?;
+    let beside_source = Path::new(source).parent().unwrap_or(Path::new(""));
+    let headers : Vec<&String> = headers.iter().filter(|h| Path::new(h).is_relative() || Path::new(h).starts_with(directory)).collect();
+    let mut names : Vec<String> = std::iter::once(source).chain(headers.iter().copied())
+        .flat_map(|file| scan_includes(&directory.join(file)))
+        .map(|(name, _)| name)
+        .collect();
+    // The longest name wins: `sub/x.h` tells more about the directory than `x.h`.
+    names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+
+    let mut include_dirs = Vec::<String>::new();
+    for header in headers {
+        let header = slashed(Path::new(header));
+        let dir = match names.iter().find(|name| header == **name || header.ends_with(&format!("/{}", name))) {
+            Some(name) => header[..header.len() - name.len()].trim_end_matches('/').to_string(),
+            None => slashed(Path::new(&header).parent().unwrap_or(Path::new(""))),
+        };
+        let dir = if dir.is_empty() { ".".to_string() } else { dir };
+        if Path::new(&dir) != beside_source && !include_dirs.contains(&dir) {
+            include_dirs.push(dir);
+        }
+    }
+
+    let mut arguments = vec![compiler.to_string()];
+    arguments.extend(include_dirs.into_iter().map(|dir| format!("-I{}", dir)));
+    arguments.extend(["-c".into(), source.clone()]);
+    Some(CompilationEntry { directory : slashed(directory), arguments, file : source.clone(), ..Default::default() })
+}
+
+/// Entries for the depfiles matching `pattern`, whose paths are relative to
+/// the build directory `directory`.
+pub fn import(pattern : &str, directory : &Path, compiler : &str) -> Result<Vec<CompilationEntry>, String> {
+    let directory = std::path::absolute(directory).map_err(|e| format!("{}: {}", directory.display(), e))?;
+    let depfiles : Vec<PathBuf> = glob::glob(pattern).map_err(|e| format!("{}: {}", pattern, e))?.filter_map(|p| p.ok()).collect();
+    if depfiles.is_empty() {
+        return Err(format!("no depfile matches {}", pattern));
+    }
+    let mut entries = Vec::new();
+    for depfile in depfiles {
+        let content = std::fs::read_to_string(&depfile).map_err(|e| format!("{}: {}", depfile.display(), e))?;
+        entries.extend(entry(&directory, &prerequisites(&content), compiler));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{import, prerequisites};
+
+    #[test]
+    fn prerequisites_of_the_first_rule() {
+        let depfile = "C:/build/a.o: C:/src/a.c C:/src/my\\ dir/a.h \\\n  $$x.h\n\nC:/src/my\\ dir/a.h:\n";
+        assert_eq!(prerequisites(depfile), ["C:/src/a.c", "C:/src/my dir/a.h", "$x.h"]);
+        assert!(prerequisites("").is_empty());
+    }
+
+    #[test]
+    fn include_dirs_the_headers_were_found_through() {
+        let dir = std::env::temp_dir().join(format!("compdb-depfiles-{}", std::process::id()));
+        let files = [
+            ("src/main.c", "#include \"config.h\"\n#include \"drivers/uart.h\"\n#include \"local.h\"\n#include <stdio.h>\n"),
+            ("src/local.h", ""),
+            ("inc/config.h", ""),
+            ("inc/drivers/uart.h", ""),
+            ("build/main.d", "build/main.o: src/main.c inc/config.h \\\n inc/drivers/uart.h src/local.h \\\n /usr/include/stdio.h\n\ninc/config.h:\n"),
+        ];
+        for (file, content) in files {
+            std::fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(file), content).unwrap();
+        }
+
+        let entries = import(&format!("{}/build/*.d", dir.display()), &dir, "gcc").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].file, "src/main.c");
+        // local.h sits beside the source and stdio.h belongs to the compiler.
+        assert_eq!(entries[0].arguments, ["gcc", "-Iinc", "-c", "src/main.c"]);
+        assert!(import(&format!("{}/none/*.d", dir.display()), &dir, "gcc").unwrap_err().starts_with("no depfile matches"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
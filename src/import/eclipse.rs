@@ -0,0 +1,312 @@
+//! Reads Eclipse CDT managed-build projects (`.cproject` next to `.project`):
+//! include paths, symbols and source folders of one build configuration.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::compdb::CompilationEntry;
+use crate::conf::{FileOptionConf, IncludeConf, OptionConf, TargetConf, WorkSpaceConf};
+use crate::generate::slashed;
+
+use super::Fragment;
+
+const C_EXTENSIONS : [&str; 1] = ["c"];
+const CPP_EXTENSIONS : [&str; 7] = ["cc", "cpp", "cxx", "C", "CC", "CPP", "CXX"];
+
+/// Compiler options of one language collected from a configuration's tools.
+#[derive(Default)]
+pub struct LanguageFlags {
+    pub c : Vec<String>,
+    pub cpp : Vec<String>,
+}
+
+pub struct CdtConfiguration {
+    pub name : String,
+    pub flags : LanguageFlags,
+    /// `(source folder, excluded sub-paths)` relative to the project directory
+    pub source_entries : Vec<(String, Vec<String>)>,
+    /// Single-valued tool options as `(superClass, value)`, for vendor
+    /// settings this reader does not interpret itself.
+    pub options : Vec<(String, String)>,
+}
+
+pub struct CdtProject {
+    pub name : String,
+    pub dir : PathBuf,
+    pub configurations : Vec<CdtConfiguration>,
+}
+
+fn project_name(project_dir : &Path) -> String {
+    let fallback = || project_dir.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+    let Ok(content) = std::fs::read_to_string(project_dir.join(".project")) else { return fallback() };
+    let Ok(document) = roxmltree::Document::parse(&content) else { return fallback() };
+    document.root_element().children().find(|n| n.has_tag_name("name")).and_then(|n| n.text()).map(|n| n.trim().to_string()).unwrap_or_else(fallback)
+}
+
+/// Expands the Eclipse variables that commonly appear in include paths.
+fn expand_variables(value : &str, project : &str, project_dir : &Path) -> String {
+    let project_dir_str = slashed(project_dir);
+    let value = value.trim().trim_matches('"')
+        .replace("${ProjName}", project)
+        .replace("${ProjDirPath}", &project_dir_str);
+    let workspace_prefix = format!("${{workspace_loc:/{}", project);
+    if let Some(rest) = value.strip_prefix(&workspace_prefix) {
+        format!("{}{}", project_dir_str, rest.trim_end_matches('}'))
+    } else {
+        value
+    }
+}
+
+/// Makes a relative include path absolute. Managed builds run in a
+/// directory named after the configuration, which is what relative paths
+/// like `../Inc` are relative to.
+fn resolve_include(path : String, build_dir : &Path) -> String {
+    if Path::new(&path).is_absolute() {
+        return path;
+    }
+    let mut resolved = PathBuf::new();
+    for component in build_dir.join(&path).components() {
+        match component {
+            std::path::Component::ParentDir => { resolved.pop(); }
+            std::path::Component::CurDir => {}
+            other => resolved.push(other),
+        }
+    }
+    slashed(&resolved)
+}
+
+fn is_cpp_tool(tool : roxmltree::Node) -> bool {
+    let id = format!("{} {}", tool.attribute("superClass").unwrap_or_default(), tool.attribute("id").unwrap_or_default()).to_lowercase();
+    id.contains("cpp.compiler") || id.contains("c++.compiler") || id.contains("gpp.compiler")
+}
+
+fn is_compiler_tool(tool : roxmltree::Node) -> bool {
+    let id = format!("{} {}", tool.attribute("superClass").unwrap_or_default(), tool.attribute("id").unwrap_or_default()).to_lowercase();
+    id.contains("compiler")
+}
+
+pub fn read_project(project_dir : &Path) -> Result<CdtProject, String> {
+    let cproject_path = project_dir.join(".cproject");
+    let content = std::fs::read_to_string(&cproject_path).map_err(|e| format!("{}: {}", cproject_path.display(), e))?;
+    let document = roxmltree::Document::parse(&content).map_err(|e| format!("{}: {}", cproject_path.display(), e))?;
+    let project_dir = std::path::absolute(project_dir).map_err(|e| e.to_string())?;
+    let name = project_name(&project_dir);
+
+    let mut configurations = Vec::<CdtConfiguration>::new();
+    for configuration in document.descendants().filter(|n| n.has_tag_name("configuration") && n.attribute("name").is_some()) {
+        let build_dir = project_dir.join(configuration.attribute("name").unwrap());
+        let mut flags = LanguageFlags::default();
+        let options : Vec<(String, String)> = configuration.descendants()
+            .filter(|n| n.has_tag_name("option"))
+            .filter_map(|n| Some((n.attribute("superClass")?.to_string(), n.attribute("value")?.to_string())))
+            .collect();
+        for tool in configuration.descendants().filter(|n| n.has_tag_name("tool")) {
+            let compiler = is_compiler_tool(tool);
+            let target = if is_cpp_tool(tool) { &mut flags.cpp } else { &mut flags.c };
+            for option in tool.children().filter(|n| n.has_tag_name("option")) {
+                if !compiler {
+                    continue;
+                }
+                let values = option.children().filter(|n| n.has_tag_name("listOptionValue")).filter_map(|n| n.attribute("value")).map(|v| expand_variables(v, &name, &project_dir));
+                match option.attribute("valueType") {
+                    Some("includePath") => target.extend(values.map(|v| format!("-I{}", resolve_include(v, &build_dir)))),
+                    Some("definedSymbols") => target.extend(values.map(|v| format!("-D{}", v))),
+                    Some("includeFiles") => target.extend(values.flat_map(|v| ["-include".to_string(), v])),
+                    _ => {}
+                }
+            }
+        }
+
+        let source_entries = configuration.descendants()
+            .filter(|n| n.has_tag_name("entry") && n.attribute("kind") == Some("sourcePath"))
+            .map(|n| (
+                n.attribute("name").unwrap_or_default().to_string(),
+                n.attribute("excluding").map(|e| e.split('|').map(String::from).collect()).unwrap_or_default(),
+            ))
+            .collect();
+
+        configurations.push(CdtConfiguration {
+            name : configuration.attribute("name").unwrap().to_string(),
+            flags,
+            source_entries,
+            options,
+        });
+    }
+
+    Ok(CdtProject { name, dir : project_dir, configurations })
+}
+
+impl CdtProject {
+    pub fn configuration(&self, name : Option<&str>) -> Result<&CdtConfiguration, String> {
+        match name {
+            Some(name) => self.configurations.iter().find(|c| c.name == name).ok_or_else(|| format!("{}: no build configuration \"{}\"", self.dir.display(), name)),
+            None => self.configurations.first().ok_or_else(|| format!("{}: no build configuration", self.dir.display())),
+        }
+    }
+
+    /// Sources under the configuration's source folders, relative to the
+    /// project directory.
+    pub fn sources(&self, configuration : &CdtConfiguration) -> Vec<String> {
+        let source_entries = if configuration.source_entries.is_empty() {
+            vec![(String::new(), Vec::new())]
+        } else {
+            configuration.source_entries.clone()
+        };
+
+        let mut sources = Vec::<String>::new();
+        for (folder, excluding) in source_entries {
+            let folder_path = self.dir.join(&folder);
+            for entry in walkdir::WalkDir::new(&folder_path).sort_by_file_name().into_iter().filter_map(|e| e.ok()).filter(|e| e.file_type().is_file()) {
+                let relative_to_folder = slashed(entry.path().strip_prefix(&folder_path).unwrap());
+                if excluding.iter().any(|x| relative_to_folder == *x || relative_to_folder.starts_with(&format!("{}/", x))) {
+                    continue;
+                }
+                let extension = entry.path().extension().and_then(|e| e.to_str()).unwrap_or_default();
+                if C_EXTENSIONS.contains(&extension) || CPP_EXTENSIONS.contains(&extension) {
+                    sources.push(slashed(entry.path().strip_prefix(&self.dir).unwrap()));
+                }
+            }
+        }
+        sources
+    }
+
+    /// Entries for `configuration`, compiling C with `c_compiler` and C++
+    /// with `cpp_compiler`; `target_flags` go right after the compiler.
+    pub fn entries(&self, configuration : &CdtConfiguration, c_compiler : &str, cpp_compiler : &str, target_flags : &[String]) -> Vec<CompilationEntry> {
+        self.sources(configuration).into_iter().map(|file| {
+            let is_cpp = CPP_EXTENSIONS.contains(&Path::new(&file).extension().and_then(|e| e.to_str()).unwrap_or_default());
+            let (compiler, flags) = if is_cpp { (cpp_compiler, &configuration.flags.cpp) } else { (c_compiler, &configuration.flags.c) };
+            let mut arguments = vec![compiler.to_string()];
+            arguments.extend(target_flags.iter().cloned());
+            arguments.extend(flags.iter().cloned());
+            arguments.extend(["-c".into(), file.clone()]);
+            CompilationEntry {
+                directory : slashed(&self.dir),
+                arguments,
+                file,
+                ..Default::default()
+            }
+        }).collect()
+    }
+
+    /// `configuration` as configuration keys with paths relative to `root`,
+    /// the directory the configuration's `common.root_dir` names: a
+    /// workspace per source folder, its exclusions as `target.ignore_pattern`,
+    /// and an include group named after the project with the include paths
+    /// both languages get, each emitted alone. The other options both
+    /// languages get are the workspaces' `option.arg`; the ones of one
+    /// language only are a `file_option` for its sources.
+    pub fn fragment(&self, configuration : &CdtConfiguration, root : &Path) -> Result<Fragment, String> {
+        let root = std::path::absolute(root).map_err(|e| e.to_string())?;
+        let relative = |path : &Path| match slashed(path.strip_prefix(&root).unwrap_or(path)) {
+            path if path.is_empty() => ".".to_string(),
+            path => path,
+        };
+        let flags = &configuration.flags;
+        let shared : Vec<String> = flags.c.iter().filter(|flag| flags.cpp.contains(flag)).cloned().collect();
+        let include_roots : Vec<String> = shared.iter().filter_map(|flag| flag.strip_prefix("-I")).map(|dir| relative(Path::new(dir))).collect();
+        let options : Vec<String> = shared.iter().filter(|flag| !flag.starts_with("-I")).cloned().collect();
+        let mut file_options = Vec::new();
+        for (own, extensions) in [(&flags.c, &C_EXTENSIONS[..]), (&flags.cpp, &CPP_EXTENSIONS[..])] {
+            let arg : Vec<String> = own.iter().filter(|flag| !shared.contains(flag)).cloned().collect();
+            if !arg.is_empty() {
+                file_options.push(FileOptionConf { pattern : format!("[.]({})$", extensions.join("|")), arg : Some(arg), remove_arg : None });
+            }
+        }
+
+        let mut fragment = Fragment { include_group : BTreeMap::new(), workspace : Vec::new() };
+        if !include_roots.is_empty() {
+            fragment.include_group.insert(self.name.clone(), IncludeConf { root_dir : Some(include_roots), max_depth : Some(0), ..Default::default() });
+        }
+        let source_entries = match configuration.source_entries.is_empty() {
+            true => vec![(String::new(), Vec::new())],
+            false => configuration.source_entries.clone(),
+        };
+        let single = source_entries.len() == 1;
+        for (folder, excluding) in source_entries {
+            let path = relative(&self.dir.join(&folder));
+            let ignore_pattern : Vec<String> = excluding.iter().map(|excluded| format!("^{}(/|$)", regex::escape(format!("{}/{}", path, excluded).trim_start_matches("./")))).collect();
+            fragment.workspace.push(WorkSpaceConf {
+                name : Some(if single || folder.is_empty() { self.name.clone() } else { format!("{}:{}", self.name, folder) }),
+                path,
+                include_groups : (!fragment.include_group.is_empty()).then(|| vec![self.name.clone()]),
+                target : (!ignore_pattern.is_empty()).then(|| TargetConf { ignore_pattern : Some(ignore_pattern), ..Default::default() }),
+                option : (!options.is_empty()).then(|| OptionConf { arg : Some(options.clone()), ..Default::default() }),
+                file_option : (!file_options.is_empty()).then(|| file_options.clone()),
+                ..Default::default()
+            });
+        }
+        Ok(fragment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_project;
+
+    const CPROJECT : &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<cproject storage_type_id="org.eclipse.cdt.core.XmlProjectDescriptionStorage">
+  <storageModule moduleId="org.eclipse.cdt.core.settings">
+    <cconfiguration id="cdt.managedbuild.config.gnu.exe.debug.1">
+      <storageModule moduleId="cdtBuildSystem" version="4.0.0">
+        <configuration name="Debug" id="cdt.managedbuild.config.gnu.exe.debug.1">
+          <folderInfo id="cdt.managedbuild.config.gnu.exe.debug.1." name="/">
+            <toolChain id="cdt.managedbuild.toolchain.gnu.exe.debug.1">
+              <tool id="cdt.managedbuild.tool.gnu.c.compiler.exe.debug.1" superClass="cdt.managedbuild.tool.gnu.c.compiler.exe.debug">
+                <option id="c.include.1" superClass="gnu.c.compiler.option.include.paths" valueType="includePath">
+                  <listOptionValue builtIn="false" value="../Inc"/>
+                  <listOptionValue builtIn="false" value="&quot;${workspace_loc:/${ProjName}/Drivers}&quot;"/>
+                </option>
+                <option id="c.defs.1" superClass="gnu.c.compiler.option.preprocessor.def.symbols" valueType="definedSymbols">
+                  <listOptionValue builtIn="false" value="DEBUG"/>
+                </option>
+              </tool>
+              <tool id="cdt.managedbuild.tool.gnu.cpp.compiler.exe.debug.1" superClass="cdt.managedbuild.tool.gnu.cpp.compiler.exe.debug">
+                <option id="cpp.include.1" superClass="gnu.cpp.compiler.option.include.paths" valueType="includePath">
+                  <listOptionValue builtIn="false" value="../Inc"/>
+                </option>
+              </tool>
+              <tool id="cdt.managedbuild.tool.gnu.c.linker.exe.debug.1" superClass="cdt.managedbuild.tool.gnu.c.linker.exe.debug">
+                <option id="link.paths.1" superClass="gnu.c.link.option.paths" valueType="libPaths">
+                  <listOptionValue builtIn="false" value="../Lib"/>
+                </option>
+              </tool>
+            </toolChain>
+          </folderInfo>
+          <sourceEntries>
+            <entry flags="VALUE_WORKSPACE_PATH|RESOLVED" kind="sourcePath" name="Src" excluding="old"/>
+          </sourceEntries>
+        </configuration>
+      </storageModule>
+    </cconfiguration>
+  </storageModule>
+</cproject>
+"#;
+
+    #[test]
+    fn flags_and_sources_of_a_managed_build_configuration() {
+        let dir = std::env::temp_dir().join(format!("compdb-eclipse-{}", std::process::id()));
+        for file in ["Src/main.c", "Src/app.cpp", "Src/old/legacy.c", "Src/notes.txt", "Other/other.c"] {
+            std::fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        std::fs::write(dir.join(".project"), "<projectDescription><name>blinky</name></projectDescription>").unwrap();
+        std::fs::write(dir.join(".cproject"), CPROJECT).unwrap();
+        let project_dir = super::slashed(&std::path::absolute(&dir).unwrap());
+
+        let project = read_project(&dir).unwrap();
+        assert_eq!(project.name, "blinky");
+        let configuration = project.configuration(Some("Debug")).unwrap();
+        assert_eq!(configuration.flags.c, [format!("-I{}/Inc", project_dir), format!("-I{}/Drivers", project_dir), "-DDEBUG".to_string()]);
+        assert_eq!(configuration.flags.cpp, [format!("-I{}/Inc", project_dir)]);
+
+        let entries = project.entries(configuration, "arm-none-eabi-gcc", "arm-none-eabi-g++", &["-mthumb".into()]);
+        let arguments : Vec<_> = entries.iter().map(|entry| entry.arguments.join(" ")).collect();
+        assert_eq!(arguments, [
+            format!("arm-none-eabi-g++ -mthumb -I{}/Inc -c Src/app.cpp", project_dir),
+            format!("arm-none-eabi-gcc -mthumb -I{0}/Inc -I{0}/Drivers -DDEBUG -c Src/main.c", project_dir),
+        ]);
+        assert!(project.configuration(Some("Release")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
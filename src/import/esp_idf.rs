@@ -0,0 +1,83 @@
+//! ESP-IDF builds (`idf.py build`): the build directory's database plus the
+//! component include paths and sdkconfig macros for application sources.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::generate::slashed;
+
+use super::BuildImport;
+
+#[derive(Deserialize)]
+struct ProjectDescription {
+    #[serde(default)]
+    build_component_info : BTreeMap<String, ComponentInfo>,
+}
+
+#[derive(Deserialize)]
+struct ComponentInfo {
+    dir : String,
+    #[serde(default)]
+    include_dirs : Vec<String>,
+}
+
+pub fn import(build_dir : &Path) -> Result<BuildImport, String> {
+    let build_dir = std::path::absolute(build_dir).map_err(|e| e.to_string())?;
+    let description_path = build_dir.join("project_description.json");
+    let content = std::fs::read_to_string(&description_path).map_err(|e| format!("{}: {}; is this an ESP-IDF build directory?", description_path.display(), e))?;
+    let description : ProjectDescription = serde_json::from_str(&content).map_err(|e| format!("{}: {}", description_path.display(), e))?;
+
+    let config_dir = build_dir.join("config");
+    let mut application_flags = vec![format!("-I{}", slashed(&config_dir))];
+    let sdkconfig = config_dir.join("sdkconfig.h");
+    if sdkconfig.exists() {
+        application_flags.extend(["-imacros".into(), slashed(&sdkconfig)]);
+    } else {
+        crate::diag::warn(&format!("{} does not exist yet; sdkconfig macros are missing until the build has run", sdkconfig.display()));
+    }
+    for component in description.build_component_info.values() {
+        for include_dir in &component.include_dirs {
+            let include_dir : PathBuf = if include_dir == "." { PathBuf::from(&component.dir) } else { Path::new(&component.dir).join(include_dir) };
+            application_flags.push(format!("-I{}", slashed(&include_dir)));
+        }
+    }
+
+    Ok(BuildImport { entries : super::read_build_db(&build_dir)?, application_flags })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::import;
+
+    #[test]
+    fn component_includes_and_sdkconfig() {
+        let build_dir = std::env::temp_dir().join(format!("compdb-esp-idf-{}", std::process::id()));
+        std::fs::create_dir_all(build_dir.join("config")).unwrap();
+        std::fs::write(build_dir.join("config/sdkconfig.h"), "#define CONFIG_IDF_TARGET \"esp32\"\n").unwrap();
+        std::fs::write(build_dir.join("project_description.json"), serde_json::json!({
+            "project_name" : "app",
+            "build_component_info" : {
+                "main" : { "dir" : "/proj/main", "include_dirs" : ["."] },
+                "driver" : { "dir" : "/idf/components/driver", "include_dirs" : ["include", "deprecated"] },
+                "soc" : { "dir" : "/idf/components/soc" },
+            },
+        }).to_string()).unwrap();
+        let config = super::slashed(&build_dir.join("config"));
+
+        let imported = import(&build_dir).unwrap();
+        assert!(imported.entries.is_empty());
+        // The components come in name order.
+        assert_eq!(imported.application_flags, [
+            format!("-I{}", config),
+            "-imacros".into(),
+            format!("{}/sdkconfig.h", config),
+            "-I/idf/components/driver/include".into(),
+            "-I/idf/components/driver/deprecated".into(),
+            "-I/proj/main".into(),
+        ]);
+        assert!(import(&build_dir.join("config")).is_err_and(|e| e.contains("is this an ESP-IDF build directory?")));
+        std::fs::remove_dir_all(&build_dir).unwrap();
+    }
+}
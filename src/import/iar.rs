@@ -0,0 +1,185 @@
+//! Reads IAR Embedded Workbench `.ewp` projects and translates the ICC
+//! settings of one configuration into clang-style arguments.
+
+use std::path::{Path, PathBuf};
+
+use crate::compdb::CompilationEntry;
+use crate::generate::slashed;
+
+fn child<'a, 'input>(node : roxmltree::Node<'a, 'input>, name : &str) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn child_text<'a>(node : roxmltree::Node<'a, '_>, name : &str) -> Option<&'a str> {
+    child(node, name).and_then(|n| n.text()).map(|t| t.trim())
+}
+
+/// The `state` values of the option called `name` in a settings block.
+fn option_states<'a>(settings : roxmltree::Node<'a, '_>, name : &str) -> Vec<&'a str> {
+    child(settings, "data").into_iter()
+        .flat_map(|data| data.children().filter(|n| n.has_tag_name("option")))
+        .filter(|option| child_text(*option, "name") == Some(name))
+        .flat_map(|option| option.children().filter(|n| n.has_tag_name("state")).filter_map(|n| n.text()).map(|t| t.trim()))
+        .filter(|state| !state.is_empty())
+        .collect()
+}
+
+struct Variables {
+    proj_dir : String,
+    toolkit_dir : Option<String>,
+}
+
+impl Variables {
+    fn expand(&self, value : &str) -> String {
+        let mut expanded = value.replace("$PROJ_DIR$", &self.proj_dir);
+        if let Some(toolkit_dir) = &self.toolkit_dir {
+            expanded = expanded.replace("$TOOLKIT_DIR$", toolkit_dir);
+        }
+        expanded.replace("\\", "/")
+    }
+}
+
+fn is_excluded(node : roxmltree::Node, configuration : &str) -> bool {
+    child(node, "excluded").is_some_and(|excluded| excluded.children().any(|c| c.has_tag_name("configuration") && c.text().map(|t| t.trim()) == Some(configuration)))
+}
+
+fn collect_files(node : roxmltree::Node, configuration : &str, variables : &Variables, files : &mut Vec<String>) {
+    for item in node.children() {
+        if item.has_tag_name("group") && !is_excluded(item, configuration) {
+            collect_files(item, configuration, variables, files);
+        } else if item.has_tag_name("file") && !is_excluded(item, configuration) {
+            if let Some(name) = child_text(item, "name") {
+                let file = variables.expand(name);
+                let extension = Path::new(&file).extension().and_then(|e| e.to_str()).unwrap_or_default();
+                if ["c", "cc", "cpp", "cxx", "C", "CPP"].contains(&extension) {
+                    files.push(file);
+                }
+            }
+        }
+    }
+}
+
+/// Entries for the sources of `project` in `configuration` (the first if
+/// `None`). `toolkit_dir` replaces `$TOOLKIT_DIR$` when given.
+pub fn import(project : &Path, configuration : Option<&str>, compiler : &str, toolkit_dir : Option<&str>) -> Result<Vec<CompilationEntry>, String> {
+    let content = std::fs::read_to_string(project).map_err(|e| format!("{}: {}", project.display(), e))?;
+    let document = roxmltree::Document::parse(&content).map_err(|e| format!("{}: {}", project.display(), e))?;
+    let root = document.root_element();
+
+    let configurations : Vec<roxmltree::Node> = root.children().filter(|n| n.has_tag_name("configuration")).collect();
+    let configuration_node = match configuration {
+        Some(name) => configurations.iter().find(|c| child_text(**c, "name") == Some(name)).ok_or_else(|| format!("{}: no configuration \"{}\"", project.display(), name))?,
+        None => configurations.first().ok_or_else(|| format!("{}: no configuration", project.display()))?,
+    };
+    let configuration_name = child_text(*configuration_node, "name").unwrap_or_default();
+
+    let project_dir : PathBuf = std::path::absolute(project.parent().unwrap_or(Path::new("."))).map_err(|e| e.to_string())?;
+    let variables = Variables {
+        proj_dir : slashed(&project_dir),
+        toolkit_dir : toolkit_dir.map(String::from),
+    };
+
+    let mut flags = Vec::<String>::new();
+    if child(*configuration_node, "toolchain").and_then(|t| child_text(t, "name")) == Some("ARM") {
+        flags.push("--target=arm-none-eabi".into());
+    }
+    for settings in configuration_node.children().filter(|n| n.has_tag_name("settings")) {
+        if !child_text(settings, "name").is_some_and(|n| n.starts_with("ICC")) {
+            continue;
+        }
+        flags.extend(option_states(settings, "CCDefines").into_iter().map(|d| format!("-D{}", d)));
+        for include_option in ["CCIncludePath2", "CCIncludePath"] {
+            flags.extend(option_states(settings, include_option).into_iter().map(|d| format!("-I{}", variables.expand(d))));
+        }
+        for pre_include in option_states(settings, "PreInclude") {
+            flags.extend(["-include".into(), variables.expand(pre_include)]);
+        }
+    }
+
+    let mut files = Vec::<String>::new();
+    collect_files(root, configuration_name, &variables, &mut files);
+
+    Ok(files.into_iter().map(|file| {
+        let mut arguments = vec![compiler.to_string()];
+        arguments.extend(flags.clone());
+        arguments.extend(["-c".into(), file.clone()]);
+        CompilationEntry {
+            directory : variables.proj_dir.clone(),
+            arguments,
+            file,
+            ..Default::default()
+        }
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::import;
+
+    const EWP : &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<project>
+  <fileVersion>3</fileVersion>
+  <configuration>
+    <name>Debug</name>
+    <toolchain><name>ARM</name></toolchain>
+    <settings>
+      <name>ICCARM</name>
+      <data>
+        <option><name>CCDefines</name><state>USE_HAL_DRIVER</state><state>STM32F407xx</state></option>
+        <option><name>CCIncludePath2</name><state>$PROJ_DIR$\..\Inc</state><state>$TOOLKIT_DIR$\CMSIS\Include</state></option>
+        <option><name>PreInclude</name><state>$PROJ_DIR$\config.h</state></option>
+      </data>
+    </settings>
+    <settings>
+      <name>ILINK</name>
+      <data><option><name>CCDefines</name><state>NOT_A_COMPILER_DEFINE</state></option></data>
+    </settings>
+  </configuration>
+  <configuration>
+    <name>Release</name>
+    <toolchain><name>ARM</name></toolchain>
+  </configuration>
+  <group>
+    <name>App</name>
+    <file><name>$PROJ_DIR$\..\Src\main.c</name></file>
+    <file><name>$PROJ_DIR$\..\Src\debug_only.c</name><excluded><configuration>Release</configuration></excluded></file>
+    <file><name>$PROJ_DIR$\..\Src\startup.s</name></file>
+  </group>
+  <group>
+    <name>Tests</name>
+    <excluded><configuration>Debug</configuration></excluded>
+    <file><name>$PROJ_DIR$\..\Test\test.c</name></file>
+  </group>
+</project>
+"#;
+
+    #[test]
+    fn icc_settings_as_clang_arguments() {
+        let dir = std::env::temp_dir().join(format!("compdb-iar-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let project = dir.join("app.ewp");
+        std::fs::write(&project, EWP).unwrap();
+        let project_dir = super::slashed(&std::path::absolute(&dir).unwrap());
+
+        let entries = import(&project, None, "clang", Some("C:/iar/arm")).unwrap();
+        let files : Vec<_> = entries.iter().map(|entry| entry.file.clone()).collect();
+        assert_eq!(files, [format!("{}/../Src/main.c", project_dir), format!("{}/../Src/debug_only.c", project_dir)]);
+        assert_eq!(entries[0].arguments[..9], [
+            "clang".to_string(),
+            "--target=arm-none-eabi".into(),
+            "-DUSE_HAL_DRIVER".into(),
+            "-DSTM32F407xx".into(),
+            format!("-I{}/../Inc", project_dir),
+            "-IC:/iar/arm/CMSIS/Include".into(),
+            "-include".into(),
+            format!("{}/config.h", project_dir),
+            "-c".into(),
+        ]);
+
+        let entries = import(&project, Some("Release"), "clang", None).unwrap();
+        let files : Vec<_> = entries.iter().map(|entry| entry.file.rsplit('/').next().unwrap()).collect();
+        assert_eq!(files, ["main.c", "test.c"]);
+        assert_eq!(entries[0].arguments[..2], ["clang", "--target=arm-none-eabi"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+//! Reads Keil MDK µVision `.uvprojx` projects: the C/C++ include paths,
+//! defines and file list of one target.
+
+use std::path::Path;
+
+use crate::compdb::CompilationEntry;
+use crate::generate::slashed;
+
+fn child<'a, 'input>(node : roxmltree::Node<'a, 'input>, name : &str) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn path_text<'a>(node : roxmltree::Node<'a, '_>, path : &[&str]) -> Option<&'a str> {
+    let mut current = node;
+    for name in path {
+        current = child(current, name)?;
+    }
+    current.text().map(|t| t.trim())
+}
+
+/// `-mcpu` for the `CPUTYPE("Cortex-M4")` part of the device's `Cpu` string.
+fn cpu_flag(cpu : &str) -> Option<String> {
+    let (_, rest) = cpu.split_once("CPUTYPE(\"")?;
+    let (cpu_type, _) = rest.split_once('"')?;
+    Some(format!("-mcpu={}", cpu_type.to_lowercase()))
+}
+
+/// Whether a file or group carries `IncludeInBuild` = 0.
+fn excluded_from_build(node : roxmltree::Node) -> bool {
+    node.descendants().find(|n| n.has_tag_name("IncludeInBuild")).and_then(|n| n.text()).map(|t| t.trim()) == Some("0")
+}
+
+fn various_controls_flags(controls : roxmltree::Node) -> Vec<String> {
+    let mut flags = Vec::<String>::new();
+    if let Some(defines) = path_text(controls, &["Define"]) {
+        flags.extend(defines.split([',', ' ']).filter(|d| !d.is_empty()).map(|d| format!("-D{}", d)));
+    }
+    if let Some(undefines) = path_text(controls, &["Undefine"]) {
+        flags.extend(undefines.split([',', ' ']).filter(|d| !d.is_empty()).map(|d| format!("-U{}", d)));
+    }
+    if let Some(include_paths) = path_text(controls, &["IncludePath"]) {
+        flags.extend(include_paths.split(';').map(|p| p.trim()).filter(|p| !p.is_empty()).map(|p| format!("-I{}", p.replace("\\", "/"))));
+    }
+    flags
+}
+
+/// Entries for the files of `target` in `project` (the first target if `None`).
+pub fn import(project : &Path, target : Option<&str>, compiler : &str) -> Result<Vec<CompilationEntry>, String> {
+    let content = std::fs::read_to_string(project).map_err(|e| format!("{}: {}", project.display(), e))?;
+    let document = roxmltree::Document::parse(&content).map_err(|e| format!("{}: {}", project.display(), e))?;
+
+    let targets : Vec<roxmltree::Node> = document.descendants().filter(|n| n.has_tag_name("Target")).collect();
+    let target_node = match target {
+        Some(name) => targets.iter().find(|t| path_text(**t, &["TargetName"]) == Some(name)).ok_or_else(|| format!("{}: no target \"{}\"", project.display(), name))?,
+        None => targets.first().ok_or_else(|| format!("{}: no target", project.display()))?,
+    };
+
+    let mut target_flags = vec!["--target=arm-none-eabi".to_string(), "-mthumb".to_string()];
+    target_flags.extend(path_text(*target_node, &["TargetOption", "TargetCommonOption", "Cpu"]).and_then(cpu_flag));
+
+    let controls = |tool : &str| target_node.descendants().find(|n| n.has_tag_name(tool)).and_then(|n| child(n, "VariousControls"));
+    let c_flags = controls("Cads").map(various_controls_flags).unwrap_or_default();
+
+    let directory = std::path::absolute(project.parent().unwrap_or(Path::new("."))).map_err(|e| e.to_string())?;
+    let directory = slashed(&directory);
+    let mut entries = Vec::<CompilationEntry>::new();
+    for group in target_node.descendants().filter(|n| n.has_tag_name("Group")) {
+        if child(group, "GroupOption").is_some_and(excluded_from_build) {
+            continue;
+        }
+        for file in group.descendants().filter(|n| n.has_tag_name("File")) {
+            // FileType 1 is C, 8 is C++; assembler and libraries are skipped.
+            if !matches!(path_text(file, &["FileType"]), Some("1" | "8")) || child(file, "FileOption").is_some_and(excluded_from_build) {
+                continue;
+            }
+            let Some(path) = path_text(file, &["FilePath"]) else { continue };
+            let path = path.replace("\\", "/");
+
+            let mut arguments = vec![compiler.to_string()];
+            arguments.extend(target_flags.clone());
+            arguments.extend(c_flags.clone());
+            if let Some(file_controls) = file.descendants().find(|n| n.has_tag_name("Cads")).and_then(|n| child(n, "VariousControls")) {
+                arguments.extend(various_controls_flags(file_controls));
+            }
+            arguments.extend(["-c".into(), path.clone()]);
+            entries.push(CompilationEntry {
+                directory : directory.clone(),
+                arguments,
+                file : path,
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::import;
+
+    const UVPROJX : &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no" ?>
+<Project xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:noNamespaceSchemaLocation="project_projx.xsd">
+  <Targets>
+    <Target>
+      <TargetName>Blinky</TargetName>
+      <TargetOption>
+        <TargetCommonOption>
+          <Device>STM32F407VGTx</Device>
+          <Cpu>IRAM(0x20000000,0x20000) IROM(0x08000000,0x100000) CPUTYPE("Cortex-M4") FPU2 CLOCK(12000000)</Cpu>
+        </TargetCommonOption>
+        <TargetArmAds>
+          <Cads>
+            <VariousControls>
+              <Define>USE_HAL_DRIVER,STM32F407xx</Define>
+              <Undefine></Undefine>
+              <IncludePath>..\Inc;..\Drivers\CMSIS\Include</IncludePath>
+            </VariousControls>
+          </Cads>
+        </TargetArmAds>
+      </TargetOption>
+      <Groups>
+        <Group>
+          <GroupName>App</GroupName>
+          <Files>
+            <File><FileName>main.c</FileName><FileType>1</FileType><FilePath>..\Src\main.c</FilePath></File>
+            <File>
+              <FileName>driver.cpp</FileName><FileType>8</FileType><FilePath>..\Src\driver.cpp</FilePath>
+              <FileOption><FileArmAds><Cads><VariousControls><Define>DRIVER</Define></VariousControls></Cads></FileArmAds></FileOption>
+            </File>
+            <File><FileName>startup.s</FileName><FileType>2</FileType><FilePath>startup.s</FilePath></File>
+            <File>
+              <FileName>unused.c</FileName><FileType>1</FileType><FilePath>..\Src\unused.c</FilePath>
+              <FileOption><CommonProperty><IncludeInBuild>0</IncludeInBuild></CommonProperty></FileOption>
+            </File>
+          </Files>
+        </Group>
+        <Group>
+          <GroupName>Tests</GroupName>
+          <GroupOption><CommonProperty><IncludeInBuild>0</IncludeInBuild></CommonProperty></GroupOption>
+          <Files><File><FileName>test.c</FileName><FileType>1</FileType><FilePath>..\Test\test.c</FilePath></File></Files>
+        </Group>
+      </Groups>
+    </Target>
+  </Targets>
+</Project>
+"#;
+
+    #[test]
+    fn c_and_cpp_files_of_a_target() {
+        let dir = std::env::temp_dir().join(format!("compdb-keil-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let project = dir.join("Blinky.uvprojx");
+        std::fs::write(&project, UVPROJX).unwrap();
+
+        let entries = import(&project, Some("Blinky"), "clang").unwrap();
+        let arguments : Vec<_> = entries.iter().map(|entry| entry.arguments.join(" ")).collect();
+        let common = "clang --target=arm-none-eabi -mthumb -mcpu=cortex-m4 -DUSE_HAL_DRIVER -DSTM32F407xx -I../Inc -I../Drivers/CMSIS/Include";
+        assert_eq!(arguments, [format!("{} -c ../Src/main.c", common), format!("{} -DDRIVER -c ../Src/driver.cpp", common)]);
+        assert_eq!(entries[0].directory, super::slashed(&std::path::absolute(&dir).unwrap()));
+        assert!(import(&project, Some("Release"), "clang").unwrap_err().contains("no target \"Release\""));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
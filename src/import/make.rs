@@ -0,0 +1,138 @@
+//! Recovers compile commands from the output of `make -nB` or from a saved
+//! build log.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::compdb::{self, CompilationEntry};
+use crate::shell;
+
+fn default_compiler_regex() -> regex::Regex {
+    regex::Regex::new(r"^(.*-)?(cc|c\+\+|gcc|g\+\+|clang|clang\+\+)(-[0-9.]+)?(\.exe)?$").unwrap()
+}
+
+/// Lines of a build log with backslash continuations joined.
+fn logical_lines(log : &str) -> Vec<String> {
+    let mut lines = Vec::<String>::new();
+    let mut current = String::new();
+    for line in log.lines() {
+        if let Some(continued) = line.strip_suffix('\\') {
+            current.push_str(continued);
+            current.push(' ');
+        } else {
+            current.push_str(line);
+            lines.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// The directory of a `make[N]: Entering directory '...'` line.
+fn entered_directory(line : &str) -> Option<&str> {
+    let (_, rest) = line.split_once(": Entering directory ")?;
+    Some(rest.trim().trim_matches(|c| c == '\'' || c == '`' || c == '"'))
+}
+
+/// `arguments` without the shell's redirections, `2>&1`, `> log` and the
+/// like, which `redirection` matches the operators of.
+fn without_redirections(arguments : Vec<String>, redirection : &regex::Regex) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut arguments = arguments.into_iter();
+    while let Some(argument) = arguments.next() {
+        match redirection.find(&argument) {
+            // The target is the next word.
+            Some(operator) if operator.len() == argument.len() && !operator.as_str().contains('&') => { arguments.next(); }
+            Some(_) => {}
+            None => kept.push(argument),
+        }
+    }
+    kept
+}
+
+/// Entries for the compiler invocations found in `log`, whose commands run
+/// in `directory` unless make reports entering another one.
+pub fn parse_log(log : &str, directory : &Path, compiler : Option<&regex::Regex>) -> Vec<CompilationEntry> {
+    let default_compiler = default_compiler_regex();
+    let compiler = compiler.unwrap_or(&default_compiler);
+    let redirection = regex::Regex::new(r"^[0-9]*(>>?|<)(&[0-9-])?").unwrap();
+
+    let mut directories = vec![directory.to_path_buf()];
+    let mut entries = Vec::<CompilationEntry>::new();
+    for line in logical_lines(log) {
+        if let Some(entered) = entered_directory(&line) {
+            directories.push(directory.join(entered));
+            continue;
+        }
+        if line.contains(": Leaving directory ") {
+            if directories.len() > 1 {
+                directories.pop();
+            }
+            continue;
+        }
+
+        let mut cwd : PathBuf = directories.last().unwrap().clone();
+        for arguments in shell::split_list(&line) {
+            // `ccache gcc ...` compiles with gcc.
+            let arguments = compdb::strip_compiler_wrappers(&without_redirections(arguments, &redirection));
+            let Some(program) = arguments.first() else { continue };
+            if program == "cd" {
+                if let Some(dir) = arguments.get(1).filter(|d| *d != ".") {
+                    cwd = cwd.join(dir);
+                }
+                continue;
+            }
+            let program_name = Path::new(program).file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            if !compiler.is_match(program_name) {
+                continue;
+            }
+            entries.extend(super::entry_from_invocation(&cwd, arguments));
+        }
+    }
+
+    entries
+}
+
+/// Runs `make -nB` in `directory` and parses what it would have executed.
+pub fn dry_run(make : &str, directory : &Path, make_args : &[String]) -> Result<String, String> {
+    let output = Command::new(make).arg("-C").arg(directory).args(["-n", "-B", "-w"]).args(make_args).output()
+        .map_err(|e| format!("{}: {}", make, e))?;
+    if !output.status.success() {
+        return Err(format!("{} -nB: {}", make, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::parse_log;
+
+    const LOG : &str = r#"make: Entering directory '/proj'
+echo "gcc -c not_compiled.c"; gcc -Iinc \
+    -DMSG='"a;b && c"' -c main.c -o main.o
+make[1]: Entering directory '/proj/lib'
+cd sub && ccache gcc -O2 -c util.c -o util.o 2>&1 >> build.log | tee util.log
+distcc arm-none-eabi-g++ -c driver.cpp
+make[1]: Leaving directory '/proj/lib'
+gcc main.o -o app
+"#;
+
+    #[test]
+    fn compiler_invocations_with_their_directories() {
+        let entries = parse_log(LOG, Path::new("/"), None);
+        let found : Vec<_> = entries.iter().map(|entry| (entry.directory.as_str(), entry.arguments.join(" "))).collect();
+        assert_eq!(found, [
+            ("/proj", r#"gcc -Iinc -DMSG="a;b && c" -c main.c -o main.o"#.to_string()),
+            ("/proj/lib/sub", "gcc -O2 -c util.c -o util.o".to_string()),
+            ("/proj/lib", "arm-none-eabi-g++ -c driver.cpp".to_string()),
+        ]);
+        assert_eq!(entries[2].file, "driver.cpp");
+
+        let only_cross = regex::Regex::new("^arm-none-eabi-").unwrap();
+        assert_eq!(parse_log(LOG, Path::new("/"), Some(&only_cross)).len(), 1);
+    }
+}
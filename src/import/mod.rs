@@ -0,0 +1,94 @@
+//! Importers turning other build systems' descriptions into compilation
+//! entries.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::compdb::{self, CompilationEntry};
+use crate::conf::{IncludeConf, WorkSpaceConf};
+use crate::generate::slashed;
+
+pub mod autosar;
+pub mod cmake;
+pub mod depfiles;
+pub mod eclipse;
+pub mod esp_idf;
+pub mod iar;
+pub mod keil;
+pub mod make;
+pub mod ninja;
+pub mod platformio;
+pub mod stm32cube;
+pub mod vcxproj;
+pub mod zephyr;
+
+/// Configuration keys to merge with the project's own configuration.
+#[derive(Serialize)]
+pub struct Fragment {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub include_group : BTreeMap<String, IncludeConf>,
+    pub workspace : Vec<WorkSpaceConf>,
+}
+
+/// Writes `fragment` as TOML to `output`, or to stdout when it is `-`.
+pub fn write_fragment(fragment : &Fragment, output : &str) -> Result<(), String> {
+    let text = toml::to_string_pretty(fragment).map_err(|e| e.to_string())?;
+    match output {
+        "-" => {
+            print!("{}", text);
+            Ok(())
+        }
+        output => std::fs::write(output, text).map_err(|e| format!("{}: {}", output, e)),
+    }
+}
+
+const SOURCE_EXTENSIONS : [&str; 11] = ["c", "cc", "cpp", "cxx", "c++", "C", "CC", "CPP", "CXX", "s", "S"];
+
+fn source_of(arguments : &[String]) -> Option<&String> {
+    let mut previous = "";
+    for argument in arguments {
+        if !argument.starts_with('-') && previous != "-o" {
+            let extension = Path::new(argument).extension().and_then(|e| e.to_str()).unwrap_or_default();
+            if SOURCE_EXTENSIONS.contains(&extension) {
+                return Some(argument);
+            }
+        }
+        previous = argument;
+    }
+    None
+}
+
+/// The entry for a compiler invocation run in `directory`, if it compiles
+/// (`-c`) a source file.
+pub fn entry_from_invocation(directory : &Path, arguments : Vec<String>) -> Option<CompilationEntry> {
+    if !arguments.iter().any(|a| a == "-c") {
+        return None;
+    }
+    let file = source_of(&arguments)?.clone();
+    Some(CompilationEntry {
+        directory : slashed(directory),
+        file,
+        arguments,
+        ..Default::default()
+    })
+}
+
+/// What importers of SDK build directories produce: the build's own
+/// entries, and the flags TOML-defined application workspaces need.
+pub struct BuildImport {
+    pub entries : Vec<CompilationEntry>,
+    pub application_flags : Vec<String>,
+}
+
+/// The `compile_commands.json` a CMake-based SDK build leaves in
+/// `build_dir`; empty, with a warning, when the build has not run yet.
+fn read_build_db(build_dir : &Path) -> Result<Vec<CompilationEntry>, String> {
+    let database = build_dir.join("compile_commands.json");
+    if !database.exists() {
+        crate::diag::warn(&format!("{} does not exist yet; only application flags are available", database.display()));
+        return Ok(Vec::new());
+    }
+    Ok(compdb::read_db(&database)?.into_iter().map(CompilationEntry::into_arguments_form).collect())
+}
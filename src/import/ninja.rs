@@ -0,0 +1,55 @@
+//! Takes the compile commands of a Ninja build from `ninja -t compdb`.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::compdb::{self, CompilationEntry};
+
+/// Entries for the compile edges of the Ninja build in `build_dir`. With no
+/// `rules`, every command that compiles (`-c`) is kept.
+pub fn import(ninja : &str, build_dir : &Path, rules : &[String]) -> Result<Vec<CompilationEntry>, String> {
+    let output = Command::new(ninja).arg("-C").arg(build_dir).args(["-t", "compdb", "-x"]).args(rules).output()
+        .map_err(|e| format!("{}: {}", ninja, e))?;
+    if !output.status.success() {
+        return Err(format!("{} -t compdb: {}", ninja, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    let entries = compdb::parse_db(&String::from_utf8_lossy(&output.stdout)).map_err(|e| format!("{} -t compdb: {}", ninja, e))?;
+    Ok(entries.into_iter()
+        .map(CompilationEntry::into_arguments_form)
+        .filter(|e| !rules.is_empty() || e.arguments.iter().any(|a| a == "-c"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::import;
+
+    /// A stand-in for `ninja -t compdb` printing a database with a link edge.
+    #[cfg(unix)]
+    #[test]
+    fn keeps_the_compile_edges_in_the_arguments_form() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("compdb-ninja-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ninja = dir.join("ninja");
+        std::fs::write(&ninja, r#"#!/bin/sh
+cat <<'EOF'
+[
+  { "directory": "/build", "command": "cc -I../inc '-DNAME=\"x y\"' -c ../a.c -o a.o", "file": "../a.c" },
+  { "directory": "/build", "command": "cc a.o -o app", "file": "app" }
+]
+EOF
+"#).unwrap();
+        std::fs::set_permissions(&ninja, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let entries = import(&ninja.to_string_lossy(), &dir, &[]).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].command, None);
+        assert_eq!(entries[0].arguments, ["cc", "-I../inc", "-DNAME=\"x y\"", "-c", "../a.c", "-o", "a.o"]);
+        // Named rules are taken as they are.
+        assert_eq!(import(&ninja.to_string_lossy(), &dir, &["link".into()]).unwrap().len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+//! PlatformIO projects, from the JSON of `pio project metadata`: compiler,
+//! flags, defines and include paths per environment.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+use serde::Deserialize;
+
+use crate::compdb::CompilationEntry;
+use crate::generate::slashed;
+use crate::shell;
+
+#[derive(Deserialize)]
+struct Environment {
+    cc_path : Option<String>,
+    cxx_path : Option<String>,
+    #[serde(default)]
+    cc_flags : serde_json::Value,
+    #[serde(default)]
+    cxx_flags : serde_json::Value,
+    #[serde(default)]
+    defines : Vec<String>,
+    #[serde(default)]
+    includes : serde_json::Value,
+}
+
+/// Flags are a string in older PlatformIO versions and a list in newer ones.
+fn flag_list(value : &serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::String(flags) => shell::split(flags),
+        serde_json::Value::Array(flags) => flags.iter().filter_map(|f| f.as_str()).map(String::from).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Include paths are a list, or lists grouped by kind (`build`, `compatlib`,
+/// `toolchain`), depending on the PlatformIO version.
+fn include_list(value : &serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::Array(_) => flag_list(value),
+        serde_json::Value::Object(groups) => ["build", "compatlib", "toolchain"].iter().filter_map(|g| groups.get(*g)).flat_map(flag_list).collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn run_metadata(pio : &str, project_dir : &Path, environments : &[String]) -> Result<String, String> {
+    let mut command = Command::new(pio);
+    command.args(["project", "metadata", "--json-output", "-d"]).arg(project_dir);
+    for environment in environments {
+        command.args(["-e", environment]);
+    }
+    let output = command.output().map_err(|e| format!("{}: {}", pio, e))?;
+    if !output.status.success() {
+        return Err(format!("{} project metadata: {}", pio, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Entries for the sources under `src_dir`, per environment of `metadata`.
+pub fn import(metadata : &str, project_dir : &Path, src_dir : &Path) -> Result<BTreeMap<String, Vec<CompilationEntry>>, String> {
+    let environments : BTreeMap<String, Environment> = serde_json::from_str(metadata).map_err(|e| format!("pio project metadata: {}", e))?;
+    let project_dir = std::path::absolute(project_dir).map_err(|e| e.to_string())?;
+    let directory = slashed(&project_dir);
+
+    let mut sources = Vec::<(String, bool)>::new();
+    for entry in walkdir::WalkDir::new(project_dir.join(src_dir)).sort_by_file_name().into_iter().filter_map(|e| e.ok()).filter(|e| e.file_type().is_file()) {
+        let extension = entry.path().extension().and_then(|e| e.to_str()).unwrap_or_default();
+        let is_cpp = ["cc", "cpp", "cxx", "ino", "CC", "CPP", "CXX"].contains(&extension);
+        if is_cpp || extension == "c" {
+            sources.push((slashed(entry.path().strip_prefix(&project_dir).unwrap()), is_cpp));
+        }
+    }
+
+    let mut databases = BTreeMap::<String, Vec<CompilationEntry>>::new();
+    for (name, environment) in environments {
+        let includes : Vec<String> = include_list(&environment.includes).into_iter().map(|i| format!("-I{}", i.replace("\\", "/"))).collect();
+        let defines : Vec<String> = environment.defines.iter().map(|d| format!("-D{}", d)).collect();
+
+        let entries = sources.iter().map(|(file, is_cpp)| {
+            let (compiler, flags) = if *is_cpp {
+                (environment.cxx_path.clone().unwrap_or_else(|| "c++".into()), flag_list(&environment.cxx_flags))
+            } else {
+                (environment.cc_path.clone().unwrap_or_else(|| "cc".into()), flag_list(&environment.cc_flags))
+            };
+            let mut arguments = vec![compiler];
+            arguments.extend(flags);
+            arguments.extend(defines.clone());
+            arguments.extend(includes.clone());
+            if file.ends_with(".ino") {
+                arguments.extend(["-x".into(), "c++".into()]);
+            }
+            arguments.extend(["-c".into(), file.clone()]);
+            CompilationEntry {
+                directory : directory.clone(),
+                arguments,
+                file : file.clone(),
+                ..Default::default()
+            }
+        }).collect();
+        databases.insert(name, entries);
+    }
+
+    Ok(databases)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::import;
+
+    #[test]
+    fn a_database_per_environment() {
+        let dir = std::env::temp_dir().join(format!("compdb-platformio-{}", std::process::id()));
+        for file in ["src/main.cpp", "src/sketch.ino", "src/util.c", "src/README.md"] {
+            std::fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        // Flags as strings and plain includes, as older versions print them,
+        // and as lists with grouped includes, as newer ones do.
+        let metadata = serde_json::json!({
+            "uno" : {
+                "cc_path" : "/pio/avr-gcc", "cxx_path" : "/pio/avr-g++",
+                "cc_flags" : "-Os -mmcu=atmega328p", "cxx_flags" : "-Os -fno-exceptions",
+                "defines" : ["ARDUINO=10808"],
+                "includes" : ["/pio/cores/arduino"],
+            },
+            "esp32" : {
+                "cc_path" : "/pio/xtensa-esp32-elf-gcc",
+                "cc_flags" : ["-Os", "-mlongcalls"],
+                "includes" : { "build" : ["C:\\pio\\include"], "toolchain" : ["/pio/toolchain/include"] },
+            },
+        }).to_string();
+
+        let databases = import(&metadata, &dir, Path::new("src")).unwrap();
+        assert_eq!(databases.keys().collect::<Vec<_>>(), ["esp32", "uno"]);
+        let arguments = |name : &str| databases[name].iter().map(|entry| entry.arguments.join(" ")).collect::<Vec<_>>();
+        assert_eq!(arguments("uno"), [
+            "/pio/avr-g++ -Os -fno-exceptions -DARDUINO=10808 -I/pio/cores/arduino -c src/main.cpp",
+            "/pio/avr-g++ -Os -fno-exceptions -DARDUINO=10808 -I/pio/cores/arduino -x c++ -c src/sketch.ino",
+            "/pio/avr-gcc -Os -mmcu=atmega328p -DARDUINO=10808 -I/pio/cores/arduino -c src/util.c",
+        ]);
+        assert_eq!(arguments("esp32")[2], "/pio/xtensa-esp32-elf-gcc -Os -mlongcalls -IC:/pio/include -I/pio/toolchain/include -c src/util.c");
+        // No C++ compiler in the metadata.
+        assert!(arguments("esp32")[0].starts_with("c++ -IC:/pio/include"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
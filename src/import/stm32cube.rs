@@ -0,0 +1,75 @@
+//! STM32CubeIDE projects are Eclipse CDT managed builds; on top of what
+//! the generic reader extracts, this derives the MCU flags from the ST
+//! toolchain options.
+
+use super::eclipse::CdtConfiguration;
+
+const OPTION_PREFIX : &str = "com.st.stm32cube.ide.mcu.gnu.managedbuild.option.";
+
+/// The Cortex core of an STM32 part number such as `STM32F407VGTx`.
+fn core_of(mcu : &str) -> Option<&'static str> {
+    let family = mcu.strip_prefix("STM32")?;
+    let core = match family.get(..2)? {
+        "F0" | "G0" | "L0" | "C0" => "cortex-m0plus",
+        "F1" | "F2" | "L1" => "cortex-m3",
+        "F3" | "F4" | "G4" | "L4" | "WB" | "WL" => "cortex-m4",
+        "F7" | "H7" => "cortex-m7",
+        "L5" | "U5" | "H5" => "cortex-m33",
+        "MP" => "cortex-a7",
+        _ => return None,
+    };
+    Some(core)
+}
+
+/// The value of the ST option `name`, with the `<option id>.value.` prefix
+/// of enumerated values stripped.
+fn st_option<'a>(configuration : &'a CdtConfiguration, name : &str) -> Option<&'a str> {
+    let super_class = format!("{}{}", OPTION_PREFIX, name);
+    configuration.options.iter()
+        .find(|(class, _)| class == &super_class)
+        .map(|(_, value)| value.rsplit_once(".value.").map(|(_, v)| v).unwrap_or(value))
+}
+
+pub fn target_flags(configuration : &CdtConfiguration) -> Vec<String> {
+    let mut flags = Vec::<String>::new();
+    if let Some(core) = st_option(configuration, "target_mcu").and_then(core_of) {
+        flags.push(format!("-mcpu={}", core));
+        flags.push("-mthumb".into());
+    }
+    if let Some(fpu) = st_option(configuration, "fpu").filter(|f| *f != "no") {
+        flags.push(format!("-mfpu={}", fpu));
+    }
+    if let Some(float_abi) = st_option(configuration, "floatabi") {
+        flags.push(format!("-mfloat-abi={}", float_abi));
+    }
+    flags
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::eclipse::{CdtConfiguration, LanguageFlags};
+
+    use super::{target_flags, OPTION_PREFIX};
+
+    fn configuration(options : &[(&str, &str)]) -> CdtConfiguration {
+        CdtConfiguration {
+            name : "Debug".into(),
+            flags : LanguageFlags::default(),
+            source_entries : Vec::new(),
+            options : options.iter().map(|(name, value)| (format!("{}{}", OPTION_PREFIX, name), value.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn mcu_flags_from_the_st_toolchain_options() {
+        let f407 = configuration(&[
+            ("target_mcu", "STM32F407VGTx"),
+            ("fpu", "com.st.stm32cube.ide.mcu.gnu.managedbuild.option.fpu.value.fpv4-sp-d16"),
+            ("floatabi", "com.st.stm32cube.ide.mcu.gnu.managedbuild.option.floatabi.value.hard"),
+        ]);
+        assert_eq!(target_flags(&f407), ["-mcpu=cortex-m4", "-mthumb", "-mfpu=fpv4-sp-d16", "-mfloat-abi=hard"]);
+        let g071 = configuration(&[("target_mcu", "STM32G071RBTx"), ("fpu", "com.st.stm32cube.ide.mcu.gnu.managedbuild.option.fpu.value.no")]);
+        assert_eq!(target_flags(&g071), ["-mcpu=cortex-m0plus", "-mthumb"]);
+        assert!(target_flags(&configuration(&[("target_mcu", "GD32F303")])).is_empty());
+    }
+}
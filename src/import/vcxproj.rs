@@ -0,0 +1,216 @@
+//! Reads MSBuild `.vcxproj` files: include directories, preprocessor
+//! definitions and the `ClCompile` items of one configuration, with the
+//! settings of each item over the item definitions.
+
+use std::path::Path;
+
+use crate::compdb::CompilationEntry;
+use crate::generate::slashed;
+
+const MSBUILD_NS : &str = "http://schemas.microsoft.com/developer/msbuild/2003";
+
+/// The configuration an MSBuild `Condition` like
+/// `'$(Configuration)|$(Platform)'=='Debug|x64'` selects, if any.
+fn condition_configuration(condition : &str) -> Option<&str> {
+    let (_, value) = condition.split_once("==")?;
+    Some(value.trim().trim_matches('\''))
+}
+
+fn applies(node : roxmltree::Node, configuration : &str) -> bool {
+    node.attribute("Condition").and_then(condition_configuration).is_none_or(|c| c == configuration)
+}
+
+fn children<'a, 'input>(node : roxmltree::Node<'a, 'input>, name : &'a str) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> + 'a {
+    node.children().filter(move |n| n.has_tag_name((MSBUILD_NS, name)) || n.has_tag_name(name))
+}
+
+/// Splits a `;`-separated MSBuild list, dropping `%(...)` inheritance
+/// placeholders and expanding the macros we can know statically.
+fn expand_list(value : &str, macros : &[(&str, String)]) -> Vec<String> {
+    value.split(';')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty() && !item.starts_with("%("))
+        .map(|item| macros.iter().fold(item.to_string(), |acc, (name, value)| acc.replace(&format!("$({})", name), value)))
+        .collect()
+}
+
+/// Whether `compiler` takes MSVC's options (`/std:c++17`) rather than GCC's.
+fn is_msvc(compiler : &str) -> bool {
+    Path::new(compiler).file_stem().is_some_and(|stem| ["cl", "clang-cl"].contains(&stem.to_string_lossy().to_ascii_lowercase().as_str()))
+}
+
+fn language_standard_flag(standard : &str, msvc : bool) -> Option<String> {
+    let (language, version) = match standard.strip_prefix("stdcpp") {
+        Some(version) => ("c++", version),
+        None => ("c", standard.strip_prefix("stdc")?),
+    };
+    match (msvc, version) {
+        (true, _) => Some(format!("/std:{}{}", language, version)),
+        (false, "latest") if language == "c++" => Some("-std=c++2c".into()),
+        (false, "latest") => Some("-std=c2x".into()),
+        (false, _) => Some(format!("-std={}{}", language, version)),
+    }
+}
+
+/// The flags of the `ClCompile` settings read, for the item definitions or
+/// one item.
+#[derive(Clone, Default)]
+struct Settings {
+    include_dirs : Vec<String>,
+    defines : Vec<String>,
+    cpp_standard : Option<String>,
+    c_standard : Option<String>,
+}
+
+impl Settings {
+    /// Applies a child of a `ClCompile` definition or item. A list replaces
+    /// the one set before, which goes where it names it with `%(...)`.
+    fn apply(&mut self, setting : roxmltree::Node, macros : &[(&str, String)], msvc : bool) {
+        let value = setting.text().unwrap_or_default();
+        let set = |list : &mut Vec<String>, flag : &dyn Fn(String) -> String| {
+            let mut items = Vec::new();
+            for item in value.split(';').map(str::trim) {
+                if item.starts_with("%(") {
+                    items.append(list);
+                } else {
+                    items.extend(expand_list(item, macros).into_iter().map(flag));
+                }
+            }
+            *list = items;
+        };
+        match setting.tag_name().name() {
+            "AdditionalIncludeDirectories" => set(&mut self.include_dirs, &|d| format!("-I{}", d.replace("\\", "/"))),
+            "PreprocessorDefinitions" => set(&mut self.defines, &|d| format!("-D{}", d)),
+            "LanguageStandard" => self.cpp_standard = language_standard_flag(value.trim(), msvc),
+            "LanguageStandard_C" => self.c_standard = language_standard_flag(value.trim(), msvc),
+            _ => {}
+        }
+    }
+
+    fn flags(&self) -> impl Iterator<Item = String> + '_ {
+        self.include_dirs.iter().chain(&self.defines).chain(&self.cpp_standard).chain(&self.c_standard).cloned()
+    }
+}
+
+/// Entries for the sources of `project` built in `configuration`
+/// (`Debug|x64` style; the first declared configuration if `None`).
+pub fn import(project : &Path, configuration : Option<&str>, compiler : &str) -> Result<Vec<CompilationEntry>, String> {
+    let content = std::fs::read_to_string(project).map_err(|e| format!("{}: {}", project.display(), e))?;
+    let document = roxmltree::Document::parse(&content).map_err(|e| format!("{}: {}", project.display(), e))?;
+    let root = document.root_element();
+
+    let declared : Vec<&str> = children(root, "ItemGroup").flat_map(|g| children(g, "ProjectConfiguration")).filter_map(|n| n.attribute("Include")).collect();
+    let configuration = match configuration {
+        Some(configuration) if declared.is_empty() || declared.contains(&configuration) => configuration,
+        Some(configuration) => return Err(format!("{}: no configuration \"{}\" (available: {})", project.display(), configuration, declared.join(", "))),
+        None => declared.first().copied().ok_or_else(|| format!("{}: no ProjectConfiguration declared", project.display()))?,
+    };
+    let (configuration_name, platform) = configuration.split_once('|').unwrap_or((configuration, ""));
+
+    let project_dir = std::path::absolute(project.parent().unwrap_or(Path::new("."))).map_err(|e| e.to_string())?;
+    let project_dir_str = format!("{}/", slashed(&project_dir));
+    let macros = [
+        ("ProjectDir", project_dir_str.clone()),
+        ("MSBuildProjectDirectory", project_dir_str.trim_end_matches('/').into()),
+        ("MSBuildThisFileDirectory", project_dir_str.clone()),
+        ("ProjectName", project.file_stem().unwrap_or_default().to_string_lossy().into_owned()),
+        ("Configuration", configuration_name.into()),
+        ("Platform", platform.into()),
+    ];
+
+    let msvc = is_msvc(compiler);
+    let mut definitions = Settings::default();
+    for definition_group in children(root, "ItemDefinitionGroup").filter(|n| applies(*n, configuration)) {
+        for cl_compile in children(definition_group, "ClCompile").filter(|n| applies(*n, configuration)) {
+            for setting in cl_compile.children().filter(|n| n.is_element() && applies(*n, configuration)) {
+                definitions.apply(setting, &macros, msvc);
+            }
+        }
+    }
+
+    let mut entries = Vec::<CompilationEntry>::new();
+    for item_group in children(root, "ItemGroup").filter(|n| applies(*n, configuration)) {
+        for item in children(item_group, "ClCompile").filter(|n| applies(*n, configuration)) {
+            let Some(include) = item.attribute("Include") else { continue };
+            let excluded = children(item, "ExcludedFromBuild").any(|n| applies(n, configuration) && n.text().is_some_and(|t| t.trim().eq_ignore_ascii_case("true")));
+            if excluded {
+                continue;
+            }
+
+            // An item's own settings start from the item definitions.
+            let mut settings = definitions.clone();
+            for setting in item.children().filter(|n| n.is_element() && applies(*n, configuration)) {
+                settings.apply(setting, &macros, msvc);
+            }
+            let file = include.replace("\\", "/");
+            let mut arguments = vec![compiler.to_string()];
+            arguments.extend(settings.flags());
+            arguments.extend(["-c".into(), file.clone()]);
+            entries.push(CompilationEntry {
+                directory : project_dir_str.trim_end_matches('/').into(),
+                arguments,
+                file,
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::import;
+
+    const PROJECT : &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<Project DefaultTargets="Build" xmlns="http://schemas.microsoft.com/developer/msbuild/2003">
+  <ItemGroup Label="ProjectConfigurations">
+    <ProjectConfiguration Include="Debug|x64" />
+    <ProjectConfiguration Include="Release|x64" />
+  </ItemGroup>
+  <ItemDefinitionGroup Condition="'$(Configuration)|$(Platform)'=='Debug|x64'">
+    <ClCompile>
+      <AdditionalIncludeDirectories>inc;$(ProjectDir)third_party\lib;%(AdditionalIncludeDirectories)</AdditionalIncludeDirectories>
+      <PreprocessorDefinitions>_DEBUG;%(PreprocessorDefinitions)</PreprocessorDefinitions>
+      <LanguageStandard>stdcpp17</LanguageStandard>
+    </ClCompile>
+  </ItemDefinitionGroup>
+  <ItemGroup>
+    <ClCompile Include="src\main.cpp" />
+    <ClCompile Include="src\legacy.cpp">
+      <AdditionalIncludeDirectories>legacy;%(AdditionalIncludeDirectories)</AdditionalIncludeDirectories>
+      <PreprocessorDefinitions Condition="'$(Configuration)|$(Platform)'=='Debug|x64'">LEGACY</PreprocessorDefinitions>
+    </ClCompile>
+    <ClCompile Include="src\skipped.cpp">
+      <ExcludedFromBuild Condition="'$(Configuration)|$(Platform)'=='Debug|x64'">true</ExcludedFromBuild>
+    </ClCompile>
+  </ItemGroup>
+</Project>
+"#;
+
+    #[test]
+    fn items_add_to_or_replace_the_item_definitions() {
+        let dir = std::env::temp_dir().join(format!("compdb-vcxproj-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let project = dir.join("app.vcxproj");
+        std::fs::write(&project, PROJECT).unwrap();
+        let project_dir = super::slashed(&std::path::absolute(&dir).unwrap());
+
+        let entries = import(&project, None, "clang++").unwrap();
+        let arguments : Vec<_> = entries.iter().map(|entry| entry.arguments.join(" ")).collect();
+        assert_eq!(arguments, [
+            format!("clang++ -Iinc -I{}/third_party/lib -D_DEBUG -std=c++17 -c src/main.cpp", project_dir),
+            format!("clang++ -Ilegacy -Iinc -I{}/third_party/lib -DLEGACY -std=c++17 -c src/legacy.cpp", project_dir),
+        ]);
+        assert_eq!(entries[0].directory, project_dir);
+
+        let entries = import(&project, Some("Debug|x64"), "cl.exe").unwrap();
+        assert!(entries[0].arguments.contains(&"/std:c++17".to_string()));
+        // The item definitions are for Debug only.
+        let entries = import(&project, Some("Release|x64"), "cl").unwrap();
+        assert_eq!(entries[0].arguments, ["cl", "-c", "src/main.cpp"]);
+        assert_eq!(entries.len(), 3);
+        assert!(import(&project, Some("Profile|x64"), "cl").unwrap_err().contains("available: Debug|x64, Release|x64"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+//! Zephyr builds: the build directory's `compile_commands.json` plus the
+//! flags TOML-defined application workspaces need to see Zephyr's headers,
+//! including the generated ones (`autoconf.h`, `syscalls/*.h`) that only
+//! appear once the build has run.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::generate::slashed;
+
+use super::BuildImport;
+
+fn read_cmake_cache(build_dir : &Path) -> Result<HashMap<String, String>, String> {
+    let cache_path = build_dir.join("CMakeCache.txt");
+    let content = std::fs::read_to_string(&cache_path).map_err(|e| format!("{}: {}", cache_path.display(), e))?;
+    Ok(content.lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with("//"))
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            let key = key.split_once(':').map(|(k, _)| k).unwrap_or(key);
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect())
+}
+
+pub fn import(build_dir : &Path) -> Result<BuildImport, String> {
+    let build_dir = std::path::absolute(build_dir).map_err(|e| e.to_string())?;
+    let cache = read_cmake_cache(&build_dir)?;
+    let zephyr_base = cache.get("ZEPHYR_BASE").ok_or_else(|| format!("{}: CMakeCache.txt has no ZEPHYR_BASE; is this a Zephyr build directory?", build_dir.display()))?;
+
+    let entries = super::read_build_db(&build_dir)?;
+
+    let generated = build_dir.join("zephyr/include/generated");
+    let autoconf = generated.join("autoconf.h");
+    if !generated.join("syscalls").exists() && !generated.join("zephyr/syscalls").exists() {
+        crate::diag::warn(&format!("{}/syscalls does not exist yet; Zephyr generates the syscall headers during the build", slashed(&generated)));
+    }
+
+    let mut application_flags = vec![
+        format!("-I{}/include", zephyr_base),
+        format!("-I{}", slashed(&generated)),
+        format!("-I{}", slashed(&generated.join("zephyr"))),
+    ];
+    if autoconf.exists() {
+        application_flags.extend(["-imacros".into(), slashed(&autoconf)]);
+    }
+    application_flags.push("-D__ZEPHYR__=1".into());
+    if let Some(board) = cache.get("BOARD").or_else(|| cache.get("CACHED_BOARD")) {
+        application_flags.push(format!("-DBOARD_{}", board.to_uppercase().replace(['/', '-'], "_")));
+    }
+
+    Ok(BuildImport { entries, application_flags })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::import;
+
+    #[test]
+    fn build_entries_and_application_flags() {
+        let build_dir = std::env::temp_dir().join(format!("compdb-zephyr-{}", std::process::id()));
+        let generated = build_dir.join("zephyr/include/generated");
+        std::fs::create_dir_all(generated.join("zephyr/syscalls")).unwrap();
+        std::fs::write(generated.join("autoconf.h"), "#define CONFIG_BOARD \"nrf52840dk\"\n").unwrap();
+        std::fs::write(build_dir.join("CMakeCache.txt"), "# comment\nZEPHYR_BASE:PATH=/zephyrproject/zephyr\nBOARD:STRING=nrf52840dk/nrf52840\n").unwrap();
+        let build = super::slashed(&build_dir);
+        assert!(import(&build_dir.join("missing")).is_err());
+
+        let imported = import(&build_dir).unwrap();
+        assert!(imported.entries.is_empty());
+        assert_eq!(imported.application_flags, [
+            "-I/zephyrproject/zephyr/include".to_string(),
+            format!("-I{}/zephyr/include/generated", build),
+            format!("-I{}/zephyr/include/generated/zephyr", build),
+            "-imacros".into(),
+            format!("{}/zephyr/include/generated/autoconf.h", build),
+            "-D__ZEPHYR__=1".into(),
+            "-DBOARD_NRF52840DK_NRF52840".into(),
+        ]);
+
+        std::fs::write(build_dir.join("compile_commands.json"), r#"[{ "directory": "/b", "command": "gcc -c main.c", "file": "main.c" }]"#).unwrap();
+        assert_eq!(import(&build_dir).unwrap().entries[0].arguments, ["gcc", "-c", "main.c"]);
+        std::fs::remove_dir_all(&build_dir).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+//! Which include directories the `#include` directives of the target
+//! files actually resolve through.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::compdb::{self, CompilationEntry};
+use crate::db::clean;
+use crate::generate::HEADER_EXTENSIONS;
+
+/// The `#include` directives of `file`: the header name and whether it is
+/// quoted (as opposed to angled).
+pub fn scan_includes(file : &Path) -> Vec<(String, bool)> {
+    let directive = regex::Regex::new(r#"^\s*#\s*include\s*([<"])([^>"]+)[>"]"#).unwrap();
+    let Ok(content) = std::fs::read(file) else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&content).lines()
+        .filter_map(|line| directive.captures(line))
+        .map(|c| (c[2].to_string(), &c[1] == "\""))
+        .collect()
+}
+
+const UNITY_SOURCE_EXTENSIONS : [&str; 8] = ["c", "cc", "cpp", "cxx", "C", "CC", "CPP", "CXX"];
+
+/// The sources a unity (jumbo) file includes, resolved beside it, then
+/// through `include_dirs`; `None` when `file` is not a unity file. Paths
+/// are relative to `root`, like the targets and include dirs. A unity
+/// file has nothing but preprocessor lines and comments, and all its
+/// `#include`s (at least one) name sources.
+pub fn unity_sources(root : &Path, file : &Path, include_dirs : &[PathBuf]) -> Option<Vec<PathBuf>> {
+    let content = std::fs::read(root.join(file)).ok()?;
+    let content = String::from_utf8_lossy(&content);
+    let only_directives = content.lines().map(str::trim).all(|line| line.is_empty() || ["#", "//", "/*", "*"].iter().any(|start| line.starts_with(start)));
+    let includes = scan_includes(&root.join(file));
+    let all_sources = includes.iter().all(|(name, _)| UNITY_SOURCE_EXTENSIONS.contains(&Path::new(name).extension().unwrap_or_default().to_str().unwrap_or_default()));
+    if !only_directives || includes.is_empty() || !all_sources {
+        return None;
+    }
+    let beside = file.parent().unwrap_or(Path::new(""));
+    Some(includes.into_iter().filter_map(|(name, quoted)| {
+        let beside = clean(&beside.join(&name));
+        if quoted && root.join(&beside).is_file() {
+            return Some(beside);
+        }
+        include_dirs.iter().map(|d| clean(&d.join(&name))).find(|p| root.join(p).is_file())
+    }).collect())
+}
+
+/// Headers reached from the targets, each with the index of the include
+/// directory it was found through. Quoted includes found next to the
+/// including file are not attributed to any directory.
+pub struct IncludeUsage {
+    pub resolved : HashMap<PathBuf, usize>,
+}
+
+impl IncludeUsage {
+    pub fn used_dirs(&self) -> HashSet<usize> {
+        self.resolved.values().copied().collect()
+    }
+}
+
+/// Follows the `#include`s of `targets` (relative to `root`, like the
+/// include dirs) and of every header they reach, searching `include_dirs`
+/// in order as the compiler would.
+pub fn resolve_usage(root : &Path, targets : &[PathBuf], include_dirs : &[PathBuf]) -> IncludeUsage {
+    let include_dirs : Vec<PathBuf> = include_dirs.iter().map(|d| root.join(d)).collect();
+    let mut usage = IncludeUsage { resolved : HashMap::new() };
+    let mut visited = HashSet::<PathBuf>::new();
+    let mut pending : Vec<PathBuf> = targets.iter().map(|t| root.join(t)).collect();
+
+    while let Some(file) = pending.pop() {
+        if !visited.insert(file.clone()) {
+            continue;
+        }
+        for (header, quoted) in scan_includes(&file) {
+            if quoted {
+                let beside = file.parent().unwrap_or(root).join(&header);
+                if beside.is_file() {
+                    pending.push(beside);
+                    continue;
+                }
+            }
+            if let Some((index, found)) = include_dirs.iter().enumerate().map(|(i, d)| (i, d.join(&header))).find(|(_, p)| p.is_file()) {
+                usage.resolved.entry(found.clone()).or_insert(index);
+                pending.push(found);
+            }
+        }
+    }
+    usage
+}
+
+/// Header files under `dir`, as the names an `#include` would use.
+pub fn headers_under(dir : &Path) -> Vec<String> {
+    walkdir::WalkDir::new(dir).into_iter().filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && HEADER_EXTENSIONS.contains(&e.path().extension().unwrap_or_default().to_str().unwrap_or_default()))
+        .map(|e| e.path().strip_prefix(dir).unwrap().to_string_lossy().replace("\\", "/"))
+        .collect()
+}
+
+/// The directories `entry` searches for `#include <...>`, in the order the
+/// compiler does: `-I`, then `-isystem`, then `-idirafter`, each in command
+/// line order; absolute and without repeats.
+pub fn search_dirs(entry : &CompilationEntry) -> Vec<PathBuf> {
+    let mut dirs : [Vec<PathBuf>; 3] = Default::default();
+    let argv = entry.argv();
+    for option in compdb::group_options(argv.get(1..).unwrap_or_default()) {
+        let (kind, dir) = match option {
+            [flag, value] if flag == "-I" => (0, value.as_str()),
+            [flag, value] if flag == "-isystem" => (1, value.as_str()),
+            [flag, value] if flag == "-idirafter" => (2, value.as_str()),
+            [single] if single.starts_with("-isystem") => (1, &single["-isystem".len()..]),
+            [single] if single.starts_with("-idirafter") => (2, &single["-idirafter".len()..]),
+            [single] if single.starts_with("-I") => (0, &single[2..]),
+            _ => continue,
+        };
+        dirs[kind].push(clean(&Path::new(&entry.directory).join(dir)));
+    }
+    let mut seen = HashSet::new();
+    dirs.into_iter().flatten().filter(|dir| seen.insert(dir.clone())).collect()
+}
+
+/// Header names found in several of `dirs`, with the dirs holding each in
+/// search order: the first is the one the compiler picks. `headers` caches
+/// `headers_under` across calls.
+pub fn shadowed_headers(dirs : &[PathBuf], headers : &mut HashMap<PathBuf, Vec<String>>) -> Vec<(String, Vec<PathBuf>)> {
+    let mut holders = HashMap::<String, Vec<PathBuf>>::new();
+    for dir in dirs {
+        for header in headers.entry(dir.clone()).or_insert_with(|| headers_under(dir)).iter() {
+            holders.entry(header.clone()).or_default().push(dir.clone());
+        }
+    }
+    let mut shadowed : Vec<(String, Vec<PathBuf>)> = holders.into_iter().filter(|(_, dirs)| dirs.len() > 1).collect();
+    shadowed.sort();
+    shadowed
+}
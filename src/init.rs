@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use dialoguer::{Confirm, Input, MultiSelect, Select};
+
+use crate::check::find_executable;
+use crate::conf::{CONF_VERSION, CONFIG_FILE_NAMES};
+use crate::generate::{HEADER_EXTENSIONS, walk};
+
+const SOURCE_EXTENSIONS : [&str; 7] = ["c", "cc", "cpp", "cxx", "s", "S", "sx"];
+const INCLUDE_DIR_NAMES : [&str; 4] = ["include", "inc", "Include", "Inc"];
+const PRUNE_DIRS : [&str; 5] = ["build", "out", "target", "node_modules", "CMakeFiles"];
+const C_COMPILERS : [&str; 3] = ["gcc", "clang", "cc"];
+const CPP_COMPILERS : [&str; 3] = ["g++", "clang++", "c++"];
+const DEFAULT_MATCH_PATTERN : &str = r"\.(c|cc|cpp|cxx)$";
+pub const CROSS_COMPILERS : [&str; 5] = ["arm-none-eabi-gcc", "riscv64-unknown-elf-gcc", "riscv32-esp-elf-gcc", "xtensa-esp32-elf-gcc", "avr-gcc"];
+
+/// What `init` found in a directory, turned into a configuration by `render`.
+pub struct Scan {
+    pub c_compiler : String,
+    pub cpp_compiler : String,
+    /// Cross compilers on PATH, suggested in a comment.
+    pub cross_compilers : Vec<String>,
+    /// Top-level header directories, shared by every workspace.
+    pub include_dirs : Vec<String>,
+    pub workspaces : Vec<ScannedWorkspace>,
+    pub match_pattern : String,
+}
+
+pub struct ScannedWorkspace {
+    /// Relative to the scanned directory.
+    pub path : String,
+    pub sources : usize,
+    /// Relative to `path`.
+    pub include_dirs : Vec<String>,
+}
+
+fn has_extension(path : &Path, extensions : &[&str]) -> bool {
+    extensions.contains(&path.extension().unwrap_or_default().to_str().unwrap_or_default())
+}
+
+fn scan_workspace(root : &Path, path : String) -> ScannedWorkspace {
+    let prune_dirs : Vec<String> = PRUNE_DIRS.iter().map(|d| d.to_string()).collect();
+    let mut workspace = ScannedWorkspace { path, sources : 0, include_dirs : Vec::new() };
+    let workspace_dir = root.join(&workspace.path);
+    for entry in walk(&workspace_dir, None, &prune_dirs, false).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if entry.file_type().is_file() && has_extension(&path, &SOURCE_EXTENSIONS) {
+            workspace.sources += 1;
+        } else if entry.file_type().is_dir() && INCLUDE_DIR_NAMES.iter().any(|name| entry.file_name() == *name) {
+            // Nested include dirs (e.g. inc/private) are reached from the outer one.
+            let relative = crate::generate::slashed(path.strip_prefix(&workspace_dir).unwrap());
+            if !workspace.include_dirs.iter().any(|dir| relative.starts_with(&format!("{}/", dir))) {
+                workspace.include_dirs.push(relative);
+            }
+        }
+    }
+    workspace
+}
+
+/// Looks for source directories under `root` and for compilers on PATH.
+pub fn scan(root : &Path) -> Result<Scan, String> {
+    let first_found = |candidates : &[&str]| candidates.iter().find(|c| find_executable(c).is_some()).unwrap_or(&candidates[0]).to_string();
+    let mut scan = Scan {
+        c_compiler : first_found(&C_COMPILERS),
+        cpp_compiler : first_found(&CPP_COMPILERS),
+        cross_compilers : CROSS_COMPILERS.iter().filter(|c| find_executable(c).is_some()).map(|c| c.to_string()).collect(),
+        include_dirs : Vec::new(),
+        workspaces : Vec::new(),
+        match_pattern : DEFAULT_MATCH_PATTERN.into(),
+    };
+
+    let mut top_dirs : Vec<String> = std::fs::read_dir(root).map_err(|e| format!("{}: {}", root.display(), e))?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.') && !PRUNE_DIRS.contains(&name.as_str()))
+        .collect();
+    top_dirs.sort();
+    for dir in top_dirs {
+        let workspace = scan_workspace(root, dir);
+        if workspace.sources > 0 {
+            scan.workspaces.push(workspace);
+        } else if INCLUDE_DIR_NAMES.contains(&workspace.path.as_str()) || walk(&root.join(&workspace.path), None, &[], false).into_iter()
+            .filter_map(|e| e.ok()).any(|e| e.file_type().is_file() && has_extension(&e.path(), &HEADER_EXTENSIONS)) {
+            scan.include_dirs.push(workspace.path);
+        }
+    }
+    if scan.workspaces.is_empty() {
+        let workspace = scan_workspace(root, ".".into());
+        scan.include_dirs.retain(|dir| !workspace.include_dirs.contains(dir));
+        scan.workspaces.push(workspace);
+    }
+    Ok(scan)
+}
+
+fn quoted(strings : &[String]) -> String {
+    toml::Value::Array(strings.iter().map(|s| toml::Value::String(s.clone())).collect()).to_string()
+}
+
+/// The starter configuration for `scan`, with commented examples.
+pub fn render(scan : &Scan) -> String {
+    let mut out = String::new();
+    writeln!(out, "# Starter configuration; see `config print` for what each workspace resolves to.").unwrap();
+    writeln!(out, "version = {}", CONF_VERSION).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "[common]").unwrap();
+    writeln!(out, "c_compiler = {}", quoted(std::slice::from_ref(&scan.c_compiler))).unwrap();
+    writeln!(out, "cpp_compiler = {}", quoted(std::slice::from_ref(&scan.cpp_compiler))).unwrap();
+    for cross in &scan.cross_compilers {
+        writeln!(out, "# c_compiler = [\"{}\", \"-mcpu=...\"]", cross).unwrap();
+    }
+    writeln!(out, "# Relative to this file.").unwrap();
+    writeln!(out, "root_dir = \".\"").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "[common.target]").unwrap();
+    writeln!(out, "# Regexes matched against paths relative to root_dir.").unwrap();
+    if scan.match_pattern.contains('\'') {
+        writeln!(out, "match_pattern = [{}]", toml::Value::String(scan.match_pattern.clone())).unwrap();
+    } else {
+        writeln!(out, "match_pattern = ['{}']", scan.match_pattern).unwrap();
+    }
+    writeln!(out, "# ignore_pattern = ['(^|/)tests?/', '_test\\.c$']").unwrap();
+    writeln!(out, "prune_dirs = {}", quoted(&PRUNE_DIRS.map(String::from))).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "[common.include]").unwrap();
+    match scan.include_dirs.as_slice() {
+        [] => writeln!(out, "# root_dir = [\"include\"]").unwrap(),
+        dirs => writeln!(out, "root_dir = {}", quoted(dirs)).unwrap(),
+    }
+    writeln!(out, "# ignore_pattern = ['/private$']").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "[common.option]").unwrap();
+    writeln!(out, "# arg = [\"-DDEBUG\", \"-Wall\"]").unwrap();
+    for workspace in &scan.workspaces {
+        writeln!(out).unwrap();
+        writeln!(out, "# {} source file(s)", workspace.sources).unwrap();
+        writeln!(out, "[[workspace]]").unwrap();
+        writeln!(out, "path = {}", toml::Value::String(workspace.path.clone())).unwrap();
+        writeln!(out, "# uses = [\"other-workspace\"]").unwrap();
+        if !workspace.include_dirs.is_empty() {
+            writeln!(out, "[workspace.include]").unwrap();
+            writeln!(out, "# Relative to the workspace path.").unwrap();
+            writeln!(out, "root_dir = {}", quoted(&workspace.include_dirs)).unwrap();
+        }
+    }
+    out
+}
+
+/// Source files below each directory of `root`, by path relative to `root`.
+fn count_sources(root : &Path) -> HashMap<PathBuf, usize> {
+    let prune_dirs : Vec<String> = PRUNE_DIRS.iter().map(|d| d.to_string()).collect();
+    let mut counts = HashMap::new();
+    for entry in walk(root, None, &prune_dirs, false).into_iter().filter_map(|e| e.ok()) {
+        if entry.file_type().is_file() && has_extension(&entry.path(), &SOURCE_EXTENSIONS) {
+            for dir in entry.path().strip_prefix(root).unwrap().ancestors().skip(1) {
+                *counts.entry(dir.to_path_buf()).or_insert(0) += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Files of each workspace matching `pattern`, as `scan` would get entries.
+fn preview(root : &Path, workspaces : &[ScannedWorkspace], pattern : &regex::Regex) {
+    let prune_dirs : Vec<String> = PRUNE_DIRS.iter().map(|d| d.to_string()).collect();
+    for workspace in workspaces {
+        let matched = walk(&root.join(&workspace.path), None, &prune_dirs, false).into_iter().filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| pattern.is_match(&crate::generate::slashed(e.path().strip_prefix(root).unwrap())))
+            .count();
+        eprintln!("  {:<32} {} file(s)", workspace.path, matched);
+    }
+}
+
+fn pick_compiler(prompt : &str, candidates : Vec<String>, current : &str) -> Result<String, dialoguer::Error> {
+    let mut items : Vec<String> = candidates.into_iter().filter(|c| find_executable(c).is_some()).collect();
+    if !items.iter().any(|c| c == current) {
+        items.insert(0, current.into());
+    }
+    items.push("other...".into());
+    let picked = Select::new().with_prompt(prompt).items(&items).default(items.iter().position(|c| c == current).unwrap()).interact()?;
+    if picked + 1 < items.len() {
+        return Ok(items[picked].clone());
+    }
+    Input::new().with_prompt(prompt).interact_text()
+}
+
+/// Lets the user adjust `scan`: workspaces picked from the directory tree,
+/// compilers and the target pattern, previewing what it matches.
+fn interact(root : &Path, mut scan : Scan) -> Result<Scan, dialoguer::Error> {
+    let counts = count_sources(root);
+    let mut dirs : Vec<&PathBuf> = counts.keys().filter(|dir| (1..=3).contains(&dir.components().count())).collect();
+    dirs.sort();
+    let items : Vec<(String, bool)> = dirs.iter().map(|dir| {
+        let label = format!("{}{}/ ({} source file(s))", "  ".repeat(dir.components().count() - 1), dir.file_name().unwrap().to_string_lossy(), counts[*dir]);
+        let checked = scan.workspaces.iter().any(|w| Path::new(&w.path) == dir.as_path());
+        (label, checked)
+    }).collect();
+    while !items.is_empty() {
+        let picked = MultiSelect::new().with_prompt("Workspaces (space toggles, enter accepts)").items_checked(items.clone()).interact()?;
+        if picked.is_empty() {
+            eprintln!("Pick at least one workspace");
+            continue;
+        }
+        scan.workspaces = picked.into_iter().map(|i| scan_workspace(root, crate::generate::slashed(dirs[i]))).collect();
+        break;
+    }
+
+    let cross_cpp = CROSS_COMPILERS.iter().filter_map(|c| c.strip_suffix("gcc")).map(|prefix| format!("{}g++", prefix));
+    scan.c_compiler = pick_compiler("C compiler", C_COMPILERS.iter().chain(CROSS_COMPILERS.iter()).map(|c| c.to_string()).collect(), &scan.c_compiler)?;
+    scan.cpp_compiler = pick_compiler("C++ compiler", CPP_COMPILERS.iter().map(|c| c.to_string()).chain(cross_cpp).collect(), &scan.cpp_compiler)?;
+
+    loop {
+        scan.match_pattern = Input::new().with_prompt("Target pattern (regex on paths relative to the root)")
+            .default(scan.match_pattern.clone())
+            .validate_with(|pattern : &String| regex::Regex::new(pattern).map(|_| ()).map_err(|e| e.to_string()))
+            .interact_text()?;
+        preview(root, &scan.workspaces, &regex::Regex::new(&scan.match_pattern).unwrap());
+        if Confirm::new().with_prompt("Write the configuration with this pattern?").default(true).interact()? {
+            return Ok(scan);
+        }
+    }
+}
+
+/// Writes a starter configuration for the current directory, after asking
+/// the user to adjust it when `interactive`.
+pub fn init(force : bool, interactive : bool) -> Result<(), String> {
+    let output = Path::new(CONFIG_FILE_NAMES[0]);
+    if output.exists() && !force {
+        return Err(format!("{} already exists; pass --force to overwrite it", output.display()));
+    }
+    let root = Path::new(".");
+    let mut scan = scan(root)?;
+    if interactive {
+        if !std::io::IsTerminal::is_terminal(&std::io::stderr()) {
+            return Err("--interactive needs a terminal".into());
+        }
+        scan = interact(root, scan).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(output, render(&scan)).map_err(|e| format!("{}: {}", output.display(), e))?;
+    eprintln!("wrote {} with {} workspace(s)", output.display(), scan.workspaces.len());
+    Ok(())
+}
@@ -0,0 +1,165 @@
+//! Records real compiler invocations during a build. `run` puts wrappers
+//! named like the usual compilers, the known cross compilers and the ones
+//! asked for in front of `PATH`; the wrappers are this executable, which
+//! logs the invocation and then runs the real compiler.
+
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::compdb::CompilationEntry;
+use crate::generate::slashed;
+use crate::import;
+
+const LOG_ENV : &str = "COMPDB_INTERCEPT_LOG";
+const PATH_ENV : &str = "COMPDB_INTERCEPT_PATH";
+/// The names of the wrapped compilers, `;`-separated.
+const COMPILERS_ENV : &str = "COMPDB_INTERCEPT_COMPILERS";
+
+const WRAPPED_COMPILERS : [&str; 6] = ["cc", "c++", "gcc", "g++", "clang", "clang++"];
+
+/// The compilers `run` wraps: the usual ones, the cross compilers `init`
+/// looks for with their C++ drivers, and the file names of `extra`.
+fn wrapped_compilers(extra : &[String]) -> Vec<String> {
+    let cross = crate::init::CROSS_COMPILERS.iter().flat_map(|gcc| [gcc.to_string(), format!("{}g++", gcc.trim_end_matches("gcc"))]);
+    let extra = extra.iter().filter_map(|compiler| {
+        let name = Path::new(compiler).file_name()?.to_str()?;
+        Some(name.strip_suffix(std::env::consts::EXE_SUFFIX).unwrap_or(name).to_string())
+    });
+    let mut compilers = Vec::<String>::new();
+    for compiler in WRAPPED_COMPILERS.iter().map(|c| c.to_string()).chain(cross).chain(extra) {
+        if !compilers.contains(&compiler) {
+            compilers.push(compiler);
+        }
+    }
+    compilers
+}
+
+#[derive(Serialize, Deserialize)]
+struct Invocation {
+    directory : String,
+    arguments : Vec<String>,
+}
+
+/// The first `program` on `path`; on Windows also with the extensions of
+/// `PATHEXT`, as the shell would find it.
+fn find_in_path(program : &str, path : &OsString) -> Option<PathBuf> {
+    let extensions : Vec<String> = match cfg!(windows) {
+        true => std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into()).split(';').filter(|e| !e.is_empty()).map(String::from).collect(),
+        false => Vec::new(),
+    };
+    std::env::split_paths(path)
+        .flat_map(|dir| std::iter::once(dir.join(program)).chain(extensions.iter().map(move |extension| dir.join(format!("{}{}", program, extension)))).collect::<Vec<_>>())
+        .find(|candidate| candidate.is_file())
+}
+
+/// When this process was started as one of the wrappers, logs the
+/// invocation, runs the real compiler and returns its exit code.
+pub fn wrapper_main() -> Option<i32> {
+    let log = std::env::var_os(LOG_ENV)?;
+    let original_path = std::env::var_os(PATH_ENV)?;
+    let compilers = std::env::var(COMPILERS_ENV).ok()?;
+    // Arguments are passed on as they are; only the log gets them lossily.
+    let mut args = std::env::args_os();
+    // Without the suffix, since the wrappers are `cc.exe` and so on on Windows.
+    let name = Path::new(&args.next()?).file_name()?.to_str()?.to_string();
+    let program = name.strip_suffix(std::env::consts::EXE_SUFFIX).unwrap_or(&name).to_string();
+    if !compilers.split(';').any(|compiler| compiler == program) {
+        return None;
+    }
+    let compiler_args : Vec<OsString> = args.collect();
+
+    let invocation = Invocation {
+        directory : std::env::current_dir().map(|d| slashed(&d)).unwrap_or_default(),
+        arguments : std::iter::once(program.clone()).chain(compiler_args.iter().map(|arg| arg.to_string_lossy().into_owned())).collect(),
+    };
+    if let Ok(mut log_file) = std::fs::OpenOptions::new().create(true).append(true).open(&log) {
+        let _ = log_file.write_all(format!("{}\n", serde_json::to_string(&invocation).unwrap()).as_bytes());
+    }
+
+    let Some(real_compiler) = find_in_path(&program, &original_path) else {
+        eprintln!("intercept: {} not found in PATH", program);
+        return Some(127);
+    };
+    match Command::new(real_compiler).args(&compiler_args).env("PATH", &original_path).status() {
+        Ok(status) => Some(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("intercept: {}: {}", program, e);
+            Some(126)
+        }
+    }
+}
+
+fn install_wrappers(wrapper_dir : &Path, compilers : &[String]) -> Result<(), String> {
+    let this_exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(wrapper_dir).map_err(|e| format!("{}: {}", wrapper_dir.display(), e))?;
+    for compiler in compilers {
+        let wrapper = wrapper_dir.join(format!("{}{}", compiler, std::env::consts::EXE_SUFFIX));
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&this_exe, &wrapper).map_err(|e| format!("{}: {}", wrapper.display(), e))?;
+        #[cfg(not(unix))]
+        std::fs::copy(&this_exe, &wrapper).map_err(|e| format!("{}: {}", wrapper.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Runs `build` with the wrappers installed and returns an entry per
+/// recorded compilation. `compilers` are wrapped on top of the usual ones.
+pub fn run(build : &[String], compilers : &[String]) -> Result<Vec<CompilationEntry>, String> {
+    let (program, build_args) = build.split_first().ok_or("A build command is required")?;
+
+    let work_dir = std::env::temp_dir().join(format!("compdb-intercept-{}", std::process::id()));
+    let wrapper_dir = work_dir.join("bin");
+    let log = work_dir.join("invocations.jsonl");
+    let compilers = wrapped_compilers(compilers);
+    install_wrappers(&wrapper_dir, &compilers)?;
+
+    let original_path = std::env::var_os("PATH").unwrap_or_default();
+    let wrapped_path = std::env::join_paths(std::iter::once(wrapper_dir.clone()).chain(std::env::split_paths(&original_path))).map_err(|e| e.to_string())?;
+    let status = Command::new(program).args(build_args)
+        .env("PATH", wrapped_path)
+        .env(PATH_ENV, &original_path)
+        .env(LOG_ENV, &log)
+        .env(COMPILERS_ENV, compilers.join(";"))
+        .env("CC", wrapper_dir.join(format!("cc{}", std::env::consts::EXE_SUFFIX)))
+        .env("CXX", wrapper_dir.join(format!("c++{}", std::env::consts::EXE_SUFFIX)))
+        .status();
+
+    let recorded = std::fs::read_to_string(&log).unwrap_or_default();
+    let _ = std::fs::remove_dir_all(&work_dir);
+    let status = status.map_err(|e| format!("{}: {}", program, e))?;
+    if !status.success() {
+        crate::diag::warn(&format!("build command exited with {}", status));
+    }
+
+    let mut entries = Vec::<CompilationEntry>::new();
+    for line in recorded.lines() {
+        let invocation : Invocation = serde_json::from_str(line).map_err(|e| e.to_string())?;
+        entries.extend(import::entry_from_invocation(Path::new(&invocation.directory), invocation.arguments));
+    }
+    if entries.is_empty() {
+        crate::diag::warn(&format!(
+            "the build compiled nothing through the wrapped compilers ({}); name others with --compiler, and rebuild from clean if the build was up to date",
+            compilers.join(", "),
+        ));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::wrapped_compilers;
+
+    #[test]
+    fn wraps_cross_compilers_and_the_ones_asked_for_once() {
+        let compilers = wrapped_compilers(&["/opt/ti/bin/armcl".into(), "gcc".into(), format!("tricore-gcc{}", std::env::consts::EXE_SUFFIX)]);
+        assert_eq!(compilers[..6], ["cc", "c++", "gcc", "g++", "clang", "clang++"]);
+        assert!(compilers.contains(&"arm-none-eabi-gcc".to_string()) && compilers.contains(&"arm-none-eabi-g++".to_string()));
+        assert_eq!(compilers[compilers.len() - 2..], ["armcl", "tricore-gcc"]);
+        assert_eq!(compilers.iter().filter(|c| *c == "gcc").count(), 1);
+    }
+}
@@ -0,0 +1,27 @@
+//! Generates a JSON Compilation Database from a configuration of workspaces,
+//! include roots and options, or imports one from other build systems.
+//! [`cli::main`] is the command line; [`plugin`] lets other crates add
+//! formats to it.
+
+mod check;
+pub mod cli;
+pub mod compdb;
+mod conf;
+mod db;
+mod diag;
+mod export;
+mod generate;
+mod graph;
+mod hooks;
+mod import;
+mod includes;
+mod init;
+mod intercept;
+mod packages;
+mod pkg_config;
+pub mod plugin;
+mod run;
+mod sarif;
+mod serve;
+mod shell;
+mod watch;
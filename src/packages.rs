@@ -0,0 +1,115 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::conf::PackageConf;
+use crate::generate::slashed;
+
+#[derive(Deserialize)]
+struct ConanBuildInfo {
+    #[serde(default)]
+    dependencies : Vec<ConanDependency>,
+}
+
+#[derive(Deserialize)]
+struct ConanDependency {
+    #[serde(default)]
+    include_paths : Vec<String>,
+    #[serde(default)]
+    defines : Vec<String>,
+}
+
+/// The `-I`/`-D` flags of the dependencies in `conanbuildinfo.json`,
+/// written by Conan 1's `json` generator.
+fn conan_build_info_flags(build_info : &Path) -> Result<Vec<String>, String> {
+    let content = std::fs::read_to_string(build_info).map_err(|e| format!("{}: {}", build_info.display(), e))?;
+    let build_info_json : ConanBuildInfo = serde_json::from_str(&content).map_err(|e| format!("{}: {}", build_info.display(), e))?;
+
+    let mut flags = Vec::<String>::new();
+    for dependency in build_info_json.dependencies {
+        flags.extend(dependency.include_paths.into_iter().map(|p| format!("-I{}", p)));
+        flags.extend(dependency.defines.into_iter().map(|d| format!("-D{}", d)));
+    }
+    Ok(flags)
+}
+
+/// The `-I`/`-D` flags of the dependencies in the `<package>-*-data.cmake`
+/// files Conan 2's `CMakeDeps` generator wrote to `generators_dir`: the
+/// values of its `<package>_INCLUDE_DIRS_<CONFIG>` and
+/// `<package>_COMPILE_DEFINITIONS_<CONFIG>` variables.
+fn cmake_deps_flags(generators_dir : &Path) -> Result<Vec<String>, String> {
+    let mut data_files : Vec<_> = std::fs::read_dir(generators_dir).map_err(|e| format!("{}: {}", generators_dir.display(), e))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with("-data.cmake")))
+        .collect();
+    if data_files.is_empty() {
+        return Err(format!("{}: no *-data.cmake files; run conan install with -g CMakeDeps, or point packages.conan at a conanbuildinfo.json (Conan 1, -g json)", generators_dir.display()));
+    }
+    data_files.sort();
+
+    let set = regex::Regex::new(r"(?ms)^\s*set\((\w+)(.*?)\)\s*$").unwrap();
+    let value = regex::Regex::new(r#""([^"]*)"|(\S+)"#).unwrap();
+    let variable = regex::Regex::new(r"\$\{(\w+)\}").unwrap();
+    let include_dirs = regex::Regex::new(r"_INCLUDE_DIRS_[A-Z]+$").unwrap();
+    let definitions = regex::Regex::new(r"_COMPILE_DEFINITIONS_[A-Z]+$").unwrap();
+
+    let mut flags = Vec::<String>::new();
+    for data_file in data_files {
+        let content = std::fs::read_to_string(&data_file).map_err(|e| format!("{}: {}", data_file.display(), e))?;
+        let mut variables = HashMap::<String, String>::new();
+        for captures in set.captures_iter(&content) {
+            let values : Vec<String> = value.captures_iter(&captures[2])
+                .map(|v| v.get(1).or(v.get(2)).unwrap().as_str())
+                .map(|v| variable.replace_all(v, |r : &regex::Captures| variables.get(&r[1]).cloned().unwrap_or_default()).into_owned())
+                .filter(|v| !v.is_empty())
+                .collect();
+            let name = &captures[1];
+            if include_dirs.is_match(name) {
+                flags.extend(values.iter().map(|dir| format!("-I{}", dir)));
+            } else if definitions.is_match(name) {
+                flags.extend(values.iter().map(|define| format!("-D{}", define)));
+            }
+            variables.insert(name.to_string(), values.join(";"));
+        }
+    }
+    let mut seen = HashSet::new();
+    flags.retain(|flag| seen.insert(flag.clone()));
+    Ok(flags)
+}
+
+/// Flags from `conan`: a `conanbuildinfo.json`, or the generators folder
+/// of Conan 2 (where `conan_toolchain.cmake` is) holding `CMakeDeps` output.
+fn conan_flags(conan : &Path) -> Result<Vec<String>, String> {
+    if conan.is_dir() {
+        return cmake_deps_flags(conan);
+    }
+    match conan.file_name().and_then(|n| n.to_str()) {
+        Some("conan_toolchain.cmake") => cmake_deps_flags(conan.parent().unwrap_or(Path::new("."))),
+        Some(name) if name.ends_with(".json") => conan_build_info_flags(conan),
+        _ => Err(format!("{}: packages.conan must be a conanbuildinfo.json (Conan 1, -g json) or the folder of Conan 2's CMakeDeps output", conan.display())),
+    }
+}
+
+fn vcpkg_flags(installed : &Path) -> Result<Vec<String>, String> {
+    let include = installed.join("include");
+    if !include.is_dir() {
+        return Err(format!("{}: not a vcpkg installed tree (no include directory)", installed.display()));
+    }
+    Ok(vec![format!("-I{}", slashed(&include))])
+}
+
+/// `-I`/`-D` flags contributed by the package managers in `packages`.
+pub fn flags(common_root : &Path, packages : &[PackageConf]) -> Result<Vec<String>, String> {
+    let mut flags = Vec::<String>::new();
+    for package in packages {
+        if let Some(conan) = &package.conan {
+            flags.extend(conan_flags(&common_root.join(conan))?);
+        }
+        if let Some(vcpkg) = &package.vcpkg {
+            flags.extend(vcpkg_flags(&common_root.join(vcpkg))?);
+        }
+    }
+    Ok(flags)
+}
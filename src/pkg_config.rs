@@ -0,0 +1,22 @@
+use std::process::Command;
+
+use crate::shell;
+
+/// Runs `pkg-config --cflags` for `packages` and keeps the `-I` and `-D`
+/// flags, which are the ones that matter to a compilation database.
+pub fn cflags(packages : &[String]) -> Result<Vec<String>, String> {
+    if packages.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let output = Command::new("pkg-config").arg("--cflags").args(packages).output().map_err(|e| format!("pkg-config: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("pkg-config --cflags {}: {}", packages.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    // Quoted and escaped like a shell command line, e.g. `-I/opt/my\ sdk`.
+    Ok(shell::split(&String::from_utf8_lossy(&output.stdout))
+        .into_iter()
+        .filter(|f| f.starts_with("-I") || f.starts_with("-D"))
+        .collect())
+}
@@ -0,0 +1,55 @@
+//! Extension points for project-file formats this crate does not know.
+//! A downstream binary registers its importers and exporters and hands the
+//! registry to [`crate::cli::main`]; they are then available as
+//! `import plugin <name>` and `generate --exporter <name>`.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::compdb::CompilationEntry;
+
+/// Turns a project description (a file or a directory) into compilation
+/// entries.
+pub trait Importer {
+    fn import(&self, project : &Path) -> Result<Vec<CompilationEntry>, String>;
+}
+
+/// Writes the generated entries in another format than a JSON Compilation
+/// Database.
+pub trait Exporter {
+    fn export(&self, output : &Path, entries : &[CompilationEntry]) -> Result<(), String>;
+}
+
+/// Importers and exporters by the name they are selected with.
+#[derive(Default)]
+pub struct Registry {
+    importers : BTreeMap<String, Box<dyn Importer>>,
+    exporters : BTreeMap<String, Box<dyn Exporter>>,
+}
+
+impl Registry {
+    /// Adds `importer` as `name`, replacing an importer of that name.
+    pub fn register_importer(&mut self, name : &str, importer : Box<dyn Importer>) -> &mut Self {
+        self.importers.insert(name.into(), importer);
+        self
+    }
+
+    /// Adds `exporter` as `name`, replacing an exporter of that name.
+    pub fn register_exporter(&mut self, name : &str, exporter : Box<dyn Exporter>) -> &mut Self {
+        self.exporters.insert(name.into(), exporter);
+        self
+    }
+
+    pub fn importer(&self, name : &str) -> Result<&dyn Importer, String> {
+        self.importers.get(name).map(Box::as_ref).ok_or_else(|| format!("No importer \"{}\" is registered{}", name, known(self.importers.keys())))
+    }
+
+    pub fn exporter(&self, name : &str) -> Result<&dyn Exporter, String> {
+        self.exporters.get(name).map(Box::as_ref).ok_or_else(|| format!("No exporter \"{}\" is registered{}", name, known(self.exporters.keys())))
+    }
+}
+
+fn known<'a>(names : impl Iterator<Item = &'a String>) -> String {
+    let names : Vec<&str> = names.map(String::as_str).collect();
+    if names.is_empty() { String::new() } else { format!(" (known: {})", names.join(", ")) }
+}
@@ -0,0 +1,161 @@
+//! Source analysis tools run over the generated entries.
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::check;
+use crate::compdb::CompilationEntry;
+use crate::sarif;
+
+/// The number of jobs when `--jobs` is not given: one per CPU.
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// `f` applied to every item on `jobs` threads, the results in the order of
+/// `items`.
+pub fn parallel<T : Sync, R : Send>(items : &[T], jobs : usize, f : impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let mut results : Vec<(usize, R)> = std::thread::scope(|scope| {
+        let workers : Vec<_> = (0..jobs.clamp(1, items.len().max(1))).map(|_| scope.spawn(|| {
+            let mut done = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(i) else { break };
+                done.push((i, f(item)));
+            }
+            done
+        })).collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// What include-what-you-use suggests for one file: the source or one of
+/// the headers it is associated with.
+pub struct IwyuSuggestion {
+    pub file : String,
+    /// `#include` lines to add, with IWYU's `// for` comment.
+    pub add : Vec<String>,
+    /// `#include` lines to remove, with the lines they are on.
+    pub remove : Vec<String>,
+}
+
+/// Runs `program` (include-what-you-use) on `entry` with `mapping_files`
+/// and returns its suggestions, the files already right left out. An
+/// output IWYU did not produce a report in is an error.
+pub fn iwyu(program : &str, mapping_files : &[String], entry : &CompilationEntry) -> Result<Vec<IwyuSuggestion>, String> {
+    let argv = check::analysis_argv(entry);
+    let mut command = Command::new(program);
+    command.args(argv.get(1..).unwrap_or_default()).current_dir(&entry.directory);
+    for mapping_file in mapping_files {
+        command.args(["-Xiwyu", &format!("--mapping_file={}", mapping_file)]);
+    }
+    let output = command.output().map_err(|e| format!("{}: {}", program, e))?;
+    // IWYU reports on stderr, newer versions on stdout.
+    let text = String::from_utf8_lossy(&output.stderr).into_owned() + &String::from_utf8_lossy(&output.stdout);
+
+    let mut suggestions = Vec::<IwyuSuggestion>::new();
+    let mut reported = false;
+    let mut section = None;
+    for line in text.lines() {
+        if let Some(file) = line.strip_suffix(" should add these lines:") {
+            reported = true;
+            section = Some(true);
+            if suggestions.last().is_none_or(|last| last.file != file) {
+                suggestions.push(IwyuSuggestion { file : file.to_string(), add : Vec::new(), remove : Vec::new() });
+            }
+        } else if let Some(file) = line.strip_suffix(" should remove these lines:") {
+            reported = true;
+            section = Some(false);
+            if suggestions.last().is_none_or(|last| last.file != file) {
+                suggestions.push(IwyuSuggestion { file : file.to_string(), add : Vec::new(), remove : Vec::new() });
+            }
+        } else if line.ends_with(" has correct #includes/fwd-decls)") {
+            reported = true;
+        } else if line.trim().is_empty() || line.starts_with("The full include-list for ") || line == "---" {
+            section = None;
+        } else if let (Some(add), Some(last)) = (section, suggestions.last_mut()) {
+            match add {
+                true => last.add.push(line.to_string()),
+                false => last.remove.push(line.trim_start_matches("- ").to_string()),
+            }
+        }
+    }
+    if !reported {
+        return Err(format!("{}: no report from {} ({})\n{}", entry.path().display(), program, output.status, text.trim_end()).trim_end().into());
+    }
+    suggestions.retain(|suggestion| !suggestion.add.is_empty() || !suggestion.remove.is_empty());
+    Ok(suggestions)
+}
+
+/// How `run clang-tidy` reports its diagnostics.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// clang-tidy's own lines, then a summary per workspace.
+    Text,
+    /// An array of diagnostics.
+    Json,
+    /// A SARIF 2.1.0 log, for code scanning services.
+    Sarif,
+}
+
+/// A diagnostic of clang-tidy, notes left out.
+#[derive(serde::Serialize, Clone, PartialEq, Eq, Hash)]
+pub struct TidyDiagnostic {
+    pub workspace : String,
+    pub file : String,
+    pub line : u64,
+    pub column : u64,
+    /// `warning` or `error`.
+    pub severity : String,
+    pub message : String,
+    /// The check, e.g. `bugprone-narrowing-conversions`, or
+    /// `clang-diagnostic-error` for compiler errors.
+    pub check : String,
+}
+
+impl TidyDiagnostic {
+    pub fn to_finding(&self) -> sarif::Finding {
+        sarif::Finding {
+            rule : self.check.clone(),
+            level : self.severity.clone(),
+            message : self.message.clone(),
+            file : Some(self.file.clone()),
+            line : Some(self.line),
+            column : Some(self.column),
+        }
+    }
+}
+
+/// Runs `program` (clang-tidy) once over `files`, with the database in
+/// `db_dir` and `checks` if any. A file failing to compile is a diagnostic,
+/// not an error.
+pub fn clang_tidy(program : &str, checks : Option<&str>, db_dir : &Path, workspace : &str, files : &[String]) -> Result<Vec<TidyDiagnostic>, String> {
+    let mut command = Command::new(program);
+    command.arg("-p").arg(db_dir).arg("--quiet");
+    if let Some(checks) = checks {
+        command.arg(format!("--checks={}", checks));
+    }
+    let output = command.args(files).output().map_err(|e| format!("{}: {}", program, e))?;
+
+    let diagnostic = regex::Regex::new(r"^(.+?):(\d+):(\d+): (warning|error): (.*?)(?: \[([^\]]+)\])?$").unwrap();
+    let diagnostics : Vec<TidyDiagnostic> = String::from_utf8_lossy(&output.stdout).lines().filter_map(|line| {
+        let captures = diagnostic.captures(line)?;
+        Some(TidyDiagnostic {
+            workspace : workspace.to_string(),
+            file : captures[1].to_string(),
+            line : captures[2].parse().ok()?,
+            column : captures[3].parse().ok()?,
+            severity : captures[4].to_string(),
+            message : captures[5].to_string(),
+            check : captures.get(6).map_or("clang-diagnostic-error", |check| check.as_str()).to_string(),
+        })
+    }).collect();
+    if diagnostics.is_empty() && !output.status.success() {
+        return Err(format!("{} ({}): {}", program, output.status, String::from_utf8_lossy(&output.stderr).trim_end()));
+    }
+    Ok(diagnostics)
+}
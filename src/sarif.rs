@@ -0,0 +1,126 @@
+//! SARIF 2.1.0 logs, the format code scanning services (GitHub, GitLab,
+//! Azure DevOps) show findings from.
+
+use serde_json::{Value, json};
+
+/// One finding: a diagnostic at a place in a file, about a whole file when
+/// `line` is missing, or about the configuration when `file` is.
+pub struct Finding {
+    /// The check or kind of problem, e.g. `bugprone-macro-parentheses`.
+    pub rule : String,
+    /// `error`, `warning` or `note`.
+    pub level : String,
+    pub message : String,
+    pub file : Option<String>,
+    pub line : Option<u64>,
+    pub column : Option<u64>,
+}
+
+/// The SARIF run of `tool` that produced `findings`.
+pub fn run(tool : &str, findings : &[Finding]) -> Value {
+    let mut rules = Vec::<&str>::new();
+    for finding in findings {
+        if !rules.contains(&finding.rule.as_str()) {
+            rules.push(&finding.rule);
+        }
+    }
+    let results : Vec<Value> = findings.iter().map(|finding| {
+        let mut result = json!({
+            "ruleId" : finding.rule,
+            "ruleIndex" : rules.iter().position(|rule| *rule == finding.rule),
+            "level" : finding.level,
+            "message" : { "text" : console::strip_ansi_codes(&finding.message) },
+        });
+        if let Some(file) = &finding.file {
+            let mut location = json!({ "artifactLocation" : { "uri" : uri(file) } });
+            let mut region = serde_json::Map::new();
+            if let Some(line) = finding.line {
+                region.insert("startLine".into(), line.into());
+            }
+            if let Some(column) = finding.column {
+                region.insert("startColumn".into(), column.into());
+            }
+            if !region.is_empty() {
+                location["region"] = region.into();
+            }
+            result["locations"] = json!([{ "physicalLocation" : location }]);
+        }
+        result
+    }).collect();
+    json!({
+        "tool" : { "driver" : {
+            "name" : tool,
+            "rules" : rules.iter().map(|rule| json!({ "id" : rule })).collect::<Vec<_>>(),
+        } },
+        "results" : results,
+    })
+}
+
+/// The `file:line:column: error: message` lines of gcc's or clang's
+/// `output`, run from `directory`, as findings of `rule`, or all of it as
+/// one finding about `file` when there is no such line.
+pub fn compiler_findings(output : &str, rule : &str, directory : &str, file : &str) -> Vec<Finding> {
+    let diagnostic = regex::Regex::new(r"^(.+?):(\d+):(\d+): (?:fatal )?(error|warning): (.*)$").unwrap();
+    let findings : Vec<Finding> = output.lines().filter_map(|line| {
+        let captures = diagnostic.captures(line)?;
+        Some(Finding {
+            rule : rule.into(),
+            level : captures[4].into(),
+            message : captures[5].into(),
+            file : Some(crate::generate::slashed(&std::path::Path::new(directory).join(&captures[1]))),
+            line : captures[2].parse().ok(),
+            column : captures[3].parse().ok(),
+        })
+    }).collect();
+    match findings.is_empty() {
+        true => vec![Finding { rule : rule.into(), level : "error".into(), message : output.into(), file : Some(file.into()), line : None, column : None }],
+        false => findings,
+    }
+}
+
+/// The run of this tool holding the warnings printed so far and `error`,
+/// the one failing the command if any.
+pub fn own_run(error : Option<&str>) -> Value {
+    let mut findings : Vec<Finding> = crate::diag::warnings().into_iter().map(|(kind, message)| Finding {
+        rule : kind.into(),
+        level : "warning".into(),
+        message,
+        file : None,
+        line : None,
+        column : None,
+    }).collect();
+    findings.extend(error.map(|message| Finding { rule : "error".into(), level : "error".into(), message : message.into(), file : None, line : None, column : None }));
+    run(env!("CARGO_PKG_NAME"), &findings)
+}
+
+/// The SARIF log of `runs`.
+pub fn log(runs : Vec<Value>) -> Value {
+    json!({
+        "$schema" : "https://json.schemastore.org/sarif-2.1.0.json",
+        "version" : "2.1.0",
+        "runs" : runs,
+    })
+}
+
+/// Writes the SARIF log of `runs` to `path`, or to stdout when it is `-`.
+pub fn write(path : &str, runs : Vec<Value>) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(&log(runs)).unwrap() + "\n";
+    match path {
+        "-" => {
+            print!("{}", text);
+            Ok(())
+        }
+        path => std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e)),
+    }
+}
+
+/// `path` as a URI: absolute paths get the `file://` scheme, relative ones
+/// stay relative to where the log is read; spaces and `%` are escaped.
+fn uri(path : &str) -> String {
+    let path = path.replace('\\', "/").replace('%', "%25").replace(' ', "%20");
+    match path.as_bytes() {
+        [b'/', ..] => format!("file://{}", path),
+        [drive, b':', ..] if drive.is_ascii_alphabetic() => format!("file:///{}", path),
+        _ => path,
+    }
+}
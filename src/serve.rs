@@ -0,0 +1,173 @@
+//! A small HTTP server answering `GET /flags?file=<path>` with the entry of
+//! a file as JSON, so tools can ask for one file's flags instead of reading
+//! the whole database. The entries are kept in memory and regenerated by a
+//! filesystem watcher when a configuration file changes or files come and
+//! go under the root, and when a file asked for is newer than them.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use crate::compdb::CompilationEntry;
+use crate::db::clean;
+use crate::watch;
+
+/// How often the watcher looks for changes.
+const WATCH_INTERVAL : Duration = Duration::from_secs(1);
+
+/// How many connections are answered at once.
+const WORKERS : usize = 4;
+
+/// How long a client may take to send its request or read the answer, so a
+/// stalled one only holds its worker for that long.
+const CLIENT_TIMEOUT : Duration = Duration::from_secs(5);
+
+/// The entries by cleaned absolute path, and where relative request paths start.
+struct Model {
+    root_dir : PathBuf,
+    entries : HashMap<PathBuf, CompilationEntry>,
+    generated : SystemTime,
+}
+
+fn modified(path : &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Serves on `listen` until killed, `WORKERS` connections at a time. `load`
+/// generates the entries and tells the root directory.
+pub fn run(listen : &str, config_files : &[String], load : impl Fn() -> Result<(PathBuf, Vec<CompilationEntry>), String> + Sync) -> Result<(), String> {
+    let reload = || -> Result<Model, String> {
+        let generated = SystemTime::now();
+        let (root_dir, entries) = load()?;
+        tracing::info!(entries = entries.len(), "entries generated");
+        let mut by_path = HashMap::new();
+        for entry in entries {
+            by_path.entry(clean(&entry.path())).or_insert(entry);
+        }
+        Ok(Model { root_dir, entries : by_path, generated })
+    };
+    let model = reload()?;
+    let listener = TcpListener::bind(listen).map_err(|e| format!("{}: {}", listen, e))?;
+    eprintln!("serving {} entries on http://{}/flags?file=<path>", model.entries.len(), listen);
+    let watched_roots = vec![model.root_dir.clone()];
+    let model = RwLock::new(model);
+    // A few workers answer the connections the listener queues for them;
+    // accepting waits while they are all busy.
+    let (sender, receiver) = std::sync::mpsc::sync_channel::<TcpStream>(WORKERS);
+    let receiver = Mutex::new(receiver);
+
+    std::thread::scope(|scope| {
+        let (model, reload, receiver) = (&model, &reload, &receiver);
+        scope.spawn(move || {
+            let config_files : Vec<PathBuf> = config_files.iter().map(PathBuf::from).collect();
+            watch::watch(&config_files, &watched_roots, WATCH_INTERVAL, || match reload() {
+                Ok(reloaded) => *model.write().unwrap() = reloaded,
+                // The previous entries stay served.
+                Err(message) => crate::diag::warn(&message),
+            })
+        });
+        for _ in 0..WORKERS {
+            scope.spawn(move || loop {
+                let Ok(stream) = receiver.lock().unwrap().recv() else {
+                    break;
+                };
+                respond(stream, model, reload);
+            });
+        }
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if sender.send(stream).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+fn respond(mut stream : TcpStream, model : &RwLock<Model>, reload : &impl Fn() -> Result<Model, String>) {
+    if let Err(e) = stream.set_read_timeout(Some(CLIENT_TIMEOUT)).and_then(|_| stream.set_write_timeout(Some(CLIENT_TIMEOUT))) {
+        tracing::warn!(error = %e, "connection dropped");
+        return;
+    }
+    let (status, body) = match request_target(&stream) {
+        Some(target) => answer(&target, model, reload),
+        None => ("400 Bad Request", error("malformed request")),
+    };
+    let response = format!("HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body);
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        tracing::warn!(error = %e, "response not sent");
+    }
+}
+
+/// The target of a `GET` request, e.g. `/flags?file=src/main.c`.
+fn request_target(stream : &TcpStream) -> Option<String> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok()?;
+    // The headers are not needed, but must be read before answering.
+    let mut header = String::new();
+    while reader.read_line(&mut header).ok()? > 2 {
+        header.clear();
+    }
+    match request_line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["GET", target, _] => Some(target.into()),
+        _ => None,
+    }
+}
+
+fn answer(target : &str, model : &RwLock<Model>, reload : &impl Fn() -> Result<Model, String>) -> (&'static str, String) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path != "/flags" {
+        return ("404 Not Found", error("only /flags?file=<path> is served"));
+    }
+    let Some(file) = query.split('&').filter_map(|pair| pair.split_once('=')).find(|(key, _)| *key == "file").map(|(_, value)| percent_decode(value)) else {
+        return ("400 Bad Request", error("the file parameter is missing"));
+    };
+    let (file, new_file) = {
+        let model = model.read().unwrap();
+        let file = clean(&model.root_dir.join(&file));
+        // A file created since the generation may be a new target the
+        // watcher has not seen yet.
+        let new_file = !model.entries.contains_key(&file) && modified(&file).is_some_and(|m| m > model.generated);
+        (file, new_file)
+    };
+    if new_file {
+        match reload() {
+            Ok(reloaded) => *model.write().unwrap() = reloaded,
+            Err(message) => return ("500 Internal Server Error", error(&message)),
+        }
+    }
+    match model.read().unwrap().entries.get(&file) {
+        Some(entry) => ("200 OK", serde_json::to_string(entry).unwrap()),
+        None => ("404 Not Found", error(&format!("no entry for {}", file.display()))),
+    }
+}
+
+fn error(message : &str) -> String {
+    serde_json::json!({ "error" : message }).to_string()
+}
+
+/// `value` with `+` and `%XX` escapes of a query string decoded.
+fn percent_decode(value : &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = value.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'+', _) => decoded.push(b' '),
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 2;
+            }
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
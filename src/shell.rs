@@ -0,0 +1,101 @@
+/// Splits a command line into words the way a POSIX shell would, honouring
+/// single quotes, double quotes and backslash escapes. No expansion is done.
+pub fn split(command : &str) -> Vec<String> {
+    split_commands(command, false).pop().unwrap_or_default()
+}
+
+/// The words of each command of a shell list, like `cd src && cc -c a.c;
+/// true`: the commands end at the `;`, `&&`, `||`, `&` and `|` outside quotes.
+pub fn split_list(line : &str) -> Vec<Vec<String>> {
+    split_commands(line, true)
+}
+
+fn split_commands(command : &str, list : bool) -> Vec<Vec<String>> {
+    let mut commands = Vec::<Vec<String>>::new();
+    let mut words = Vec::<String>::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    word.push(c);
+                }
+            }
+            '"' => {
+                in_word = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(n @ ('"' | '\\' | '$' | '`')) => word.push(n),
+                            Some(n) => { word.push('\\'); word.push(n); }
+                            None => word.push('\\'),
+                        },
+                        _ => word.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(n) = chars.next() {
+                    word.push(n);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            // `2>&1` redirects, it does not end the command.
+            ';' | '&' | '|' if list && !(c == '&' && word.ends_with(['<', '>'])) => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+                if !words.is_empty() {
+                    commands.push(std::mem::take(&mut words));
+                }
+            }
+            _ => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        commands.push(words);
+    }
+
+    commands
+}
+
+/// Joins `words` into a command line `split` turns back into them, double
+/// quoting the words that need it.
+pub fn join(words : &[String]) -> String {
+    words.iter().map(|word| {
+        if !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || "-_=+./:,@%".contains(c)) {
+            word.clone()
+        } else {
+            let mut quoted = String::from('"');
+            for c in word.chars() {
+                if matches!(c, '"' | '\\' | '$' | '`') {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+            quoted.push('"');
+            quoted
+        }
+    }).collect::<Vec<_>>().join(" ")
+}
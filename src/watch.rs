@@ -0,0 +1,69 @@
+//! A polling filesystem watcher: the modification times of some files and
+//! of every directory below some roots, compared at an interval. A
+//! directory's time changes when a file is added, removed or renamed in it,
+//! which is what changes the generated entries; edits inside sources do not.
+//! The files are cheap to look at and are polled at every interval; the
+//! walk below the roots backs off while nothing changes.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// The longest the walk below the roots waits, after it found nothing for a while.
+const MAX_WALK_INTERVAL : Duration = Duration::from_secs(30);
+
+/// The modification times the watcher compares between two polls.
+type Times = HashMap<PathBuf, Option<SystemTime>>;
+
+fn modified(path : &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn file_times(files : &[PathBuf]) -> Times {
+    files.iter().map(|file| (file.clone(), modified(file))).collect()
+}
+
+fn dir_times(roots : &[PathBuf]) -> Times {
+    let mut times = HashMap::new();
+    for root in roots {
+        let dirs = walkdir::WalkDir::new(root).into_iter()
+            .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir());
+        for dir in dirs {
+            times.insert(dir.path().to_path_buf(), dir.metadata().ok().and_then(|m| m.modified().ok()));
+        }
+    }
+    times
+}
+
+/// Calls `changed` whenever `files` or the directories below `roots` change;
+/// never returns. `files` are checked every `interval`, the directories
+/// after twice as long each time they were found unchanged, up to
+/// `MAX_WALK_INTERVAL`, and every `interval` again after a change.
+pub fn watch(files : &[PathBuf], roots : &[PathBuf], interval : Duration, mut changed : impl FnMut()) -> ! {
+    let (mut last_files, mut last_dirs) = (file_times(files), dir_times(roots));
+    let mut walk_interval = interval;
+    let mut walked = Instant::now();
+    loop {
+        std::thread::sleep(interval);
+        let current_files = file_times(files);
+        let mut is_changed = current_files != last_files;
+        last_files = current_files;
+        if walked.elapsed() >= walk_interval {
+            let current_dirs = dir_times(roots);
+            walked = Instant::now();
+            if current_dirs != last_dirs {
+                last_dirs = current_dirs;
+                is_changed = true;
+            } else {
+                walk_interval = (walk_interval * 2).min(MAX_WALK_INTERVAL.max(interval));
+            }
+        }
+        if is_changed {
+            tracing::debug!("watched files changed");
+            walk_interval = interval;
+            changed();
+        }
+    }
+}